@@ -0,0 +1,514 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::calc::CalculatorOption;
+use crate::models::CsvRef;
+use crate::{Calculator, GcalcError, GcalcResult, PlotTarget};
+
+const DEFAULT_OUTPUT_DIR: &str = "batch";
+const INDEX_FILE: &str = "index.json";
+
+/// Manifest of scenarios
+///
+/// Each scenario is parsed only when it runs, thus an invalid scenario fails by itself.
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    output_dir: Option<PathBuf>,
+    #[serde(default)]
+    scenarios: Vec<Value>,
+}
+
+/// Named calculation of a manifest
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+    name: String,
+    command: Command,
+    /// Path of an option file or an embedded option
+    #[serde(default)]
+    option: Option<Value>,
+    /// Table path relative to an output directory, "<name>.<extension of format>" by default
+    #[serde(default)]
+    output: Option<PathBuf>,
+    /// Counts to execute for a range command
+    #[serde(default)]
+    count: Option<usize>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum Command {
+    Range,
+    Cond,
+    Qual,
+}
+
+impl Command {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Range => "range",
+            Self::Cond => "cond",
+            Self::Qual => "qual",
+        }
+    }
+}
+
+/// Result of a scenario in an index
+#[derive(Serialize)]
+struct ScenarioResult {
+    name: String,
+    command: Option<&'static str>,
+    status: &'static str,
+    output: Option<PathBuf>,
+    chart: Option<PathBuf>,
+    error: Option<String>,
+}
+
+/// Run scenarios of a manifest and write outputs into a directory with an index
+///
+/// Relative paths of a manifest, i.e. an output directory, option files and references, are
+/// resolved against a directory of the manifest.
+pub(crate) struct Batch {
+    manifest: Manifest,
+    base_dir: PathBuf,
+}
+
+impl Batch {
+    /// Read a manifest, which is toml if an extension is toml and json otherwise
+    pub fn from_file(path: &Path) -> GcalcResult<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let is_toml = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+        let manifest = if is_toml {
+            toml::from_str(&text).map_err(|err| err.to_string())
+        } else {
+            serde_json::from_str(&text).map_err(|err| err.to_string())
+        }
+        .map_err(|err| {
+            GcalcError::InvalidConversion(format!(
+                "Manifest \"{}\" is not valid : {}",
+                path.display(),
+                err
+            ))
+        })?;
+        let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(Self { manifest, base_dir })
+    }
+
+    /// Run every scenario, where a failed scenario doesn't stop others
+    ///
+    /// Output directory is given directory, one of a manifest or "batch" in order. This fails
+    /// after every scenario has run if any scenario has failed.
+    pub fn run(&self, output_dir: Option<&Path>) -> GcalcResult<()> {
+        let dir = output_dir
+            .map(|dir| dir.to_owned())
+            .or_else(|| {
+                self.manifest
+                    .output_dir
+                    .as_ref()
+                    .map(|dir| self.base_dir.join(dir))
+            })
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_OUTPUT_DIR));
+        std::fs::create_dir_all(&dir)?;
+
+        let mut names = HashSet::new();
+        let mut paths = HashSet::new();
+        let mut results = vec![];
+        for (index, value) in self.manifest.scenarios.iter().enumerate() {
+            // Name is needed for an index even if a scenario is invalid
+            let name = value
+                .get("name")
+                .and_then(|name| name.as_str())
+                .map(|name| name.to_owned())
+                .unwrap_or_else(|| format!("#{}", index + 1));
+            let result = if names.insert(name.clone()) {
+                self.run_scenario(value, &dir, &mut paths)
+            } else {
+                Err(GcalcError::InvalidArgument(format!(
+                    "Scenario name \"{}\" is duplicate",
+                    name
+                )))
+            };
+            results.push(match result {
+                Ok((command, output, chart)) => ScenarioResult {
+                    name,
+                    command: Some(command.name()),
+                    status: "ok",
+                    output: Some(output),
+                    chart,
+                    error: None,
+                },
+                Err(err) => ScenarioResult {
+                    name,
+                    command: value
+                        .get("command")
+                        .and_then(|command| serde_json::from_value::<Command>(command.clone()).ok())
+                        .map(|command| command.name()),
+                    status: "failed",
+                    output: None,
+                    chart: None,
+                    error: Some(err.to_string()),
+                },
+            });
+        }
+
+        let index = dir.join(INDEX_FILE);
+        let json = serde_json::to_string_pretty(&serde_json::json!({ "scenarios": &results }))
+            .map_err(|err| GcalcError::FormatFail(Box::new(err)))?;
+        std::fs::write(&index, json)?;
+
+        let mut stdout = std::io::stdout();
+        for result in &results {
+            match (&result.output, &result.error) {
+                (Some(output), _) => writeln!(
+                    stdout,
+                    "{:<6}  {} -> {}",
+                    result.status,
+                    result.name,
+                    output.display()
+                )?,
+                (None, error) => writeln!(
+                    stdout,
+                    "{:<6}  {} : {}",
+                    result.status,
+                    result.name,
+                    error.as_deref().unwrap_or_default().replace('\n', " ")
+                )?,
+            }
+        }
+
+        let failed = results.iter().filter(|r| r.error.is_some()).count();
+        if failed > 0 {
+            return Err(GcalcError::BatchError(format!(
+                "{} of {} scenario(s) failed, see \"{}\"",
+                failed,
+                results.len(),
+                index.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Run a scenario and return a command, a table path and a chart path if any
+    ///
+    /// Paths are paths written by previous scenarios, which a scenario should not overwrite.
+    fn run_scenario(
+        &self,
+        value: &Value,
+        dir: &Path,
+        paths: &mut HashSet<PathBuf>,
+    ) -> GcalcResult<(Command, PathBuf, Option<PathBuf>)> {
+        let Scenario {
+            name,
+            command,
+            option,
+            output,
+            count,
+        } = serde_json::from_value(value.clone()).map_err(|err| {
+            GcalcError::InvalidConversion(format!("Scenario is not valid : {}", err))
+        })?;
+        // Name is a file name of outputs
+        if name.is_empty() || name == ".." || name.contains(['/', '\\']) {
+            return Err(GcalcError::InvalidArgument(format!(
+                "Scenario name \"{}\" cannot be used as a file name",
+                name
+            )));
+        }
+
+        // Output should stay in an output directory, thus it has only names of directories and a file
+        let output = match output {
+            Some(output) => {
+                let invalid = || {
+                    GcalcError::InvalidArgument(format!(
+                        "Output \"{}\" should be a relative path within an output directory",
+                        output.display()
+                    ))
+                };
+                let mut path = PathBuf::new();
+                for component in output.components() {
+                    match component {
+                        Component::Normal(name) => path.push(name),
+                        Component::CurDir => (),
+                        _ => return Err(invalid()),
+                    }
+                }
+                if path.as_os_str().is_empty() {
+                    return Err(invalid());
+                }
+                Some(path)
+            }
+            None => None,
+        };
+
+        // Reference of an option file is relative to the option file
+        let (option, option_dir) = match option {
+            Some(Value::String(path)) => {
+                let path = self.base_dir.join(path);
+                if !path.is_file() {
+                    return Err(GcalcError::InvalidArgument(format!(
+                        "Option file \"{}\" doesn't exist",
+                        path.display()
+                    )));
+                }
+                let option_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                (CalculatorOption::from_file(&path)?, option_dir)
+            }
+            Some(value) => (
+                serde_json::from_value(value).map_err(|err| {
+                    GcalcError::InvalidConversion(format!("Option is not valid : {}", err))
+                })?,
+                self.base_dir.clone(),
+            ),
+            None => (CalculatorOption::new(), self.base_dir.clone()),
+        };
+        let mut cal = Calculator::new()?.try_option(&option)?;
+        if let Some(reference) = cal.reference_file() {
+            let reference = option_dir.join(reference);
+            cal.set_csv_file(CsvRef::File(reference));
+        }
+
+        let output = dir.join(output.unwrap_or_else(|| {
+            PathBuf::from(format!(
+                "{}.{}",
+                name,
+                cal.current_table_format().extension()
+            ))
+        }));
+        Self::reserve(paths, dir, &output)?;
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        cal.set_out_file(&output);
+
+        // Chart is written next to a table, while a terminal chart is not a file
+        let mut chart = None;
+        if cal.plot_enabled() {
+            let attribute = cal.chart_attribute().clone();
+            if attribute.target == PlotTarget::Terminal {
+                cal.set_plot(false);
+            } else if !matches!(command, Command::Qual) {
+                let path = dir.join(format!("{}.{}", name, attribute.image_format().extension()));
+                Self::reserve(paths, dir, &path)?;
+                cal.set_plot_attribute(attribute.path(&path))?;
+                chart.replace(path);
+            }
+        }
+
+        match command {
+            Command::Range => cal.print_range(count, None)?,
+            Command::Cond => cal.print_conditional()?,
+            Command::Qual => cal.print_qualfication()?,
+        }
+        Ok((command, output, chart))
+    }
+
+    /// Add a path to be written, which is neither an index nor an output of another scenario
+    fn reserve(paths: &mut HashSet<PathBuf>, dir: &Path, path: &Path) -> GcalcResult<()> {
+        if path == dir.join(INDEX_FILE) {
+            return Err(GcalcError::InvalidArgument(format!(
+                "Output \"{}\" is reserved for an index",
+                path.display()
+            )));
+        }
+        if !paths.insert(path.to_owned()) {
+            return Err(GcalcError::InvalidArgument(format!(
+                "Output \"{}\" is already written by another scenario",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty directory of a test, where a manifest is written
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gcalc-batch-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Run a manifest in a directory and return a result with scenarios of an index
+    fn run(dir: &Path, manifest: Value) -> (GcalcResult<()>, Vec<Value>) {
+        let path = dir.join("manifest.json");
+        std::fs::write(&path, manifest.to_string()).unwrap();
+        let result = Batch::from_file(&path).unwrap().run(None);
+        let index = std::fs::read_to_string(dir.join("out").join(INDEX_FILE)).unwrap();
+        let index: Value = serde_json::from_str(&index).unwrap();
+        (result, index["scenarios"].as_array().unwrap().clone())
+    }
+
+    fn statuses(scenarios: &[Value]) -> Vec<(&str, &str)> {
+        scenarios
+            .iter()
+            .map(|s| (s["name"].as_str().unwrap(), s["status"].as_str().unwrap()))
+            .collect()
+    }
+
+    fn scenario(name: &str, output: &str) -> Value {
+        serde_json::json!({
+            "name": name,
+            "command": "range",
+            "count": 2,
+            "option": { "probability": 0.5 },
+            "output": output,
+        })
+    }
+
+    #[test]
+    fn outputs_stay_within_output_directory() {
+        let dir = temp_dir("output");
+        let (result, scenarios) = run(
+            &dir,
+            serde_json::json!({
+                "output_dir": "out",
+                "scenarios": [
+                    scenario("nested", "./tables/nested.csv"),
+                    scenario("parent", "../parent.csv"),
+                    scenario("absolute", "/absolute.csv"),
+                    scenario("current", "."),
+                    scenario("a/b", "name.csv"),
+                ],
+            }),
+        );
+        assert!(matches!(result, Err(GcalcError::BatchError(_))));
+        assert_eq!(
+            statuses(&scenarios),
+            [
+                ("nested", "ok"),
+                ("parent", "failed"),
+                ("absolute", "failed"),
+                ("current", "failed"),
+                ("a/b", "failed"),
+            ]
+        );
+        // Directories of an output are created
+        assert!(dir.join("out/tables/nested.csv").is_file());
+        assert!(!dir.join("parent.csv").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn duplicate_names_and_outputs_fail() {
+        let dir = temp_dir("duplicate");
+        let (result, scenarios) = run(
+            &dir,
+            serde_json::json!({
+                "output_dir": "out",
+                "scenarios": [
+                    scenario("first", "table.csv"),
+                    scenario("first", "other.csv"),
+                    scenario("second", "table.csv"),
+                    scenario("index", "index.json"),
+                ],
+            }),
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            statuses(&scenarios),
+            [
+                ("first", "ok"),
+                ("first", "failed"),
+                ("second", "failed"),
+                ("index", "failed"),
+            ]
+        );
+        assert!(scenarios[1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("duplicate"));
+        assert!(scenarios[2]["error"]
+            .as_str()
+            .unwrap()
+            .contains("already written"));
+        // Index is not overwritten by a scenario
+        assert!(scenarios[3]["error"].as_str().unwrap().contains("reserved"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_scenarios_are_listed_in_index() {
+        let dir = temp_dir("failed");
+        let (result, scenarios) = run(
+            &dir,
+            serde_json::json!({
+                "output_dir": "out",
+                "scenarios": [
+                    scenario("ok", "ok.csv"),
+                    { "name": "unreachable", "command": "cond", "option": { "probability": 0.0, "target": 0.5 } },
+                    { "name": "unknown", "command": "launch" },
+                    { "command": "range", "option": "missing.json" },
+                ],
+            }),
+        );
+        match result {
+            Err(GcalcError::BatchError(message)) => {
+                assert!(message.starts_with("3 of 4 scenario(s) failed"))
+            }
+            _ => panic!("batch should fail"),
+        }
+
+        assert_eq!(
+            scenarios[0]["output"],
+            dir.join("out/ok.csv").to_str().unwrap()
+        );
+        assert!(scenarios[0]["error"].is_null());
+        // Command is kept if it is valid, and a missing name is a position
+        assert_eq!(scenarios[1]["command"], "cond");
+        assert!(scenarios[2]["command"].is_null());
+        assert_eq!(scenarios[3]["name"], "#4");
+        for scenario in &scenarios[1..] {
+            assert_eq!(scenario["status"], "failed");
+            assert!(scenario["output"].is_null());
+            assert!(scenario["error"].is_string());
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reference_of_option_file_is_relative_to_option_file() {
+        let dir = temp_dir("reference");
+        let options = dir.join("options");
+        std::fs::create_dir_all(&options).unwrap();
+        std::fs::write(
+            options.join("ref.csv"),
+            "count,prob,cost,constant\n1,0.5,10,0\n2,0.5,10,0\n",
+        )
+        .unwrap();
+        std::fs::write(
+            options.join("option.json"),
+            r#"{ "csv_ref": { "File": "ref.csv" }, "format": "CSV" }"#,
+        )
+        .unwrap();
+
+        let (result, scenarios) = run(
+            &dir,
+            serde_json::json!({
+                "output_dir": "out",
+                "scenarios": [
+                    { "name": "file", "command": "range", "count": 2, "option": "options/option.json" },
+                    {
+                        "name": "embedded",
+                        "command": "range",
+                        "option": { "csv_ref": { "File": "options/ref.csv" } },
+                    },
+                ],
+            }),
+        );
+        result.unwrap();
+        assert_eq!(statuses(&scenarios), [("file", "ok"), ("embedded", "ok")]);
+        let table = std::fs::read_to_string(dir.join("out/file.csv")).unwrap();
+        assert_eq!(table.lines().count(), 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -6,8 +6,9 @@ fn main() -> GcalcResult<()> {
     #[cfg(feature = "binary")]
     if let Err(err) = Cli::run() {
         use std::io::Write;
-        // Propagate error to stdout
+        // Propagate error to stderr and exit with failure
         writeln!(std::io::stderr(), "{}", err)?;
+        std::process::exit(1);
     }
     Ok(())
 }
@@ -8,34 +8,80 @@ use cindex::{Indexer, Query};
 
 use crate::consts::*;
 use crate::formatter::{QualFormatter, RecordFormatter};
+#[cfg(any(feature = "plotters", feature = "json"))]
+use crate::models::Annotation;
 use crate::models::{
-    CSVInvalidBehaviour, CsvRef, OutOption, ProbType, Qualficiation, Record, RecordCursor,
+    CSVInvalidBehaviour, ColumnKind, CsvDialect, CsvFallback, CsvRecordBehaviour, CsvRef,
+    CurrencyFormat, InputSummary, NumberFormat, OutOption, OutputColumn, PlotAttribute, PlotFormat,
+    PlotTarget, ProbType, Record, RecordCursor, RecordFilter, RoundingMode,
 };
 #[cfg(feature = "plotters")]
-use crate::plot::{PlotAttribute, Renderer};
+use crate::plot::Renderer;
+use crate::sweep::SweepMetric;
+use crate::term::TermRenderer;
 use crate::utils;
+#[cfg(feature = "json")]
+use crate::vega::VegaRenderer;
 use crate::{GcalcError, GcalcResult};
 #[cfg(feature = "option")]
 use serde::{Deserialize, Serialize};
 
+/// Options of a calculator, where a missing field has a default value
 #[cfg(feature = "option")]
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct CalculatorOption {
     count: usize,
     prob_type: ProbType,
     prob_precision: Option<usize>,
+    #[serde(default)]
+    rounding: RoundingMode,
+    #[serde(default)]
+    significant: Option<usize>,
+    #[serde(default)]
+    cost_precision: Option<usize>,
+    #[serde(default)]
+    currency: CurrencyFormat,
+    // Inputs of a calculation, which arguments override
+    #[serde(default)]
+    probability: Option<f32>,
+    #[serde(default)]
+    constant: Option<f32>,
+    #[serde(default)]
+    cost: Option<f32>,
     budget: Option<f32>,
+    #[serde(default)]
+    pity: Option<usize>,
     fallback: CSVInvalidBehaviour,
+    #[serde(default)]
+    column_fallback: HashMap<String, CSVInvalidBehaviour>,
+    #[serde(default)]
+    column_default: HashMap<String, f32>,
     no_header: bool,
     strict: bool,
+    #[serde(default)]
+    cycle: bool,
+    #[serde(default)]
+    stop: bool,
+    #[serde(default)]
+    strict_prob: bool,
     target: Option<f32>,
+    #[serde(default)]
+    targets: Vec<f32>,
     value: Option<f32>,
     // Non-wasm exclusive options
     format: TableFormat,
     csv_ref: CsvRef, // -> For wasm it should be defined differently
     out_option: OutOption,
     column_map: HashMap<String, String>,
-    plot: bool,
+    #[serde(deserialize_with = "deserialize_plot")]
+    plot: PlotOption,
+    #[serde(default)]
+    filter: RecordFilter,
+    #[serde(default)]
+    dialect: CsvDialect,
+    #[serde(default)]
+    columns: Vec<OutputColumn>,
 }
 
 #[cfg(feature = "option")]
@@ -58,18 +104,38 @@ impl CalculatorOption {
             count: 0,
             prob_type: ProbType::Fraction,
             prob_precision: None,
+            rounding: RoundingMode::Truncate,
+            significant: None,
+            cost_precision: None,
+            currency: CurrencyFormat::new(),
+            probability: None,
+            constant: None,
+            cost: None,
             budget: None,
+            pity: None,
             fallback: CSVInvalidBehaviour::None,
+            column_fallback: HashMap::new(),
+            column_default: HashMap::new(),
             no_header: false,
             strict: false,
+            cycle: false,
+            stop: false,
+            strict_prob: false,
             target: None,
+            targets: vec![],
             value: None,
             column_map,
             // Non-wasm exclusive options
             format: TableFormat::CSV,
             csv_ref: CsvRef::None, // -> For wasm it should be defined differently
             out_option: OutOption::Console,
-            plot: false,
+            plot: PlotOption {
+                enabled: false,
+                attribute: PlotAttribute::new(),
+            },
+            filter: RecordFilter::new(),
+            dialect: CsvDialect::new(),
+            columns: vec![],
         }
     }
 
@@ -77,13 +143,53 @@ impl CalculatorOption {
         Ok(serde_json::to_string_pretty(self).expect("Failed to serialize option to json"))
     }
 
+    pub fn from_json(json: &str) -> GcalcResult<Self> {
+        serde_json::from_str(json).map_err(|err| {
+            GcalcError::InvalidConversion(format!("Option is not a valid json : {}", err))
+        })
+    }
+
     pub fn from_file(path: &std::path::Path) -> GcalcResult<Self> {
-        let option = serde_json::from_str(&std::fs::read_to_string(path)?)
-            .expect("Failed to read option file");
-        Ok(option)
+        Self::from_json(&std::fs::read_to_string(path)?).map_err(|err| match err {
+            GcalcError::InvalidConversion(err) => {
+                GcalcError::InvalidConversion(format!("{} in \"{}\"", err, path.display()))
+            }
+            err => err,
+        })
     }
 }
 
+/// Plot section of an option file
+#[cfg(feature = "option")]
+#[derive(Serialize, Deserialize)]
+struct PlotOption {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(flatten)]
+    attribute: PlotAttribute,
+}
+
+/// Plot section can also be a boolean, which was the only form before plot attributes
+#[cfg(feature = "option")]
+fn deserialize_plot<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<PlotOption, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PlotField {
+        Flag(bool),
+        Section(Box<PlotOption>),
+    }
+
+    Ok(match PlotField::deserialize(deserializer)? {
+        PlotField::Flag(enabled) => PlotOption {
+            enabled,
+            attribute: PlotAttribute::new(),
+        },
+        PlotField::Section(section) => *section,
+    })
+}
+
 // TODO
 // Csv file
 pub struct Calculator {
@@ -95,21 +201,44 @@ pub struct Calculator {
     csv_ref: CsvRef,
     csv_no_header: bool,
     column_map: HashMap<String, String>,
-    csv_invalid_behaviour: CSVInvalidBehaviour,
+    filter: RecordFilter,
+    dialect: CsvDialect,
+    csv_fallback: CsvFallback,
+    // Output columns, every column if empty
+    columns: Vec<OutputColumn>,
     prob_precision: Option<usize>,
+    rounding: RoundingMode,
+    // Minimum significant digits of small numbers
+    significant: Option<usize>,
+    // Precision of cost and value, printed as they are if none
+    cost_precision: Option<usize>,
+    currency: CurrencyFormat,
     budget: Option<f32>,
+    // Count of a guaranteed success
+    pity: Option<usize>,
     target_probability: Option<f32>,
+    // Milestone targets in ascending order
+    targets: Vec<f32>,
     target_value: Option<f32>,
     prob_type: ProbType,
     // Which behaviour to take when csv rows ends
     record_behaviour: CsvRecordBehaviour, // Strict option
+    // Reject ambiguous bare probability numbers
+    strict_prob: bool,
     out_option: OutOption,
     plot: bool,
+    plot_attribute: PlotAttribute,
 }
 impl Calculator {
     // <BUILDER>
     // Constructor methods
     pub fn new() -> GcalcResult<Self> {
+        let column_map = HashMap::from_iter(
+            ["count", "prob", "cost", "constant"]
+                .iter()
+                .map(|v| (v.to_string(), v.to_string())),
+        );
+
         Ok(Self {
             indexer: Indexer::new(),
             state: CalcState::new(),
@@ -117,38 +246,50 @@ impl Calculator {
             offset: None,
             csv_ref: CsvRef::None,
             csv_no_header: false,
-            column_map: HashMap::new(),
+            column_map,
+            filter: RecordFilter::new(),
+            dialect: CsvDialect::new(),
             format: TableFormat::CSV,
-            csv_invalid_behaviour: CSVInvalidBehaviour::None,
+            csv_fallback: CsvFallback::new(CSVInvalidBehaviour::None),
+            columns: vec![],
             prob_precision: None,
+            rounding: RoundingMode::Truncate,
+            significant: None,
+            cost_precision: None,
+            currency: CurrencyFormat::new(),
             target_probability: None,
+            targets: vec![],
             target_value: None,
             budget: None,
+            pity: None,
             prob_type: ProbType::Fraction,
             record_behaviour: CsvRecordBehaviour::Repeat,
+            strict_prob: false,
             out_option: OutOption::Console,
             plot: false,
+            plot_attribute: PlotAttribute::new(),
         })
     }
 
+    /// Apply an option
+    ///
+    /// # Panics
+    ///
+    /// Panics if an option has an invalid value, use [`Calculator::try_option`] instead to
+    /// handle it.
     #[cfg(feature = "option")]
     pub fn option(mut self, option: &CalculatorOption) -> Self {
-        self.count = option.count;
-        self.prob_type = option.prob_type;
-        self.prob_precision = option.prob_precision;
-        self.budget = option.budget;
-        self.csv_invalid_behaviour = option.fallback;
-        self.csv_no_header = option.no_header;
-        self.set_strict_csv(option.strict);
-        self.target_probability = option.target;
-        self.target_value = option.value;
-        self.column_map = option.column_map.clone();
-        self.format = option.format;
-        self.csv_ref = option.csv_ref.clone();
-        self.out_option = option.out_option.clone();
+        self.set_option(option);
         self
     }
 
+    /// Apply an option, which fails if an option has an invalid value
+    #[cfg(feature = "option")]
+    pub fn try_option(mut self, option: &CalculatorOption) -> GcalcResult<Self> {
+        self.try_set_option(option)?;
+        Ok(self)
+    }
+
     pub fn no_header(mut self, tv: bool) -> Self {
         self.csv_no_header = tv;
         self
@@ -159,6 +300,21 @@ impl Calculator {
         self
     }
 
+    pub fn filter(mut self, filter: RecordFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn dialect(mut self, dialect: CsvDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    pub fn columns(mut self, columns: Vec<OutputColumn>) -> Self {
+        self.columns = columns;
+        self
+    }
+
     pub fn strict_csv(mut self, tv: bool) -> Self {
         if tv {
             self.record_behaviour = CsvRecordBehaviour::Panic
@@ -168,11 +324,21 @@ impl Calculator {
         self
     }
 
+    pub fn record_behaviour(mut self, behaviour: CsvRecordBehaviour) -> Self {
+        self.record_behaviour = behaviour;
+        self
+    }
+
     pub fn target_probability(mut self, target_probability: f32) -> Self {
         self.target_probability.replace(target_probability);
         self
     }
 
+    pub fn targets(mut self, targets: Vec<f32>) -> GcalcResult<Self> {
+        self.set_targets(targets)?;
+        Ok(self)
+    }
+
     pub fn value(mut self, value: f32) -> Self {
         self.target_value.replace(value);
         self
@@ -183,6 +349,11 @@ impl Calculator {
         self
     }
 
+    pub fn pity(mut self, count: usize) -> Self {
+        self.pity.replace(count);
+        self
+    }
+
     pub fn table_format(mut self, format: TableFormat) -> Self {
         self.format = format;
         self
@@ -207,11 +378,36 @@ impl Calculator {
         Ok(self)
     }
 
+    pub fn strict_prob(mut self, tv: bool) -> Self {
+        self.strict_prob = tv;
+        self
+    }
+
     pub fn precision(mut self, precision: usize) -> Self {
         self.prob_precision.replace(precision);
         self
     }
 
+    pub fn rounding(mut self, rounding: RoundingMode) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    pub fn significant(mut self, digits: usize) -> Self {
+        self.significant.replace(digits);
+        self
+    }
+
+    pub fn cost_precision(mut self, precision: usize) -> Self {
+        self.cost_precision.replace(precision);
+        self
+    }
+
+    pub fn currency(mut self, currency: CurrencyFormat) -> GcalcResult<Self> {
+        self.set_currency(currency)?;
+        Ok(self)
+    }
+
     pub fn csv_ref(mut self, csv_reference: CsvRef) -> Self {
         self.csv_ref = csv_reference;
         self
@@ -229,7 +425,12 @@ impl Calculator {
     }
 
     pub fn csv_fallback(mut self, behaviour: &str) -> GcalcResult<Self> {
-        self.csv_invalid_behaviour = CSVInvalidBehaviour::from_str(behaviour)?;
+        self.csv_fallback.set_behaviours(behaviour)?;
+        Ok(self)
+    }
+
+    pub fn csv_default(mut self, defaults: &str) -> GcalcResult<Self> {
+        self.csv_fallback.set_defaults(defaults, self.strict_prob)?;
         Ok(self)
     }
 
@@ -237,30 +438,102 @@ impl Calculator {
         self.plot = tv;
         self
     }
+
+    pub fn plot_attribute(mut self, attribute: PlotAttribute) -> GcalcResult<Self> {
+        self.set_plot_attribute(attribute)?;
+        Ok(self)
+    }
     // </BUILDER>
 
     // <SETTER>
+    /// Apply an option
+    ///
+    /// # Panics
+    ///
+    /// Panics if an option has an invalid value, use [`Calculator::try_set_option`] instead to
+    /// handle it.
     #[cfg(feature = "option")]
     pub fn set_option(&mut self, option: &CalculatorOption) {
+        if let Err(err) = self.try_set_option(option) {
+            panic!("Failed to apply option : {}", err);
+        }
+    }
+
+    /// Apply an option, which fails if an option has an invalid value
+    ///
+    /// Values applied before an invalid value are kept.
+    #[cfg(feature = "option")]
+    pub fn try_set_option(&mut self, option: &CalculatorOption) -> GcalcResult<()> {
         self.count = option.count;
         self.prob_type = option.prob_type;
         self.prob_precision = option.prob_precision;
+        self.rounding = option.rounding;
+        self.significant = option.significant;
+        self.cost_precision = option.cost_precision;
+        self.set_currency(option.currency.clone())?;
+        if let Some(probability) = option.probability {
+            self.set_probability(probability, true)?;
+        }
+        if let Some(constant) = option.constant {
+            self.set_constant(constant, true)?;
+        }
+        if let Some(cost) = option.cost {
+            self.set_cost(cost, true);
+        }
         self.budget = option.budget;
-        self.csv_invalid_behaviour = option.fallback;
+        self.pity = option.pity;
+        self.csv_fallback = CsvFallback::new(option.fallback);
+        for (column, behaviour) in &option.column_fallback {
+            self.csv_fallback.column_mut(column)?.behaviour = *behaviour;
+        }
+        for (column, value) in &option.column_default {
+            self.csv_fallback.set_default(column, *value)?;
+        }
         self.csv_no_header = option.no_header;
-        self.set_strict_csv(option.strict);
+        self.record_behaviour = if option.strict {
+            CsvRecordBehaviour::Panic
+        } else if option.stop {
+            CsvRecordBehaviour::Stop
+        } else if option.cycle {
+            CsvRecordBehaviour::Cycle
+        } else {
+            CsvRecordBehaviour::Repeat
+        };
+        self.strict_prob = option.strict_prob;
         self.target_probability = option.target;
+        self.set_targets(option.targets.clone())?;
         self.target_value = option.value;
-        self.column_map = option.column_map.clone();
+        // Columns which are not mapped keep their names
+        for (column, mapped) in &option.column_map {
+            self.column_map.insert(column.clone(), mapped.clone());
+        }
+        self.filter = option.filter.clone();
+        self.dialect = option.dialect.clone();
+        self.columns = option.columns.clone();
         self.format = option.format;
         self.csv_ref = option.csv_ref.clone();
         self.out_option = option.out_option.clone();
+        self.plot = option.plot.enabled;
+        self.set_plot_attribute(option.plot.attribute.clone())?;
+        Ok(())
     }
 
     pub fn set_column_map(&mut self, column_map: HashMap<String, String>) {
         self.column_map = column_map;
     }
 
+    pub fn set_filter(&mut self, filter: RecordFilter) {
+        self.filter = filter;
+    }
+
+    pub fn set_dialect(&mut self, dialect: CsvDialect) {
+        self.dialect = dialect;
+    }
+
+    pub fn set_columns(&mut self, columns: Vec<OutputColumn>) {
+        self.columns = columns;
+    }
+
     pub fn set_no_header(&mut self, tv: bool) {
         self.csv_no_header = tv;
     }
@@ -302,10 +575,18 @@ impl Calculator {
         }
     }
 
+    pub fn set_record_behaviour(&mut self, behaviour: CsvRecordBehaviour) {
+        self.record_behaviour = behaviour;
+    }
+
+    pub fn clear_target_probability(&mut self) {
+        self.target_probability = None;
+    }
+
     pub fn set_target_probability(&mut self, target_probability: f32) -> GcalcResult<()> {
         if !(0.0f32..=1.0f32).contains(&target_probability) {
             return Err(GcalcError::InvalidArgument(format!(
-                "Given probability \"{}\" is should be bigger than 0.0 and smaller than 1.0",
+                "Given probability \"{}\" should be within 0.0 ~ 1.0",
                 target_probability
             )));
         }
@@ -314,6 +595,22 @@ impl Calculator {
         Ok(())
     }
 
+    /// Set milestone targets
+    ///
+    /// Each target yields a row of count and cost that reaches the target.
+    pub fn set_targets(&mut self, mut targets: Vec<f32>) -> GcalcResult<()> {
+        if let Some(target) = targets.iter().find(|t| !(0.0f32..=1.0f32).contains(*t)) {
+            return Err(GcalcError::InvalidArgument(format!(
+                "Given target \"{}\" should be within 0.0 ~ 1.0",
+                target
+            )));
+        }
+        targets.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        targets.dedup();
+        self.targets = targets;
+        Ok(())
+    }
+
     pub fn set_value(&mut self, value: f32) {
         self.target_value.replace(value);
     }
@@ -322,6 +619,19 @@ impl Calculator {
         self.budget.replace(budget);
     }
 
+    pub fn clear_budget(&mut self) {
+        self.budget = None;
+    }
+
+    /// Set a count where success is guaranteed
+    pub fn set_pity(&mut self, count: usize) {
+        self.pity.replace(count);
+    }
+
+    pub fn clear_pity(&mut self) {
+        self.pity = None;
+    }
+
     pub fn set_offset(&mut self, offset: usize) {
         self.offset.replace(offset);
     }
@@ -334,10 +644,35 @@ impl Calculator {
         self.prob_type = prob_type;
     }
 
+    pub fn set_strict_prob(&mut self, tv: bool) {
+        self.strict_prob = tv;
+    }
+
     pub fn set_precision(&mut self, precision: usize) {
         self.prob_precision.replace(precision);
     }
 
+    pub fn set_rounding(&mut self, rounding: RoundingMode) {
+        self.rounding = rounding;
+    }
+
+    pub fn set_significant(&mut self, digits: usize) {
+        self.significant.replace(digits);
+    }
+
+    pub fn set_cost_precision(&mut self, precision: usize) {
+        self.cost_precision.replace(precision);
+    }
+
+    /// Set currency format of cost and value
+    ///
+    /// This fails if a thousands separator or a conversion rate is invalid.
+    pub fn set_currency(&mut self, currency: CurrencyFormat) -> GcalcResult<()> {
+        currency.validate()?;
+        self.currency = currency;
+        Ok(())
+    }
+
     pub fn set_csv_file(&mut self, csv_reference: CsvRef) {
         self.csv_ref = csv_reference;
     }
@@ -346,123 +681,258 @@ impl Calculator {
         self.out_option = OutOption::File(path.to_owned());
     }
 
+    /// Set fallback behaviours, either for every column (e.g. "rollback") or per column (e.g.
+    /// "prob=none,constant=rollback")
     pub fn set_csv_value_fallback(&mut self, behaviour: &str) -> GcalcResult<()> {
-        self.csv_invalid_behaviour = CSVInvalidBehaviour::from_str(behaviour)?;
+        self.csv_fallback.set_behaviours(behaviour)?;
+        Ok(())
+    }
+
+    /// Set default values for empty cells (e.g. "cost=0,constant=0")
+    pub fn set_csv_value_default(&mut self, defaults: &str) -> GcalcResult<()> {
+        self.csv_fallback.set_defaults(defaults, self.strict_prob)?;
         Ok(())
     }
 
     pub fn set_plot(&mut self, tv: bool) {
         self.plot = tv;
     }
+
+    /// Set attributes of a chart
+    ///
+    /// This fails if a colour or a size is invalid.
+    pub fn set_plot_attribute(&mut self, attribute: PlotAttribute) -> GcalcResult<()> {
+        attribute.validate()?;
+        self.plot_attribute = attribute;
+        Ok(())
+    }
     // </SETTER>
 
     // <PROCESSING>
+    /// Parse probability notation (e.g. "1/200", "0.5%") with calculator's strictness
+    pub fn parse_probability(&self, text: &str) -> GcalcResult<f32> {
+        utils::parse_probability(text, self.strict_prob)
+    }
+
+    /// Current configuration as an option, which can be saved as an option file
+    #[cfg(feature = "option")]
+    pub fn to_option(&self) -> CalculatorOption {
+        let fallback = self.csv_fallback.probability.behaviour;
+        let columns = [
+            ("prob", &self.csv_fallback.probability, None),
+            ("constant", &self.csv_fallback.constant, None),
+            ("cost", &self.csv_fallback.cost, Some(0.0)),
+        ];
+        let column_fallback = columns
+            .iter()
+            .filter(|(_, column, _)| column.behaviour != fallback)
+            .map(|(name, column, _)| (name.to_string(), column.behaviour))
+            .collect();
+        // Only defaults which differ from those of a new fallback
+        let column_default = columns
+            .iter()
+            .filter(|(_, column, initial)| column.default != *initial)
+            .filter_map(|(name, column, _)| column.default.map(|value| (name.to_string(), value)))
+            .collect();
+
+        CalculatorOption {
+            count: self.count,
+            prob_type: self.prob_type,
+            prob_precision: self.prob_precision,
+            rounding: self.rounding,
+            significant: self.significant,
+            cost_precision: self.cost_precision,
+            currency: self.currency.clone(),
+            probability: Some(self.state.initial_probability),
+            constant: Some(self.state.initial_constant),
+            cost: Some(self.state.initial_cost),
+            budget: self.budget,
+            pity: self.pity,
+            fallback,
+            column_fallback,
+            column_default,
+            no_header: self.csv_no_header,
+            strict: self.record_behaviour == CsvRecordBehaviour::Panic,
+            cycle: self.record_behaviour == CsvRecordBehaviour::Cycle,
+            stop: self.record_behaviour == CsvRecordBehaviour::Stop,
+            strict_prob: self.strict_prob,
+            target: self.target_probability,
+            targets: self.targets.clone(),
+            value: self.target_value,
+            format: self.format,
+            csv_ref: self.csv_ref.clone(),
+            out_option: self.out_option.clone(),
+            column_map: self.column_map.clone(),
+            plot: PlotOption {
+                enabled: self.plot,
+                attribute: self.plot_attribute.clone(),
+            },
+            filter: self.filter.clone(),
+            dialect: self.dialect.clone(),
+            columns: self.columns.clone(),
+        }
+    }
+
+    /// Summary of inputs used for a calculation
+    pub fn input_summary(&self, mode: &str) -> InputSummary {
+        InputSummary {
+            mode: mode.to_owned(),
+            probability: self.state.initial_probability,
+            constant: self.state.initial_constant,
+            cost: self.state.initial_cost,
+            count: if mode == "range" {
+                Some(self.count)
+            } else {
+                None
+            },
+            budget: self.budget,
+            pity: self.pity,
+            target: self.target_probability,
+            targets: self.targets.clone(),
+            value: self.target_value,
+            reference: match &self.csv_ref {
+                CsvRef::File(path) => Some(path.display().to_string()),
+                CsvRef::Raw(_) => Some("<raw>".to_owned()),
+                CsvRef::None => None,
+            },
+            filter: self.filter.predicates.clone(),
+            money_rate: self.currency.rate,
+        }
+    }
+
     pub fn print_range(
         &mut self,
         count: Option<usize>,
         start_index: Option<usize>,
     ) -> GcalcResult<()> {
+        self.print_range_records(count, start_index)?;
+        Ok(())
+    }
+
+    /// Print a range and return calculated records, so that a caller can reuse them
+    pub(crate) fn print_range_records(
+        &mut self,
+        count: Option<usize>,
+        start_index: Option<usize>,
+    ) -> GcalcResult<Vec<Record>> {
         // Update count for calculation
         if let Some(count) = count {
             self.count = count;
         }
         let records = self.create_records(true)?;
-        self.print_records(&records, Some((start_index.unwrap_or(0), self.count)))?;
-        #[cfg(feature = "plotters")]
+        self.print_records(
+            &records,
+            Some((start_index.unwrap_or(0), self.count)),
+            "range",
+            ColumnKind::RECORD,
+            &records,
+        )?;
         if self.plot {
-            Renderer::draw_chart(PlotAttribute::default(), &records)?;
+            self.draw_chart(&records)?;
         }
-        Ok(())
+        Ok(records)
     }
 
     pub fn print_conditional(&mut self) -> GcalcResult<()> {
+        self.print_conditional_records()?;
+        Ok(())
+    }
+
+    /// Print a conditional calculation and return calculated records, so that a caller can
+    /// reuse them
+    pub(crate) fn print_conditional_records(&mut self) -> GcalcResult<Vec<Record>> {
         let records = self.create_records(false)?;
-        self.print_records(&records, None)?;
-        #[cfg(feature = "plotters")]
+        if self.targets.is_empty() {
+            self.print_records(&records, None, "cond", ColumnKind::RECORD, &records)?;
+        } else {
+            let milestones = self.find_milestones(&records);
+            self.print_records(&milestones, None, "cond", ColumnKind::MILESTONE, &records)?;
+        }
         if self.plot {
-            Renderer::draw_chart(PlotAttribute::default(), &records)?;
+            self.draw_chart(&records)?;
         }
-        Ok(())
+        Ok(records)
     }
 
     pub fn print_qualfication(&mut self) -> GcalcResult<()> {
+        // Closed form reads a state, which a previous calculation could have changed
+        self.state.reset();
         self.conditional_sanity_check()?;
-        let total_count: usize;
-        let total_cost: f32;
-        let final_probability: String;
-        let value: f32;
 
-        // Simply calculate geometric series
-        if let CsvRef::None = self.csv_ref {
-            if self.state.probability >= 1.0 {
-                total_count = 1;
-                total_cost = self.state.cost;
-                final_probability =
-                    utils::get_prob_as_formatted(1.0f32, &self.prob_type, &self.prob_precision);
-                value = 1.0f32 * self.target_value.unwrap_or(0f32);
-            }
-            // Probability and possibly with budget
-            else if let Some(target) = self.target_probability {
-                let count = utils::geometric_series_qual(
-                    self.state.probability + self.state.constant,
-                    target,
-                );
-                let count = if let Some(bud) = self.budget {
-                    if count as f32 * self.state.cost > bud {
-                        (bud / self.state.cost).floor() as usize
-                    } else {
-                        count
+        // Milestones
+        if !self.targets.is_empty() {
+            let milestones = if self.uses_closed_form() {
+                let mut milestones = vec![];
+                for &target in &self.targets {
+                    if !self.geometric_reaches(target) {
+                        continue;
                     }
-                } else {
-                    count
-                };
-                total_count = count;
-                total_cost = count as f32 * self.state.cost;
-                let prob = utils::geometric_series(
-                    total_count,
-                    self.state.probability + self.state.constant,
-                );
-                final_probability =
-                    utils::get_prob_as_formatted(prob, &self.prob_type, &self.prob_precision);
-                value = prob * self.target_value.unwrap_or(0f32);
-            } else {
-                // No probability only budget
-                if self.state.cost == 0f32 {
-                    return Err(GcalcError::InvalidArgument(
-                        "Cost should not be 0 if no reference was given as argument.".to_string(),
-                    ));
+                    let (count, cost, probability, value) =
+                        self.geometric_qualification(Some(target))?;
+                    milestones.push(
+                        self.qualification_record(count, cost, probability, value)
+                            .milestone(target, self.format_probability(target)),
+                    );
                 }
-                let count = (self.budget.unwrap() / self.state.cost).floor() as usize;
-
-                total_count = count;
-                total_cost = count as f32 * self.state.cost;
-                let prob = utils::geometric_series(
-                    total_count,
-                    self.state.probability + self.state.constant,
-                );
-                final_probability =
-                    utils::get_prob_as_formatted(prob, &self.prob_type, &self.prob_precision);
-                value = prob * self.target_value.unwrap_or(0f32);
-            }
+                milestones
+            } else {
+                let records = self.create_records(false)?;
+                self.find_milestones(&records)
+            };
+            return self.print_records(&milestones, None, "qual", ColumnKind::MILESTONE, &[]);
+        }
+
+        // Simply calculate geometric series
+        let qual = if self.uses_closed_form() {
+            let (count, cost, probability, value) =
+                self.geometric_qualification(self.target_probability)?;
+            self.qualification_record(count, cost, probability, value)
         } else {
             let records = self.create_records(false)?;
+            let last = records.last().ok_or_else(|| {
+                GcalcError::CsvError("Reference has no row to calculate".to_string())
+            })?;
+            // Stop behaviour ends a calculation with a reference, which is not a result
+            let reached = self
+                .stop_target()
+                .is_some_and(|target| last.probability_src > target || last.survival.0 == 0.0);
+            let exhausted = self.budget.is_some_and(|budget| last.cost > budget);
+            if !reached && !exhausted {
+                return Err(GcalcError::InvalidConditional(format!(
+                    "Reference ended at count {} before reaching a target or a budget",
+                    last.count
+                )));
+            }
+            // Calculation stops at the first record over a budget, which was not affordable
+            match self.affordable(&records).last() {
+                Some(record) => record.clone(),
+                None => self.qualification_record(0, 0.0, 0.0, 0.0),
+            }
+        };
 
-            total_count = records.len();
-
-            // It is a single record table so it is safe to index 0th element
-            let last_record = &records[0];
-            total_cost = last_record.cost;
-            final_probability = last_record.probability.clone();
-            value = last_record.probability_src * self.target_value.unwrap_or(0f32);
-        }
-
-        self.print_qual_table(total_count, total_cost, &final_probability, value)?;
+        self.print_qual_table(&qual)?;
 
         Ok(())
     }
 
+    /// Print counts and costs of common quantiles, i.e. median, 90th and 99th percentile
+    ///
+    /// Milestone targets are used instead if any. Budget still stops a calculation, thus a
+    /// quantile out of budget is omitted.
+    pub fn print_statistics(&mut self) -> GcalcResult<()> {
+        if !self.targets.is_empty() {
+            return self.print_qualfication();
+        }
+        self.targets = STATISTIC_QUANTILES.to_vec();
+        let result = self.print_qualfication();
+        self.targets.clear();
+        result
+    }
+
     /// Creat records accroding to miscellaenous states
     fn create_records(&mut self, use_range: bool) -> GcalcResult<Vec<Record>> {
+        // State of a previous calculation shouldn't affect a sanity check
+        self.state.reset();
         // "!use_range" (negation of use range) means it is used as conditional loop
         // Thus, at least one condition should be given or say sanity check
         if !use_range {
@@ -476,46 +946,23 @@ impl Calculator {
             CsvRef::None => "".to_owned(),
         };
 
-        let mut csv_records = self.index_record_from_value(&csv_value)?.into_iter();
+        let mut csv_cursor = CsvCursor::new(self.index_record_from_value(&csv_value)?);
 
         let mut records: Vec<Record> = Vec::new();
         let mut total_cost = 0f32;
         let mut record_index = 0;
-        let mut csv_index = 0;
-        let mut cursor: RecordCursor;
 
         loop {
-            // Default cursor behaviour is next
-            cursor = RecordCursor::Next;
-
-            // Only if csv value is not empty, update the state from csv value(file)
-            if !csv_value.is_empty() {
-                self.update_state_from_csv_file(&mut csv_records, csv_index, &mut cursor)?;
-            }
-            self.calculate_fail_success()?;
-
-            let prob_str = utils::get_prob_as_formatted(
-                self.state.success_until,
-                &self.prob_type,
-                &self.prob_precision,
-            );
-
-            // Because first try also consumes cost
-            // total_cost should be calculated before push
-            total_cost += self.state.cost;
-
-            records.push(Record::new(
-                record_index + 1,
-                self.state.success_until,
-                prob_str.to_owned(),
-                total_cost,
-                self.state.constant,
-                self.target_value.unwrap_or(0.0) * self.state.success_until,
-            ));
-
-            // If current probability is bigger than target_probability break
-            if let Some(target) = self.target_probability {
-                if self.state.success_until > target {
+            let record = match self.next_record(&mut csv_cursor, record_index, &mut total_cost)? {
+                Some(record) => record,
+                None => break, // Reference has ended
+            };
+            records.push(record);
+
+            // If current probability is bigger than target_probability break, or success is
+            // certain from now on, which is the only way to reach a target of 1.0
+            if let Some(target) = self.stop_target() {
+                if self.state.success_until > target || self.state.fail_until == 0.0 {
                     break;
                 }
             }
@@ -527,14 +974,7 @@ impl Calculator {
                 }
             }
 
-            // If and only if cursor is next,
-            // increase csv index
-            if let RecordCursor::Next = cursor {
-                csv_index += 1;
-            }
-
             // Increases record index
-            // regardless of csv_index
             record_index += 1;
 
             // When using range variant,
@@ -545,120 +985,430 @@ impl Calculator {
         }
 
         // Add more records if offset is given
-        if let Some(mut offset) = self.offset {
-            // This is a non dry code from previous loop
+        if let Some(offset) = self.offset {
             record_index += 1;
-            while offset > 0 {
-                cursor = RecordCursor::Next;
-
-                // Only if csv value is not empty, update the state from csv value(file)
-                if !csv_value.is_empty() {
-                    self.update_state_from_csv_file(&mut csv_records, csv_index, &mut cursor)?;
-                }
-                self.calculate_fail_success()?;
-
-                let prob_str = utils::get_prob_as_formatted(
-                    self.state.success_until,
-                    &self.prob_type,
-                    &self.prob_precision,
-                );
-
-                total_cost += self.state.cost;
-                records.push(Record::new(
-                    record_index + 1,
-                    self.state.success_until,
-                    prob_str.to_owned(),
-                    total_cost,
-                    self.state.constant,
-                    self.target_value.unwrap_or(0.0) * self.state.success_until,
-                ));
-
-                // If and only if cursor is next,
-                // increase csv index
-                if let RecordCursor::Next = cursor {
-                    csv_index += 1;
+            for index in record_index..record_index + offset {
+                match self.next_record(&mut csv_cursor, index, &mut total_cost)? {
+                    Some(record) => records.push(record),
+                    None => break,
                 }
+            }
+        }
 
-                // Increases record index
-                // regardless of csv_index
-                record_index += 1;
+        Ok(records)
+    }
 
-                if offset == 0 {
-                    break;
-                } else {
-                    offset -= 1;
-                }
+    /// Calculate a next record
+    ///
+    /// This returns none if reference has ended and calculation should stop.
+    fn next_record(
+        &mut self,
+        csv_cursor: &mut CsvCursor,
+        index: usize,
+        total_cost: &mut f32,
+    ) -> GcalcResult<Option<Record>> {
+        // Only if reference is given, update the state from csv value(file)
+        if !csv_cursor.is_empty() {
+            if let RecordCursor::End = self.update_state_from_csv_file(csv_cursor, index)? {
+                return Ok(None);
             }
         }
+        self.state.guaranteed = self.pity.is_some_and(|pity| index + 1 >= pity);
+        let fail_before = self.state.fail_until;
+        self.calculate_fail_success()?;
+        let rate = self.effective_rate();
 
-        Ok(records)
+        let prob_str = self.format_probability(self.state.success_until);
+
+        // Because first try also consumes cost
+        // total_cost should be calculated before push
+        *total_cost += self.state.cost;
+
+        Ok(Some(
+            Record::new(
+                index + 1,
+                self.state.success_until,
+                prob_str,
+                *total_cost,
+                self.state.constant,
+                self.target_value.unwrap_or(0.0) * self.state.success_until,
+            )
+            .marginal(
+                self.probability_pair(rate),
+                self.probability_pair(fail_before * rate),
+                self.probability_pair(self.state.fail_until),
+            )
+            .formatted_amount(&self.cost_format(), &self.currency),
+        ))
     }
 
     fn index_record_from_value(&mut self, csv_value: &str) -> GcalcResult<Vec<Vec<String>>> {
         let result = if csv_value.is_empty() {
             vec![]
         } else {
-            let columns = vec![
-                self.column_map["count"].as_str(),
-                self.column_map["prob"].as_str(),
-                self.column_map["cost"].as_str(),
-                self.column_map["constant"].as_str(),
-            ]
-            .join(",");
-            self.indexer.add_table("ref", csv_value.as_bytes())?;
-            self.indexer
+            let mut columns: Vec<String> = ["count", "prob", "cost", "constant"]
+                .iter()
+                .map(|key| self.column_map[*key].to_owned())
+                .collect();
+            // Sort and comparison columns are selected only for filtering unless they are one of
+            // above, thus those are truncated after rows are filtered
+            let order = self.filter.sort_order(&self.column_map)?;
+            let comparisons = self.filter.comparisons(&self.column_map)?;
+            let mut column_index = |column: &str| match columns.iter().position(|c| *c == column) {
+                Some(index) => index,
+                None => {
+                    columns.push(column.to_owned());
+                    columns.len() - 1
+                }
+            };
+            let sort_index = order.as_ref().map(|(column, _)| column_index(column));
+            let comparisons: Vec<_> = comparisons
+                .iter()
+                .map(|predicate| (column_index(&predicate.column), predicate))
+                .collect();
+            self.dialect
+                .add_table(&mut self.indexer, "ref", csv_value)?;
+            let mut rows = self
+                .indexer
                 .index_get_records(Query::from_str(&format!(
-                    "SELECT {} FROM ref FLAG SUP",
-                    columns
+                    "SELECT {} FROM ref{} FLAG SUP",
+                    columns.join(","),
+                    self.filter.to_clause(&self.column_map)?
                 ))?)?
                 .iter()
-                .map(|s| s.iter().map(|v| v.to_string()).collect())
-                .collect::<Vec<Vec<String>>>()
+                // Every selected column is numeric
+                .map(|s| s.iter().map(|v| self.dialect.normalize_number(v)).collect())
+                .collect::<Vec<Vec<String>>>();
+            rows.retain(|row| {
+                comparisons
+                    .iter()
+                    .all(|(index, predicate)| predicate.matches(&row[*index]))
+            });
+            if let (Some(index), Some((_, descending))) = (sort_index, order) {
+                RecordFilter::sort_rows(&mut rows, descending, |row| &row[index]);
+            }
+            for row in rows.iter_mut() {
+                row.truncate(CONSTANT_INDEX + 1);
+            }
+            rows
         };
 
         Ok(result)
     }
+
+    /// Evaluate a single value of a metric, which is used by sweeps
+    ///
+    /// Success is the probability of success within budget and tries is the count needed
+    /// to reach target probability, or NaN if it is never reached. A condition that can't be
+    /// calculated, e.g. 0 probability, is also NaN.
+    pub fn evaluate(&mut self, metric: SweepMetric) -> GcalcResult<f32> {
+        match metric {
+            SweepMetric::Success => {
+                let budget = self.budget.ok_or_else(|| {
+                    GcalcError::InvalidConditional(
+                        "Budget should be present to evaluate success".to_string(),
+                    )
+                })?;
+                // Calculation should only stop by budget
+                let target = self.target_probability.take();
+                let targets = std::mem::take(&mut self.targets);
+                let records = self.create_records(false);
+                self.target_probability = target;
+                self.targets = targets;
+                let records = match records {
+                    // A cell which can't be calculated, e.g. 0 cost, doesn't fail a whole sweep
+                    Err(GcalcError::InvalidConditional(_)) => return Ok(f32::NAN),
+                    records => records?,
+                };
+                Ok(records
+                    .iter()
+                    .take_while(|record| record.cost <= budget)
+                    .last()
+                    .map_or(0.0, |record| record.probability_src))
+            }
+            SweepMetric::Tries => {
+                let target = self.target_probability.ok_or_else(|| {
+                    GcalcError::InvalidConditional(
+                        "Target probability should be present to evaluate tries".to_string(),
+                    )
+                })?;
+                let budget = self.budget.take();
+                let records = self.create_records(false);
+                self.budget = budget;
+                let records = match records {
+                    // Target is never reached, e.g. with 0 probability
+                    Err(GcalcError::InvalidConditional(_)) => return Ok(f32::NAN),
+                    records => records?,
+                };
+                Ok(records
+                    .iter()
+                    .find(|record| record.probability_src >= target)
+                    .map_or(f32::NAN, |record| record.count as f32))
+            }
+        }
+    }
     // </PROCESSING>
 
     // <INTERNAL>
+    /// Records of a range calculation without printing
+    #[cfg(feature = "plotters")]
+    pub(crate) fn range_records(&mut self, count: Option<usize>) -> GcalcResult<Vec<Record>> {
+        if let Some(count) = count {
+            self.count = count;
+        }
+        self.create_records(true)
+    }
+
+    /// Records of a conditional calculation without printing
+    #[cfg(feature = "plotters")]
+    pub(crate) fn conditional_records(&mut self) -> GcalcResult<Vec<Record>> {
+        self.create_records(false)
+    }
+
+    #[cfg(feature = "plotters")]
+    pub(crate) fn chart_attribute(&self) -> &PlotAttribute {
+        &self.plot_attribute
+    }
+
+    /// Conditions of a calculation to draw over a chart
+    #[cfg(any(feature = "plotters", feature = "json"))]
+    fn annotation(&self) -> Annotation {
+        Annotation {
+            target: self.target_probability,
+            milestones: self.targets.clone(),
+            budget: self.budget,
+            pity: self.pity,
+        }
+    }
+
+    /// Output columns of records, resolved from given columns
+    #[cfg(feature = "tui")]
+    pub(crate) fn record_columns(&self) -> GcalcResult<Vec<OutputColumn>> {
+        OutputColumn::resolve(&self.columns, &self.available_columns(ColumnKind::RECORD))
+    }
+
+    #[cfg(feature = "tui")]
+    pub(crate) fn reference(&self) -> &CsvRef {
+        &self.csv_ref
+    }
+
+    /// Path of a reference file, none if a reference is not a file
+    #[cfg(feature = "binary")]
+    pub(crate) fn reference_file(&self) -> Option<&Path> {
+        match &self.csv_ref {
+            CsvRef::File(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "binary")]
+    pub(crate) fn current_table_format(&self) -> TableFormat {
+        self.format
+    }
+
+    #[cfg(feature = "binary")]
+    pub(crate) fn plot_enabled(&self) -> bool {
+        self.plot
+    }
+
+    /// Calculate count, cost, probability and value with geometric series formula
+    fn geometric_qualification(&self, target: Option<f32>) -> GcalcResult<(usize, f32, f32, f32)> {
+        let probability = self.state.probability + self.state.constant;
+        let target_value = self.target_value.unwrap_or(0f32);
+
+        if self.state.probability >= 1.0 {
+            return Ok((1, self.state.cost, 1.0f32, target_value));
+        }
+
+        let count = if let Some(target) = target {
+            // Probability and possibly with budget
+            let count = self.geometric_count(target);
+            match self.budget {
+                Some(bud) if count as f32 * self.state.cost > bud => {
+                    (bud / self.state.cost).floor() as usize
+                }
+                _ => count,
+            }
+        } else {
+            // No probability only budget
+            if self.state.cost == 0f32 {
+                return Err(GcalcError::InvalidArgument(
+                    "Cost should not be 0 if no reference was given as argument.".to_string(),
+                ));
+            }
+            (self.budget.unwrap_or(0f32) / self.state.cost).floor() as usize
+        };
+
+        let prob = utils::geometric_series(count, probability);
+        Ok((
+            count,
+            count as f32 * self.state.cost,
+            prob,
+            prob * target_value,
+        ))
+    }
+
+    /// Count that reaches a target by geometric series formula
+    fn geometric_count(&self, target: f32) -> usize {
+        utils::geometric_series_qual(self.state.probability + self.state.constant, target)
+    }
+
+    /// Whether a target is reached within a budget by geometric series formula
+    fn geometric_reaches(&self, target: f32) -> bool {
+        self.budget.map_or(true, |budget| {
+            self.geometric_count(target) as f32 * self.state.cost <= budget
+        })
+    }
+
+    /// Leading records that fit in a budget
+    ///
+    /// Calculation stops at the first record that exceeds a budget, which is not affordable.
+    fn affordable<'a>(&self, records: &'a [Record]) -> &'a [Record] {
+        let end = records
+            .iter()
+            .position(|record| self.budget.is_some_and(|budget| record.cost > budget))
+            .unwrap_or(records.len());
+        &records[..end]
+    }
+
+    /// Find the first record that reaches each milestone target
+    ///
+    /// Targets that are not reached, e.g. by budget or an end of a reference, are omitted.
+    fn find_milestones(&self, records: &[Record]) -> Vec<Record> {
+        self.targets
+            .iter()
+            .filter_map(|&target| {
+                self.affordable(records)
+                    .iter()
+                    .find(|record| record.probability_src >= target)
+                    .map(|record| {
+                        record
+                            .clone()
+                            .milestone(target, self.format_probability(target))
+                    })
+            })
+            .collect()
+    }
+
+    /// Create a record of geometric series, where marginal values are from a closed form
+    fn qualification_record(
+        &self,
+        count: usize,
+        cost: f32,
+        probability: f32,
+        value: f32,
+    ) -> Record {
+        let rate = self.effective_rate();
+        let survival = (1.0 - rate).powi(count as i32);
+        let first_success = if count == 0 {
+            0.0
+        } else {
+            (1.0 - rate).powi(count as i32 - 1) * rate
+        };
+        Record::new(
+            count,
+            probability,
+            self.format_probability(probability),
+            cost,
+            self.state.constant,
+            value,
+        )
+        .marginal(
+            self.probability_pair(rate),
+            self.probability_pair(first_success),
+            self.probability_pair(survival),
+        )
+        .formatted_amount(&self.cost_format(), &self.currency)
+    }
+
+    fn format_probability(&self, probability: f32) -> String {
+        let format = NumberFormat {
+            precision: self.prob_precision,
+            rounding: self.rounding,
+            significant: self.significant,
+        };
+        utils::format_probability(probability, &self.prob_type, &format)
+    }
+
+    /// Columns of an output, where money is only available with a conversion rate
+    fn available_columns(&self, kinds: &[ColumnKind]) -> Vec<ColumnKind> {
+        kinds
+            .iter()
+            .filter(|kind| **kind != ColumnKind::Money || self.currency.rate.is_some())
+            .copied()
+            .collect()
+    }
+
+    /// Number format of cost and value columns
+    fn cost_format(&self) -> NumberFormat {
+        NumberFormat {
+            precision: self.cost_precision,
+            rounding: self.rounding,
+            significant: self.significant,
+        }
+    }
+
+    fn probability_pair(&self, probability: f32) -> (f32, String) {
+        (probability, self.format_probability(probability))
+    }
+
+    /// Whether qualification can use a geometric series formula
+    fn uses_closed_form(&self) -> bool {
+        self.csv_ref == CsvRef::None && self.pity.is_none()
+    }
+
+    /// Single try success rate of a current state
+    fn effective_rate(&self) -> f32 {
+        if self.state.guaranteed {
+            return 1.0;
+        }
+        (self.state.probability + self.state.constant).clamp(0.0, 1.0)
+    }
+
     fn update_state_from_csv_file(
         &mut self,
-        csv_records: &mut impl Iterator<Item = Vec<String>>,
+        csv_cursor: &mut CsvCursor,
         index: usize,
-        cursor: &mut RecordCursor,
-    ) -> GcalcResult<()> {
-        match csv_records.next() {
-            Some(row) => {
-                // Get Count,
-                // If index is bigger than current csv index.
-                // Wait(pass) until it matches the index
-                // if count is empty than interpret it as current index
-                if self.get_count_from_row(&row, index)? > index + 1 {
-                    *cursor = RecordCursor::Stay;
-                    // Do nothing & respect previous value,
-                    return Ok(());
+    ) -> GcalcResult<RecordCursor> {
+        if csv_cursor.position >= csv_cursor.rows.len() {
+            // Record not found
+            match self.record_behaviour {
+                CsvRecordBehaviour::Repeat => return Ok(RecordCursor::Stay), // Do nothing & respect previous value,
+                CsvRecordBehaviour::Panic => {
+                    return Err(GcalcError::CsvError(format!(
+                        "Empty row in index: {}",
+                        index + 1
+                    )));
                 }
-                // Get probability
-                self.set_prob_from_row(&row)?;
-                // Get constant probability
-                self.set_constant_from_row(&row)?;
-                // Get cost
-                self.set_cost_from_row(&row, index)?;
-            } // End some match
-            None => {
-                // Record not found
-                match self.record_behaviour {
-                    CsvRecordBehaviour::Repeat => (), // Do nothing & respect previous value,
-                    CsvRecordBehaviour::Panic => {
-                        return Err(GcalcError::CsvError(format!(
-                            "Empty row in index: {}",
-                            index + 1
-                        )));
-                    }
+                CsvRecordBehaviour::Stop => return Ok(RecordCursor::End),
+                CsvRecordBehaviour::Cycle => {
+                    // Start from the first row, counts are relative to a cycle
+                    csv_cursor.position = 0;
+                    csv_cursor.cycle_start = index;
                 }
             }
         }
-        Ok(())
+
+        let row = csv_cursor.rows[csv_cursor.position].clone();
+        let relative_index = index - csv_cursor.cycle_start;
+
+        // Get Count,
+        // If count is bigger than current index.
+        // Wait(pass) until it matches the index
+        // if count is empty than interpret it as current index
+        if self.get_count_from_row(&row, relative_index)? > relative_index + 1 {
+            // Do nothing & respect previous value,
+            return Ok(RecordCursor::Stay);
+        }
+        csv_cursor.position += 1;
+
+        // Get probability
+        self.set_prob_from_row(&row, index)?;
+        // Get constant probability
+        self.set_constant_from_row(&row, index)?;
+        // Get cost
+        self.set_cost_from_row(&row, index)?;
+        Ok(RecordCursor::Next)
     }
 
     fn get_count_from_row(&self, row: &[String], index: usize) -> GcalcResult<usize> {
@@ -669,107 +1419,102 @@ impl Calculator {
             // Row without count will interpret the given row as declartive not conditional
             Ok(index + 1)
         } else {
-            // TODO Remove expect
-            Ok(count
-                .parse::<usize>()
-                .expect("Failed to get count as number"))
+            count.parse::<usize>().map_err(|_| {
+                GcalcError::ParseError(format!(
+                    "Count should be a positive integer, but the value in ({},{}) is not",
+                    index + 1,
+                    count
+                ))
+            })
         }
     }
 
-    fn set_prob_from_row(&mut self, row: &[String]) -> GcalcResult<()> {
-        let prob = &row[PROB_INDEX];
-        let result = if prob.is_empty() {
-            Err(GcalcError::ParseError(
-                "No probability in record".to_string(),
-            ))
-        } else {
-            // TODO Remove expect
-            Ok(utils::get_prob_alap(&row[PROB_INDEX], None)?)
-        };
-        match result {
-            Ok(value) => self.state.probability = value,
-            Err(err) => {
-                match self.csv_invalid_behaviour {
-                    CSVInvalidBehaviour::None => return Err(err), // this is error
-                    CSVInvalidBehaviour::Ignore => (),            // Do not update value
-                    CSVInvalidBehaviour::Rollback => {
-                        self.state.probability = self.state.initial_probability
-                    } // Use default value
-                }
-            }
-        }
+    fn set_prob_from_row(&mut self, row: &[String], index: usize) -> GcalcResult<()> {
+        let strict_prob = self.strict_prob;
+        self.state.probability = self
+            .csv_fallback
+            .probability
+            .resolve(
+                &row[PROB_INDEX],
+                self.state.probability,
+                self.state.initial_probability,
+                |cell| utils::parse_probability(cell, strict_prob),
+            )
+            .map_err(|err| {
+                GcalcError::ParseError(format!(
+                    "Probability should be a valid number, but the value in ({},\"{}\") is not : {}",
+                    index + 1,
+                    row[PROB_INDEX],
+                    err.detail()
+                ))
+            })?;
         Ok(())
     }
 
     fn set_cost_from_row(&mut self, row: &[String], index: usize) -> GcalcResult<()> {
-        let cost = &row[COST_INDEX];
-        let result: GcalcResult<f32> = if cost.is_empty() {
-            Ok(0.0f32)
-        } else {
-            // TODO Remove expect
-            Ok(row[COST_INDEX]
-                .parse::<f32>()
-                .expect("Failed to parse cost"))
-        };
-        match result {
-            Ok(value) => self.state.cost = value,
-            Err(_) => {
-                match self.csv_invalid_behaviour {
-                    CSVInvalidBehaviour::None => {
-                        // this is error
-                        return Err(GcalcError::ParseError(format!(
-                            "Cost should be a number, but the value in ({},{}) is not",
-                            index + 1,
-                            row[COST_INDEX]
-                        )));
-                    }
-                    CSVInvalidBehaviour::Ignore => (), // Do not update value
-                    CSVInvalidBehaviour::Rollback => self.state.cost = self.state.initial_cost, // Use default value
-                }
-            }
-        }
+        self.state.cost = self
+            .csv_fallback
+            .cost
+            .resolve(
+                &row[COST_INDEX],
+                self.state.cost,
+                self.state.initial_cost,
+                |cell| Ok(cell.parse::<f32>()?),
+            )
+            .map_err(|err| {
+                GcalcError::ParseError(format!(
+                    "Cost should be a number, but the value in ({},\"{}\") is not : {}",
+                    index + 1,
+                    row[COST_INDEX],
+                    err.detail()
+                ))
+            })?;
         Ok(())
     }
 
-    fn set_constant_from_row(&mut self, row: &[String]) -> GcalcResult<()> {
-        let constant = &row[CONSTANT_INDEX];
-        let result = if constant.is_empty() {
-            Err(GcalcError::ParseError(
-                "No const value in record".to_string(),
-            ))
-        } else {
-            // TODO Remove expect
-            Ok(utils::get_prob_alap(&row[CONSTANT_INDEX], None)?)
-        };
-        match result {
-            Ok(value) => self.state.constant = value,
-            Err(err) => {
-                match self.csv_invalid_behaviour {
-                    CSVInvalidBehaviour::None => return Err(err), // this is error
-                    CSVInvalidBehaviour::Ignore => (),            // Do not update value
-                    CSVInvalidBehaviour::Rollback => {
-                        self.state.constant = self.state.initial_constant
-                    } // Use default value
-                }
-            }
-        }
+    fn set_constant_from_row(&mut self, row: &[String], index: usize) -> GcalcResult<()> {
+        let strict_prob = self.strict_prob;
+        self.state.constant = self
+            .csv_fallback
+            .constant
+            .resolve(
+                &row[CONSTANT_INDEX],
+                self.state.constant,
+                self.state.initial_constant,
+                |cell| utils::parse_probability(cell, strict_prob),
+            )
+            .map_err(|err| {
+                GcalcError::ParseError(format!(
+                    "Constant should be a valid number, but the value in ({},\"{}\") is not : {}",
+                    index + 1,
+                    row[CONSTANT_INDEX],
+                    err.detail()
+                ))
+            })?;
         Ok(())
     }
 
     /// calculate fail success
     fn calculate_fail_success(&mut self) -> GcalcResult<()> {
         // Current indenpendent success rate
-        let success = (self.state.probability + self.state.constant).min(1.0f32);
-        self.state.success_until += self.state.fail_until * success;
+        let success = self.effective_rate();
+        // Accumulated floating point error should not exceed certainty
+        self.state.success_until =
+            (self.state.success_until + self.state.fail_until * success).min(1.0);
         let fail_prob = (1f32 - success).max(0.0f32);
         // Fail until is multiplied
         self.state.fail_until *= fail_prob;
         Ok(())
     }
 
+    /// Target to stop calculation, which is the largest milestone if any
+    fn stop_target(&self) -> Option<f32> {
+        self.targets.last().copied().or(self.target_probability)
+    }
+
     fn conditional_sanity_check(&self) -> GcalcResult<()> {
         // Both empty
-        if self.target_probability == None && self.budget == None {
+        if self.stop_target().is_none() && self.budget.is_none() {
             return Err(GcalcError::InvalidConditional(
                 "Either target probability or budget should be present".to_string(),
             ));
@@ -777,18 +1522,22 @@ impl Calculator {
 
         if self.csv_ref == CsvRef::None {
             // No ref file
-            if self.budget != None && self.state.cost == 0.0 {
+            if self.budget.is_some() && self.state.cost == 0.0 {
                 return Err(GcalcError::InvalidConditional(
                     "0 cost with budget will incur infinite loop".to_string(),
                 ));
             }
-            if self.target_probability != None && self.state.probability == 0.0 {
+            // Pity guarantees a success, thus any target is reached
+            if self.pity.is_some() {
+                return Ok(());
+            }
+            if self.stop_target().is_some() && self.state.probability == 0.0 {
                 return Err(GcalcError::InvalidConditional(
                     "0 probability with static target probability will incur infinite loop"
                         .to_string(),
                 ));
             }
-            if let Some(num) = self.target_probability {
+            if let Some(num) = self.stop_target() {
                 if num == 1.0f32 && self.state.constant < 1.0f32 {
                     return Err(GcalcError::InvalidConditional("1.0 probability cannot be reached. Use reference file if you need tailored control over probability.".to_string()));
                 }
@@ -798,55 +1547,138 @@ impl Calculator {
         Ok(())
     }
 
+    /// Print records in a table format
+    ///
+    /// Chart records are used for a html chart, which can differ from printed records, e.g.
+    /// milestones.
+    #[cfg_attr(not(feature = "plotters"), allow(unused_variables))]
     fn print_records(
         &self,
-        records: &Vec<Record>,
+        records: &[Record],
         range: Option<(usize, usize)>,
+        mode: &str,
+        available: &[ColumnKind],
+        chart_records: &[Record],
     ) -> GcalcResult<()> {
+        let columns = OutputColumn::resolve(&self.columns, &self.available_columns(available))?;
         let formatted = match self.format {
-            TableFormat::CSV => match RecordFormatter::to_raw_csv(records, range) {
+            TableFormat::CSV => match RecordFormatter::to_raw_csv(records, range, &columns) {
                 Ok(csv) => csv,
                 Err(err) => return Err(GcalcError::FormatFail(err)),
             },
             #[cfg(feature = "tabled")]
             TableFormat::Console => {
-                RecordFormatter::to_styled_table(records, range, tabled::Style::default())
+                RecordFormatter::to_styled_table(records, range, &columns, tabled::Style::default())
             }
             #[cfg(feature = "tabled")]
-            TableFormat::GFM => {
-                RecordFormatter::to_styled_table(records, range, tabled::Style::github_markdown())
+            TableFormat::GFM => RecordFormatter::to_styled_table(
+                records,
+                range,
+                &columns,
+                tabled::Style::github_markdown(),
+            ),
+            TableFormat::Html => {
+                // Chart is same as the one from plot option
+                #[cfg(feature = "plotters")]
+                let chart = if chart_records.is_empty() {
+                    None
+                } else {
+                    Some(Renderer::render_svg(
+                        &self.plot_attribute,
+                        chart_records,
+                        &self.annotation(),
+                    )?)
+                };
+                #[cfg(not(feature = "plotters"))]
+                let chart: Option<String> = None;
+                RecordFormatter::to_html(
+                    records,
+                    range,
+                    &columns,
+                    &self.input_summary(mode),
+                    chart.as_deref(),
+                )
+            }
+            #[cfg(feature = "json")]
+            TableFormat::Json => RecordFormatter::to_json(
+                records,
+                range,
+                &columns,
+                &self.input_summary(mode),
+                false,
+            )?,
+            #[cfg(feature = "json")]
+            TableFormat::Ndjson => {
+                RecordFormatter::to_json(records, range, &columns, &self.input_summary(mode), true)?
             }
         };
         self.yield_table(&formatted)?;
         Ok(())
     }
 
-    fn print_qual_table(
-        &self,
-        count: usize,
-        cost: f32,
-        probability: &str,
-        value: f32,
-    ) -> GcalcResult<()> {
+    fn print_qual_table(&self, qual: &Record) -> GcalcResult<()> {
+        let columns = OutputColumn::resolve(
+            &self.columns,
+            &self.available_columns(ColumnKind::QUALIFICATION),
+        )?;
         let formatted = match self.format {
-            TableFormat::CSV => {
-                QualFormatter::to_csv_table(Qualficiation::new(count, cost, probability, value))?
-            }
+            TableFormat::CSV => QualFormatter::to_csv_table(qual, &columns)?,
             #[cfg(feature = "tabled")]
-            TableFormat::Console => QualFormatter::to_styled_table(
-                Qualficiation::new(count, cost, probability, value),
-                tabled::Style::default(),
-            ),
+            TableFormat::Console => {
+                QualFormatter::to_styled_table(qual, &columns, tabled::Style::default())
+            }
             #[cfg(feature = "tabled")]
-            TableFormat::GFM => QualFormatter::to_styled_table(
-                Qualficiation::new(count, cost, probability, value),
-                tabled::Style::github_markdown(),
-            ),
+            TableFormat::GFM => {
+                QualFormatter::to_styled_table(qual, &columns, tabled::Style::github_markdown())
+            }
+            TableFormat::Html => {
+                QualFormatter::to_html(qual, &columns, &self.input_summary("qual"))
+            }
+            #[cfg(feature = "json")]
+            TableFormat::Json => {
+                QualFormatter::to_json(qual, &columns, &self.input_summary("qual"), false)?
+            }
+            #[cfg(feature = "json")]
+            TableFormat::Ndjson => {
+                QualFormatter::to_json(qual, &columns, &self.input_summary("qual"), true)?
+            }
         };
         self.yield_table(&formatted)?;
         Ok(())
     }
 
+    /// Draw chart on a target of plot attribute
+    ///
+    /// Terminal chart is printed to stdout even if a table is written to a file.
+    fn draw_chart(&self, records: &[Record]) -> GcalcResult<()> {
+        match self.plot_attribute.target {
+            PlotTarget::Terminal => {
+                let chart = TermRenderer::render(&self.plot_attribute, records)?;
+                // Csv table doesn't end with a new line
+                write!(std::io::stdout(), "\n{}", chart)?;
+            }
+            PlotTarget::File if self.plot_attribute.image_format() == PlotFormat::VegaLite => {
+                #[cfg(feature = "json")]
+                VegaRenderer::write(
+                    &self.plot_attribute,
+                    &VegaRenderer::records(&self.plot_attribute, records, &self.annotation())?,
+                )?;
+                #[cfg(not(feature = "json"))]
+                return Err(GcalcError::InvalidArgument(
+                    "Vega-Lite output requires a feature \"json\"".to_owned(),
+                ));
+            }
+            #[cfg(feature = "plotters")]
+            PlotTarget::File => {
+                Renderer::draw_chart(&self.plot_attribute, records, &self.annotation())?
+            }
+            // Image file requires plotters
+            #[cfg(not(feature = "plotters"))]
+            PlotTarget::File => (),
+        }
+        Ok(())
+    }
+
     fn yield_table(&self, table: &str) -> GcalcResult<()> {
         match &self.out_option {
             OutOption::Console => write!(std::io::stdout(), "{}", table)?,
@@ -866,10 +1698,26 @@ impl Calculator {
     // </INTERNAL>
 }
 
-enum CsvRecordBehaviour {
-    Repeat,
-    Panic,
-    // Possibly early return
+/// Reading position of reference rows
+struct CsvCursor {
+    rows: Vec<Vec<String>>,
+    position: usize,
+    // Record index where a current cycle has started
+    cycle_start: usize,
+}
+
+impl CsvCursor {
+    pub fn new(rows: Vec<Vec<String>>) -> Self {
+        Self {
+            rows,
+            position: 0,
+            cycle_start: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
 }
 
 struct CalcState {
@@ -881,6 +1729,8 @@ struct CalcState {
     pub initial_cost: f32,
     pub success_until: f32,
     pub fail_until: f32,
+    // Whether a current try is at or after pity
+    pub guaranteed: bool,
 }
 
 impl CalcState {
@@ -894,8 +1744,19 @@ impl CalcState {
             initial_cost: 0.0,
             success_until: 0.0,
             fail_until: 1.0,
+            guaranteed: false,
         }
     }
+
+    /// Restore initial values so that every calculation starts from the same state
+    pub fn reset(&mut self) {
+        self.probability = self.initial_probability;
+        self.constant = self.initial_constant;
+        self.cost = self.initial_cost;
+        self.success_until = 0.0;
+        self.fail_until = 1.0;
+        self.guaranteed = false;
+    }
 }
 
 #[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
@@ -906,6 +1767,31 @@ pub enum TableFormat {
     GFM,
     #[cfg(feature = "tabled")]
     Console,
+    /// Self contained html report
+    Html,
+    #[cfg(feature = "json")]
+    Json,
+    /// Newline delimited json
+    #[cfg(feature = "json")]
+    Ndjson,
+}
+
+impl TableFormat {
+    /// Extension of a file which has a table of this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::CSV => "csv",
+            #[cfg(feature = "tabled")]
+            Self::GFM => "md",
+            #[cfg(feature = "tabled")]
+            Self::Console => "txt",
+            Self::Html => "html",
+            #[cfg(feature = "json")]
+            Self::Json => "json",
+            #[cfg(feature = "json")]
+            Self::Ndjson => "ndjson",
+        }
+    }
 }
 
 impl std::str::FromStr for TableFormat {
@@ -917,6 +1803,11 @@ impl std::str::FromStr for TableFormat {
             #[cfg(feature = "tabled")]
             "gfm" | "github" => Ok(Self::GFM),
             "csv" => Ok(Self::CSV),
+            "html" => Ok(Self::Html),
+            #[cfg(feature = "json")]
+            "json" => Ok(Self::Json),
+            #[cfg(feature = "json")]
+            "ndjson" | "jsonl" => Ok(Self::Ndjson),
             _ => Err(GcalcError::InvalidConversion(format!(
                 "{} is not a valid table format",
                 string
@@ -924,3 +1815,357 @@ impl std::str::FromStr for TableFormat {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFERENCE: &str = "count,prob,cost,constant\n1,0.1,10,0\n2,0.2,20,0\n";
+
+    fn reference_calculator(behaviour: CsvRecordBehaviour) -> Calculator {
+        Calculator::new()
+            .unwrap()
+            .csv_ref(CsvRef::Raw(REFERENCE.to_owned()))
+            .record_behaviour(behaviour)
+    }
+
+    #[test]
+    fn cycle_restarts_reference_with_relative_counts() {
+        let mut cal = reference_calculator(CsvRecordBehaviour::Cycle);
+        cal.count = 5;
+        let records = cal.create_records(true).unwrap();
+        let counts: Vec<usize> = records.iter().map(|r| r.count).collect();
+        let rates: Vec<f32> = records.iter().map(|r| r.rate.0).collect();
+        let costs: Vec<f32> = records.iter().map(|r| r.cost).collect();
+        assert_eq!(counts, [1, 2, 3, 4, 5]);
+        assert_eq!(rates, [0.1, 0.2, 0.1, 0.2, 0.1]);
+        assert_eq!(costs, [10.0, 30.0, 40.0, 60.0, 70.0]);
+    }
+
+    #[test]
+    fn stop_ends_records_with_reference() {
+        let mut cal = reference_calculator(CsvRecordBehaviour::Stop);
+        cal.count = 5;
+        let records = cal.create_records(true).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].cost, 30.0);
+    }
+
+    #[test]
+    fn filter_compares_reference_cells_numerically() {
+        let reference = "banner,count,prob,cost,constant\n\
+                         a,10,0.3,30,0\n\
+                         a,2,0.2,20,0\n\
+                         b,1,0.1,10,0\n\
+                         a,9,0.9,90,0\n";
+        let mut cal = Calculator::new()
+            .unwrap()
+            .csv_ref(CsvRef::Raw(reference.to_owned()))
+            .filter(
+                RecordFilter::new()
+                    .predicate("banner=a")
+                    .predicate("count>=5")
+                    .order("cost:desc"),
+            );
+        let rows = cal.index_record_from_value(reference).unwrap();
+        let counts: Vec<&str> = rows.iter().map(|row| row[0].as_str()).collect();
+        assert_eq!(counts, ["9", "10"]);
+        assert!(rows.iter().all(|row| row.len() == CONSTANT_INDEX + 1));
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn marginal_columns_of_geometric_records() {
+        let mut cal = Calculator::new().unwrap().probability(0.5).unwrap();
+        cal.count = 4;
+        let records = cal.create_records(true).unwrap();
+        let rates: Vec<f32> = records.iter().map(|r| r.rate.0).collect();
+        let first: Vec<f32> = records.iter().map(|r| r.first_success.0).collect();
+        let survival: Vec<f32> = records.iter().map(|r| r.survival.0).collect();
+        assert_close(&rates, &[0.5, 0.5, 0.5, 0.5]);
+        assert_close(&first, &[0.5, 0.25, 0.125, 0.0625]);
+        assert_close(&survival, &[0.5, 0.25, 0.125, 0.0625]);
+        for record in &records {
+            // Survival is the complement of accumulated success
+            assert!((record.survival.0 + record.probability_src - 1.0).abs() < 1e-5);
+        }
+        assert_eq!(records[1].first_success.1, "0.25");
+    }
+
+    #[test]
+    fn marginal_columns_follow_reference_rates() {
+        let mut cal = reference_calculator(CsvRecordBehaviour::Repeat);
+        cal.count = 3;
+        let records = cal.create_records(true).unwrap();
+        let rates: Vec<f32> = records.iter().map(|r| r.rate.0).collect();
+        let first: Vec<f32> = records.iter().map(|r| r.first_success.0).collect();
+        let survival: Vec<f32> = records.iter().map(|r| r.survival.0).collect();
+        assert_close(&rates, &[0.1, 0.2, 0.2]);
+        assert_close(&first, &[0.1, 0.9 * 0.2, 0.72 * 0.2]);
+        assert_close(&survival, &[0.9, 0.72, 0.576]);
+    }
+
+    #[test]
+    fn first_success_ends_at_guaranteed_count() {
+        let mut cal = Calculator::new().unwrap().probability(0.5).unwrap();
+        cal.set_pity(3);
+        cal.count = 4;
+        let records = cal.create_records(true).unwrap();
+        let first: Vec<f32> = records.iter().map(|r| r.first_success.0).collect();
+        let survival: Vec<f32> = records.iter().map(|r| r.survival.0).collect();
+        assert_close(&first, &[0.5, 0.25, 0.25, 0.0]);
+        assert_close(&survival, &[0.5, 0.25, 0.0, 0.0]);
+        // Every success happens at or before pity
+        assert!((first.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn pity_guarantees_a_success_from_its_count() {
+        let mut cal = Calculator::new().unwrap().probability(0.2).unwrap();
+        assert!(cal.uses_closed_form());
+        assert_close(&[cal.effective_rate()], &[0.2]);
+
+        cal.set_pity(3);
+        assert!(!cal.uses_closed_form());
+        cal.count = 4;
+        let records = cal.create_records(true).unwrap();
+        let rates: Vec<f32> = records.iter().map(|r| r.rate.0).collect();
+        assert_close(&rates, &[0.2, 0.2, 1.0, 1.0]);
+        assert!(cal.state.guaranteed);
+        assert_close(&[cal.effective_rate()], &[1.0]);
+
+        cal.clear_pity();
+        cal.create_records(true).unwrap();
+        assert!(!cal.state.guaranteed);
+        assert!(cal.uses_closed_form());
+    }
+
+    #[test]
+    fn pity_reaches_targets_which_rates_cannot() {
+        // Zero probability reaches a target only at pity
+        let mut cal = Calculator::new()
+            .unwrap()
+            .probability(0.0)
+            .unwrap()
+            .pity(10)
+            .target_probability(0.9);
+        let records = cal.create_records(false).unwrap();
+        assert_eq!(records.len(), 10);
+        assert_eq!(records.last().unwrap().probability_src, 1.0);
+
+        // Certainty is reached only at pity
+        let mut cal = Calculator::new()
+            .unwrap()
+            .probability(0.01)
+            .unwrap()
+            .pity(10)
+            .target_probability(1.0);
+        assert_eq!(cal.create_records(false).unwrap().len(), 10);
+        assert!(cal.print_qualfication().is_ok());
+
+        // Without pity, both are still rejected
+        let mut cal = Calculator::new()
+            .unwrap()
+            .probability(0.01)
+            .unwrap()
+            .target_probability(1.0);
+        assert!(matches!(
+            cal.create_records(false),
+            Err(GcalcError::InvalidConditional(_))
+        ));
+    }
+
+    #[test]
+    fn certain_success_reaches_target_of_one() {
+        let mut cal = Calculator::new()
+            .unwrap()
+            .probability(0.5)
+            .unwrap()
+            .constant(1.0)
+            .unwrap()
+            .target_probability(1.0);
+        assert_eq!(cal.create_records(false).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn qualification_record_matches_calculated_record() {
+        let mut cal = Calculator::new()
+            .unwrap()
+            .probability(0.2)
+            .unwrap()
+            .constant(0.05)
+            .unwrap();
+        cal.count = 5;
+        let records = cal.create_records(true).unwrap();
+        let last = records.last().unwrap();
+        let qual = cal.qualification_record(5, last.cost, last.probability_src, 0.0);
+        assert_close(
+            &[qual.rate.0, qual.first_success.0, qual.survival.0],
+            &[last.rate.0, last.first_success.0, last.survival.0],
+        );
+        assert_eq!(
+            cal.qualification_record(0, 0.0, 0.0, 0.0).first_success.0,
+            0.0
+        );
+    }
+
+    #[test]
+    fn marginal_columns_are_printed_only_when_selected() {
+        let cal = Calculator::new().unwrap();
+        let available = cal.available_columns(ColumnKind::RECORD);
+        let default = OutputColumn::resolve(&[], &available).unwrap();
+        assert!(default.iter().all(|column| !column.kind.is_extra()));
+        assert!(!available.contains(&ColumnKind::Money));
+
+        let selected = OutputColumn::parse_list("count,pmf:First,survival,hazard").unwrap();
+        let resolved = OutputColumn::resolve(&selected, &available).unwrap();
+        let kinds: Vec<ColumnKind> = resolved.iter().map(|column| column.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ColumnKind::Count,
+                ColumnKind::FirstSuccess,
+                ColumnKind::Survival,
+                ColumnKind::Rate
+            ]
+        );
+        assert_eq!(resolved[1].header(), "First");
+    }
+
+    fn milestone_calculator(targets: Vec<f32>) -> Calculator {
+        let mut cal = Calculator::new()
+            .unwrap()
+            .probability(0.5)
+            .unwrap()
+            .cost(10.0);
+        cal.set_targets(targets).unwrap();
+        cal
+    }
+
+    fn milestone_counts(milestones: &[Record]) -> Vec<(f32, usize)> {
+        milestones
+            .iter()
+            .map(|record| (record.target.as_ref().unwrap().0, record.count))
+            .collect()
+    }
+
+    #[test]
+    fn milestones_are_first_records_reaching_targets() {
+        let mut cal = milestone_calculator(vec![0.9, 0.5, 0.75]);
+        let records = cal.create_records(false).unwrap();
+        assert_eq!(
+            milestone_counts(&cal.find_milestones(&records)),
+            [(0.5, 1), (0.75, 2), (0.9, 4)]
+        );
+    }
+
+    #[test]
+    fn milestones_out_of_budget_are_omitted() {
+        let mut cal = milestone_calculator(vec![0.5, 0.75, 0.99]).budget(30.0);
+        let records = cal.create_records(false).unwrap();
+        // Calculation stops at the first record over budget
+        assert_eq!(records.last().unwrap().cost, 40.0);
+        assert_eq!(
+            milestone_counts(&cal.find_milestones(&records)),
+            [(0.5, 1), (0.75, 2)]
+        );
+        assert!(cal.geometric_reaches(0.75));
+        assert!(!cal.geometric_reaches(0.99));
+    }
+
+    #[test]
+    fn stop_before_target_fails_qualification() {
+        let mut cal = reference_calculator(CsvRecordBehaviour::Stop).target_probability(0.99);
+        assert!(matches!(
+            cal.print_qualfication(),
+            Err(GcalcError::InvalidConditional(_))
+        ));
+    }
+
+    #[test]
+    fn qualification_stops_at_last_affordable_record() {
+        let mut cal = reference_calculator(CsvRecordBehaviour::Cycle).budget(35.0);
+        let records = cal.create_records(false).unwrap();
+        assert_eq!(records.last().unwrap().cost, 40.0);
+        assert_eq!(cal.affordable(&records).last().unwrap().cost, 30.0);
+    }
+
+    #[test]
+    fn unreachable_target_evaluates_to_nan() {
+        let mut cal = Calculator::new()
+            .unwrap()
+            .probability(0.0)
+            .unwrap()
+            .target_probability(0.5);
+        assert!(cal.evaluate(SweepMetric::Tries).unwrap().is_nan());
+    }
+
+    #[test]
+    fn statistics_use_quantiles_unless_milestones_are_given() {
+        assert_eq!(STATISTIC_QUANTILES, [0.5, 0.9, 0.99]);
+        let mut cal = milestone_calculator(vec![]);
+        cal.print_statistics().unwrap();
+        // Quantiles are not left as milestones
+        assert!(cal.targets.is_empty());
+
+        let mut cal = milestone_calculator(vec![0.75]);
+        cal.print_statistics().unwrap();
+        assert_eq!(cal.targets, [0.75]);
+
+        // Quantiles are cleared even if a calculation fails
+        let mut cal = Calculator::new().unwrap().probability(0.0).unwrap();
+        assert!(cal.print_statistics().is_err());
+        assert!(cal.targets.is_empty());
+    }
+
+    #[test]
+    fn state_is_reset_for_every_calculation() {
+        let mut cal = Calculator::new()
+            .unwrap()
+            .probability(0.3)
+            .unwrap()
+            .cost(10.0)
+            .target_probability(0.9);
+        let first = cal.create_records(false).unwrap();
+        cal.state.probability = 0.9;
+        cal.state.success_until = 0.5;
+        let second = cal.create_records(false).unwrap();
+        assert_eq!(first.len(), second.len());
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!((a.probability_src, a.cost), (b.probability_src, b.cost));
+        }
+
+        cal.state.reset();
+        assert_eq!(cal.state.probability, 0.3);
+        assert_eq!(cal.state.cost, 10.0);
+        assert_eq!((cal.state.success_until, cal.state.fail_until), (0.0, 1.0));
+        assert!(!cal.state.guaranteed);
+    }
+
+    #[test]
+    fn accumulated_success_is_clamped_to_certainty() {
+        // Rate over 1 is clamped, thus success is certain at a first try
+        let mut cal = Calculator::new()
+            .unwrap()
+            .probability(0.9)
+            .unwrap()
+            .constant(0.5)
+            .unwrap();
+        cal.count = 3;
+        let records = cal.create_records(true).unwrap();
+        assert!(records.iter().all(|r| r.probability_src == 1.0));
+        assert!(records.iter().all(|r| r.survival.0 == 0.0));
+
+        // Floating point error doesn't exceed certainty
+        let mut cal = Calculator::new().unwrap().probability(0.1).unwrap();
+        cal.count = 2000;
+        let records = cal.create_records(true).unwrap();
+        assert!(records.iter().all(|r| r.probability_src <= 1.0));
+    }
+}
@@ -1,6 +1,13 @@
+use crate::batch::Batch;
+use crate::repl::Repl;
+#[cfg(feature = "tui")]
+use crate::tui::Tui;
+use crate::watch::Watcher;
 use crate::{
-    calc::CalculatorOption, models::CsvRef, utils, Calculator, GcalcError, GcalcResult, ProbType,
-    TableFormat,
+    calc::CalculatorOption, models::CsvRef, models::Record, Calculator, CsvDialect,
+    CsvRecordBehaviour, CurrencyFormat, GcalcError, GcalcResult, Linter, OutputColumn, Overlay,
+    PlotAttribute, PlotFormat, PlotKind, PlotTarget, ProbType, RecordFilter, RoundingMode, Sweep,
+    SweepAxis, SweepMetric, TableFormat,
 };
 use clap::{App, Arg, ArgMatches};
 use std::io::Write;
@@ -17,95 +24,353 @@ impl Cli {
     }
 
     fn args_builder() -> ArgMatches {
-        let cond_app = Self::common_app_args(App::new("cond").about("Conditional calculation"));
-        let range_app =
-            Self::common_app_args(App::new("range").about("Prints range of calculations"));
-        let qual_app = Self::common_app_args(
+        let cond_app = Self::watch_app_args(Self::compare_app_args(Self::plot_app_args(
+            Self::milestone_app_args(Self::common_app_args(
+                App::new("cond").about("Conditional calculation"),
+            )),
+        )));
+        let range_app = Self::watch_app_args(Self::compare_app_args(Self::plot_app_args(
+            Self::common_app_args(App::new("range").about("Prints range of calculations")),
+        )));
+        let sweep_app = Self::plot_app_args(Self::target_app_args(Self::calculation_app_args(
+            App::new("sweep").about("Evaluate a grid of two parameters"),
+        )));
+        let qual_app = Self::watch_app_args(Self::milestone_app_args(Self::common_app_args(
             App::new("qual").about("Conditional calculation but only prints result"),
-        );
+        )));
+        let repl_app =
+            Self::common_app_args(App::new("repl").about("Interactive calculation session"));
 
         let main_app = App::new("gcalc")
             .version("0.4.0")
             .author("Simon creek <simoncreek@tutanota.com>")
             .about("Gcalc is a gacha simulator for game development and other decision makings.") // meta information end
             .subcommand(
-                cond_app
+                cond_app.arg(
+                    Arg::new("offset")
+                        .help("Record offset")
+                        .long("offset")
+                        .takes_value(true),
+                ),
+            )
+            .subcommand(qual_app)
+            .subcommand(
+                range_app
                     .arg(
-                        Arg::new("budget")
-                            .help("Budget of total cost")
-                            .short('b')
-                            .long("budget")
+                        Arg::new("count")
+                            .help("Counts to execute")
+                            .short('c')
+                            .long("count")
                             .takes_value(true),
                     )
                     .arg(
-                        Arg::new("target")
-                            .help("Target probability")
-                            .short('t')
-                            .long("target")
+                        Arg::new("start")
+                            .help("Starting index to print")
+                            .short('S')
+                            .long("start")
                             .takes_value(true),
+                    ),
+            )
+            .subcommand(
+                sweep_app
+                    .arg(
+                        Arg::new("x")
+                            .help("Parameter of x axis (e.g. probability=0.005:0.02:0.005)")
+                            .long("x")
+                            .takes_value(true)
+                            .required(true),
                     )
                     .arg(
-                        Arg::new("offset")
-                            .help("Record offset")
-                            .long("offset")
-                            .takes_value(true),
+                        Arg::new("y")
+                            .help("Parameter of y axis (e.g. pity=50,70,90)")
+                            .long("y")
+                            .takes_value(true)
+                            .required(true),
                     )
-                    .arg(Arg::new("plot").help("Crate plot chart").long("plot")),
+                    .arg(
+                        Arg::new("metric")
+                            .help("Value of each cell(success|tries)")
+                            .long("metric")
+                            .takes_value(true)
+                            .default_value("success"),
+                    ),
             )
             .subcommand(
-                qual_app
-                    .arg(
-                        Arg::new("budget")
-                            .help("Budget of total cost")
-                            .short('b')
-                            .long("budget")
-                            .takes_value(true),
-                    )
+                Self::reference_app_args(App::new("lint"))
+                    .about("Validate a reference file")
                     .arg(
-                        Arg::new("target")
-                            .help("Target probability")
-                            .short('t')
-                            .long("target")
-                            .takes_value(true),
+                        Arg::new("deny")
+                            .help("Treat warnings as errors")
+                            .short('D')
+                            .long("deny-warnings"),
                     ),
             )
+            .subcommand(repl_app)
             .subcommand(
-                range_app
+                App::new("batch")
+                    .about("Run scenarios of a manifest")
                     .arg(
-                        Arg::new("count")
-                            .help("Counts to execute")
-                            .short('c')
-                            .long("count")
-                            .takes_value(true),
+                        Arg::new("manifest")
+                            .help("Manifest file, toml if extension is toml and json otherwise")
+                            .required(true)
+                            .index(1),
                     )
                     .arg(
-                        Arg::new("start")
-                            .help("Starting index to print")
-                            .short('S')
-                            .long("start")
+                        Arg::new("outdir")
+                            .help("Output directory [default: \"output_dir\" of manifest or batch]")
+                            .short('o')
+                            .long("out-dir")
                             .takes_value(true),
-                    )
-                    .arg(Arg::new("plot").help("Crate plot chart").long("plot")),
+                    ),
             )
             .subcommand(App::new("reference").about("Create a reference file"));
 
         #[cfg(feature = "option")]
         let app = main_app.subcommand(App::new("option").about("Create an option file")); // "option" file creation subcommand
+        #[cfg(feature = "tui")]
+        let app = app.subcommand(Self::common_app_args(
+            App::new("tui").about("Full screen session with live calculation"),
+        ));
 
         app.get_matches()
     }
 
-    fn common_app_args(app: clap::App) -> clap::App {
-        let app = app
+    /// Arguments of a budget and a target probability
+    fn target_app_args(app: clap::App) -> clap::App {
+        app.arg(
+            Arg::new("budget")
+                .help("Budget of total cost")
+                .short('b')
+                .long("budget")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("target")
+                .help("Target probability")
+                .short('t')
+                .long("target")
+                .takes_value(true),
+        )
+    }
+
+    /// Target arguments with milestone targets, which cond and qual share
+    fn milestone_app_args(app: clap::App) -> clap::App {
+        Self::target_app_args(app).arg(
+            Arg::new("targets")
+                .help("Milestone target probabilities (e.g. 0.5,0.9,0.99)")
+                .long("targets")
+                .takes_value(true),
+        )
+    }
+
+    fn dialect_app_args(app: clap::App) -> clap::App {
+        app.arg(
+            Arg::new("delimiter")
+                .help("Reference delimiter (e.g. \";\", tab)")
+                .long("delimiter")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("decimal")
+                .help("Decimal separator of reference numbers")
+                .long("decimal")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("thousands")
+                .help("Thousands separator of reference numbers")
+                .long("thousands")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("comment")
+                .help("Prefix of reference comment lines (e.g. #)")
+                .long("comment")
+                .takes_value(true),
+        )
+    }
+
+    fn plot_app_args(app: clap::App) -> clap::App {
+        app.arg(
+            Arg::new("plot")
+                .help("Create plot chart(file|term) [default: file]")
+                .long("plot")
+                .takes_value(true)
+                .min_values(0)
+                .max_values(1),
+        )
+        .arg(
+            Arg::new("plotout")
+                .help("Chart path, png or vega-lite by a png or json extension [default: out.svg]")
+                .long("plot-out")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("plotkind")
+                .help("Chart kind(cumulative|histogram|survival)")
+                .long("plot-kind")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("plotmarkers")
+                .help("Draw median, 90th percentile and pity markers on a histogram")
+                .long("plot-markers"),
+        )
+        .arg(
+            Arg::new("noannotations")
+                .help("Don't draw target, budget, pity and milestone annotations")
+                .long("no-annotations"),
+        )
+        .arg(
+            Arg::new("logx")
+                .help("Use a logarithmic count axis")
+                .long("log-x"),
+        )
+        .arg(
+            Arg::new("xrange")
+                .help("Zoom count axis into a range (e.g. 1:500)")
+                .long("x-range")
+                .takes_value(true)
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::new("yrange")
+                .help("Zoom probability axis into a range (e.g. 0.5:1)")
+                .long("y-range")
+                .takes_value(true)
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::new("plotformat")
+                .help("Chart image format(svg|png|vega)")
+                .long("plot-format")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("plotsize")
+                .help("Chart image size (e.g. 1280x720)")
+                .long("plot-size")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("caption")
+                .help("Chart caption")
+                .long("caption")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("probcaption")
+                .help("Caption of probability axis")
+                .long("prob-caption")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("costcaption")
+                .help("Caption of cost axis")
+                .long("cost-caption")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("font")
+                .help("Chart font family")
+                .long("font")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("fontsize")
+                .help("Chart font size")
+                .long("font-size")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("probcolor")
+                .help("Colour of probability series (e.g. red, #ff0000)")
+                .long("prob-color")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("costcolor")
+                .help("Colour of cost series")
+                .long("cost-color")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("background")
+                .help("Chart background colour")
+                .long("background")
+                .takes_value(true),
+        )
+    }
+
+    fn watch_app_args(app: clap::App) -> clap::App {
+        app.arg(
+            Arg::new("watch")
+                .help("Calculate again whenever a reference or an option file changes")
+                .long("watch")
+                .conflicts_with("refin"),
+        )
+    }
+
+    fn compare_app_args(app: clap::App) -> clap::App {
+        app.arg(
+            Arg::new("compare")
+                .help("Overlay a scenario of a reference or an option file (e.g. pity=ref.csv)")
+                .long("compare")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+    }
+
+    /// Arguments that change a calculation itself
+    /// Arguments to read a reference, which calculations and lint share
+    fn reference_app_args(app: clap::App) -> clap::App {
+        Self::dialect_app_args(app)
             .arg(
-                Arg::new("value")
-                    .help("Value of target item")
-                    .long("value")
+                Arg::new("strictprob")
+                    .help("Reject ambiguous bare probability numbers (e.g. 1.5)")
+                    .long("strict-prob"),
+            )
+            .arg(
+                Arg::new("reference")
+                    .help("Reference file")
+                    .short('r')
+                    .long("ref")
+                    .takes_value(true)
+                    .conflicts_with("refin"),
+            )
+            .arg(
+                Arg::new("refin")
+                    .help("Reference from stdin")
+                    .long("refin")
+                    .conflicts_with("reference"),
+            )
+            .arg(
+                Arg::new("column")
+                    .help("Column mapping")
+                    .long("column")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("where")
+                    .help("Filter reference rows (e.g. banner=limited)")
+                    .short('w')
+                    .long("where")
+                    .takes_value(true)
+                    .multiple_occurrences(true),
+            )
+            .arg(
+                Arg::new("sort")
+                    .help("Sort reference rows by column (e.g. count:desc)")
+                    .long("sort")
                     .takes_value(true),
             )
+    }
+
+    fn calculation_app_args(app: clap::App) -> clap::App {
+        let app = Self::reference_app_args(app)
             .arg(
                 Arg::new("prob")
-                    .help("Basic probability")
+                    .help("Basic probability (e.g. 0.005, 0.5%, 1/200, \"1 in 200\", 50bp)")
                     .short('p')
                     .long("probability")
                     .takes_value(true),
@@ -124,27 +389,81 @@ impl Cli {
                     .takes_value(true),
             )
             .arg(
-                Arg::new("reference")
-                    .help("Reference file")
-                    .short('r')
-                    .long("ref")
-                    .takes_value(true)
-                    .conflicts_with("refin"),
+                Arg::new("pity")
+                    .help("Count where success is guaranteed")
+                    .long("pity")
+                    .takes_value(true),
             )
             .arg(
-                Arg::new("refin")
-                    .help("Reference from stdin")
-                    .long("refin")
-                    .conflicts_with("reference"),
+                Arg::new("noheader")
+                    .help("CSV without header")
+                    .long("noheader"),
+            )
+            .arg(
+                Arg::new("fallback")
+                    .help("Set csv value fallback (rollback|ignore|none), globally or per column (e.g. prob=none,constant=rollback)")
+                    .long("fallback")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("default")
+                    .help("Set default values for empty csv cells (e.g. cost=0,constant=0.5%)")
+                    .long("default")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("strict")
+                    .help("Set strict CSV reader mode")
+                    .short('s')
+                    .long("strict")
+                    .conflicts_with_all(&["cycle", "stop"]),
+            )
+            .arg(
+                Arg::new("cycle")
+                    .help("Loop back to the first CSV row when reference ends")
+                    .long("cycle")
+                    .conflicts_with("stop"),
+            )
+            .arg(
+                Arg::new("stop")
+                    .help("End calculation at the last CSV row")
+                    .long("stop"),
+            );
+
+        #[cfg(feature = "option")]
+        let app = app.arg(
+            Arg::new("option")
+                .help("Option file to use")
+                .short('O')
+                .long("option")
+                .takes_value(true),
+        );
+
+        app
+    }
+
+    fn common_app_args(app: clap::App) -> clap::App {
+        Self::calculation_app_args(app)
+            .arg(
+                Arg::new("value")
+                    .help("Value of target item")
+                    .long("value")
+                    .takes_value(true),
             )
             .arg(
                 Arg::new("format")
-                    .help("Table format(csv|console|gfm)")
+                    .help("Table format(csv|console|gfm|html|json|ndjson)")
                     .short('f')
                     .long("format")
                     .takes_value(true)
                     .default_value("console"),
             )
+            .arg(
+                Arg::new("columns")
+                    .help("Output columns with optional headers (e.g. count,probability:Chance)")
+                    .long("columns")
+                    .takes_value(true),
+            )
             .arg(
                 Arg::new("precision")
                     .help("Precision")
@@ -154,22 +473,71 @@ impl Cli {
                     .default_value("2"),
             )
             .arg(
-                Arg::new("probtype")
-                    .help("Probability type(percentage|fraction)")
-                    .short('T')
-                    .long("type")
+                Arg::new("rounding")
+                    .help("Rounding mode(truncate|half-up|half-even|ceil|floor)")
+                    .long("rounding")
                     .takes_value(true),
             )
             .arg(
-                Arg::new("column")
-                    .help("Column mapping")
-                    .long("column")
+                Arg::new("significant")
+                    .help("Minimum significant digits of small numbers")
+                    .long("significant")
                     .takes_value(true),
             )
             .arg(
-                Arg::new("noheader")
-                    .help("CSV without header")
-                    .long("noheader"),
+                Arg::new("costprecision")
+                    .help("Precision of cost and value")
+                    .long("cost-precision")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("grouping")
+                    .help("Thousands separator of cost and value (e.g. comma, space)")
+                    .long("grouping")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("costprefix")
+                    .help("Currency prefix of cost and value")
+                    .long("cost-prefix")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("costsuffix")
+                    .help("Currency suffix of cost and value")
+                    .long("cost-suffix")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("moneyrate")
+                    .help("Real money per a unit of cost, which adds a money column")
+                    .long("money-rate")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("moneyprefix")
+                    .help("Currency prefix of money")
+                    .long("money-prefix")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("moneysuffix")
+                    .help("Currency suffix of money")
+                    .long("money-suffix")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("moneyprecision")
+                    .help("Precision of money [default: 2]")
+                    .long("money-precision")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("probtype")
+                    .help("Probability type(percentage|fraction)")
+                    .short('T')
+                    .long("type")
+                    .takes_value(true),
             )
             .arg(
                 Arg::new("out")
@@ -178,41 +546,30 @@ impl Cli {
                     .long("out")
                     .takes_value(true),
             )
-            .arg(
-                Arg::new("fallback")
-                    .help("Set csv value fallback (rollback|ignore|none)")
-                    .long("fallback")
-                    .default_value("none"),
-            )
-            .arg(
-                Arg::new("strict")
-                    .help("Set strict CSV reader mode")
-                    .short('s')
-                    .long("strict"),
-            );
-
-        #[cfg(feature = "option")]
-        let app = app.arg(
-            Arg::new("option")
-                .help("Option file to use")
-                .short('O')
-                .long("option")
-                .takes_value(true),
-        );
-
-        app
     }
 
     fn run_calculator(args: &ArgMatches) -> GcalcResult<()> {
         match args.subcommand() {
             Some(("range", range_m)) => {
-                Self::subcommand_range(range_m)?;
+                Self::watch(range_m, Self::subcommand_range)?;
             }
             Some(("cond", cond_m)) => {
-                Self::subcommand_conditional(cond_m)?;
+                Self::watch(cond_m, Self::subcommand_conditional)?;
             }
             Some(("qual", qual_m)) => {
-                Self::subcommand_qual(qual_m)?;
+                Self::watch(qual_m, Self::subcommand_qual)?;
+            }
+            Some(("sweep", sweep_m)) => {
+                Self::subcommand_sweep(sweep_m)?;
+            }
+            Some(("lint", lint_m)) => {
+                Self::subcommand_lint(lint_m)?;
+            }
+            Some(("repl", repl_m)) => {
+                Self::subcommand_repl(repl_m)?;
+            }
+            Some(("batch", batch_m)) => {
+                Self::subcommand_batch(batch_m)?;
             }
             Some(("reference", _)) => {
                 Self::subcommand_reference()?;
@@ -221,6 +578,10 @@ impl Cli {
             Some(("option", _)) => {
                 Self::subcommand_option()?;
             }
+            #[cfg(feature = "tui")]
+            Some(("tui", tui_m)) => {
+                Self::subcommand_tui(tui_m)?;
+            }
             _ => writeln!(
                 std::io::stderr(),
                 "No proper sub command was given to the program"
@@ -230,6 +591,74 @@ impl Cli {
         Ok(())
     }
 
+    /// Run a subcommand, again whenever a watched file changes if watch is given
+    ///
+    /// Watched files are an option file and a reference file, which are read again for each
+    /// run. A failed run is printed and watch continues, e.g. while a file is being edited.
+    fn watch(args: &ArgMatches, subcommand: fn(&ArgMatches) -> GcalcResult<()>) -> GcalcResult<()> {
+        if !args.is_present("watch") {
+            return subcommand(args);
+        }
+
+        let mut stderr = std::io::stderr();
+        let mut watcher = Watcher::new(Self::watched_files(args));
+        if watcher.paths().next().is_none() {
+            return Err(GcalcError::InvalidArgument(
+                "Watch requires a reference file or an option file".to_owned(),
+            ));
+        }
+        let option_file = Self::option_file(args);
+        loop {
+            if let Err(err) = subcommand(args) {
+                writeln!(stderr, "{}", err)?;
+            }
+            // Csv table doesn't end with a new line
+            writeln!(std::io::stdout())?;
+
+            let paths: Vec<String> = watcher
+                .paths()
+                .map(|path| format!("\"{}\"", path.display()))
+                .collect();
+            writeln!(stderr, "Watching {} for changes", paths.join(", "))?;
+            let changed = watcher.wait();
+            let names: Vec<String> = changed
+                .iter()
+                .map(|path| format!("\"{}\"", path.display()))
+                .collect();
+            writeln!(stderr, "Changed {}, calculating again", names.join(", "))?;
+            // Option file can change a reference, thus watched files are resolved again
+            if option_file
+                .as_ref()
+                .is_some_and(|file| changed.contains(file))
+            {
+                watcher.set_paths(Self::watched_files(args));
+            }
+        }
+    }
+
+    fn option_file(args: &ArgMatches) -> Option<PathBuf> {
+        #[cfg(feature = "option")]
+        if let Some(file) = args.value_of("option") {
+            return Some(PathBuf::from(file));
+        }
+        None
+    }
+
+    fn watched_files(args: &ArgMatches) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = Self::option_file(args).into_iter().collect();
+        // Reference can be given by an option file, which is watched regardless of validity
+        let mut cal = match Calculator::new() {
+            Ok(cal) => cal,
+            Err(_) => return files,
+        };
+        if Self::set_calculator_attribute(&mut cal, args).is_ok() {
+            if let Some(file) = cal.reference_file() {
+                files.push(file.to_owned());
+            }
+        }
+        files
+    }
+
     fn subcommand_range(args: &ArgMatches) -> GcalcResult<()> {
         // Override count if value was given
         let count = if let Some(count) = args.value_of("count") {
@@ -251,20 +680,53 @@ impl Cli {
         }
         Self::set_calculator_attribute(&mut cal, args)?;
 
-        cal.set_plot(args.is_present("plot"));
-        cal.print_range(count, Some(min))?;
+        Self::set_plot_attribute(&mut cal, args)?;
+        let scenarios = Self::get_scenarios(&cal, args)?;
+        if !scenarios.is_empty() {
+            cal.set_plot(false);
+        }
+        let records = cal.print_range_records(count, Some(min))?;
 
-        Ok(())
+        Self::draw_overlay(args, (&cal, records), scenarios, |overlay, name, cal| {
+            overlay.add_range(name, cal, count)
+        })
     }
 
     fn subcommand_conditional(args: &ArgMatches) -> GcalcResult<()> {
         let mut cal = Calculator::new()?;
         Self::set_calculator_attribute(&mut cal, args)?;
+        Self::set_conditional_attribute(&mut cal, args)?;
 
-        if let Some(target) = args.value_of("target") {
-            let prob = target.parse().map_err(|_| {
-                GcalcError::ParseError("Target should be a float within 0.0 ~ 1.0".to_owned())
+        Self::set_plot_attribute(&mut cal, args)?;
+        let scenarios = Self::get_scenarios(&cal, args)?;
+        if !scenarios.is_empty() {
+            cal.set_plot(false);
+        }
+        let records = cal.print_conditional_records()?;
+
+        Self::draw_overlay(args, (&cal, records), scenarios, |overlay, name, cal| {
+            Self::set_conditional_attribute(cal, args)?;
+            overlay.add_conditional(name, cal)
+        })
+    }
+
+    fn set_conditional_attribute(cal: &mut Calculator, args: &ArgMatches) -> GcalcResult<()> {
+        Self::set_milestone_attribute(cal, args)?;
+
+        if let Some(offset) = args.value_of("offset") {
+            let offset = offset.parse().map_err(|_| {
+                GcalcError::ParseError("Offset should be a positive integer (usize)".to_owned())
             })?;
+            cal.set_offset(offset);
+        }
+
+        Ok(())
+    }
+
+    /// Set a target probability and a budget
+    fn set_target_attribute(cal: &mut Calculator, args: &ArgMatches) -> GcalcResult<()> {
+        if let Some(target) = args.value_of("target") {
+            let prob = cal.parse_probability(target)?;
             cal.set_target_probability(prob)?;
         }
 
@@ -275,57 +737,146 @@ impl Cli {
             cal.set_budget(budget);
         }
 
-        if let Some(offset) = args.value_of("offset") {
-            let offset = offset
-                .parse()
-                .map_err(|_| GcalcError::ParseError("Budget should be a number".to_owned()))?;
-            cal.set_offset(offset);
+        Ok(())
+    }
+
+    /// Set target attributes with milestone targets
+    fn set_milestone_attribute(cal: &mut Calculator, args: &ArgMatches) -> GcalcResult<()> {
+        Self::set_target_attribute(cal, args)?;
+
+        if let Some(targets) = args.value_of("targets") {
+            let targets = targets
+                .split(',')
+                .map(|target| cal.parse_probability(target))
+                .collect::<GcalcResult<Vec<f32>>>()?;
+            cal.set_targets(targets)?;
         }
 
-        cal.set_plot(args.is_present("plot"));
-        cal.print_conditional()?;
         Ok(())
     }
 
     fn subcommand_qual(args: &ArgMatches) -> GcalcResult<()> {
         let mut cal = Calculator::new()?;
         Self::set_calculator_attribute(&mut cal, args)?;
+        Self::set_milestone_attribute(&mut cal, args)?;
 
-        if let Some(target) = args.value_of("target") {
-            let prob = target.parse().map_err(|_| {
-                GcalcError::ParseError("Target should be a float within 0.0 ~ 1.0".to_owned())
-            })?;
-            cal.set_target_probability(prob)?;
+        cal.print_qualfication()?;
+        Ok(())
+    }
+
+    /// Print a grid of a sweep as csv and draw a heatmap if plot is enabled
+    fn subcommand_sweep(args: &ArgMatches) -> GcalcResult<()> {
+        let x = SweepAxis::parse(args.value_of("x").unwrap_or_default())?;
+        let y = SweepAxis::parse(args.value_of("y").unwrap_or_default())?;
+        let metric = SweepMetric::from_str(args.value_of("metric").unwrap_or("success"))?;
+        let sweep = Sweep::new(x, y, metric)?;
+        // Every cell reads a reference again, while stdin can be read only once
+        if args.is_present("refin") {
+            return Err(GcalcError::InvalidArgument(
+                "Sweep cannot be used with a reference from stdin".to_owned(),
+            ));
         }
 
-        if let Some(budget) = args.value_of("budget") {
-            let budget = budget
-                .parse()
-                .map_err(|_| GcalcError::ParseError("Budget should be a number".to_owned()))?;
-            cal.set_budget(budget);
+        let base = || {
+            let mut cal = Calculator::new()?;
+            Self::set_calculation_attribute(&mut cal, args)?;
+            Self::set_target_attribute(&mut cal, args)?;
+            Ok(cal)
+        };
+        // Plot attribute is validated before a sweep
+        let mut cal = base()?;
+        Self::set_plot_attribute(&mut cal, args)?;
+        if cal.plot_enabled() && cal.chart_attribute().target == PlotTarget::Terminal {
+            return Err(GcalcError::InvalidArgument(
+                "Heatmap cannot be drawn on a terminal".to_owned(),
+            ));
         }
+        let grid = sweep.run(base)?;
 
-        cal.print_qualfication()?;
+        writeln!(std::io::stdout(), "{}", grid.to_csv())?;
+        if cal.plot_enabled() {
+            grid.draw(cal.chart_attribute())?;
+        }
         Ok(())
     }
 
     fn set_calculator_attribute(cal: &mut Calculator, args: &ArgMatches) -> GcalcResult<()> {
+        Self::set_calculation_attribute(cal, args)?;
+
+        if let Some(value) = args.value_of("value") {
+            let value = value
+                .parse::<f32>()
+                .map_err(|_| GcalcError::ParseError("Value should be a number".to_owned()))?;
+            cal.set_value(value);
+        }
+
+        if let Some(format) = args.value_of("format") {
+            cal.set_table_format(TableFormat::from_str(format)?);
+        }
+
+        if let Some(precision) = args.value_of("precision") {
+            let precision = precision.parse().map_err(|_| {
+                GcalcError::ParseError("Precision should be a positive integer (usize)".to_owned())
+            })?;
+            cal.set_precision(precision);
+        }
+
+        if let Some(rounding) = args.value_of("rounding") {
+            cal.set_rounding(RoundingMode::from_str(rounding)?);
+        }
+
+        if let Some(digits) = args.value_of("significant") {
+            let digits = digits.parse().map_err(|_| {
+                GcalcError::ParseError(
+                    "Significant digits should be a positive integer (usize)".to_owned(),
+                )
+            })?;
+            cal.set_significant(digits);
+        }
+
+        if let Some(precision) = args.value_of("costprecision") {
+            let precision = precision.parse().map_err(|_| {
+                GcalcError::ParseError(
+                    "Cost precision should be a positive integer (usize)".to_owned(),
+                )
+            })?;
+            cal.set_cost_precision(precision);
+        }
+
+        if let Some(prob_type) = args.value_of("probtype") {
+            cal.set_prob_type(ProbType::from_str(prob_type)?);
+        }
+
+        if let Some(file) = args.value_of("out") {
+            cal.set_out_file(std::path::Path::new(file));
+        }
+
+        if let Some(currency) = Self::get_currency(args)? {
+            cal.set_currency(currency)?;
+        }
+
+        if let Some(columns) = args.value_of("columns") {
+            cal.set_columns(OutputColumn::parse_list(columns)?);
+        }
+
+        Ok(())
+    }
+
+    /// Set attributes of arguments from calculation app args
+    fn set_calculation_attribute(cal: &mut Calculator, args: &ArgMatches) -> GcalcResult<()> {
         #[cfg(feature = "option")]
         if let Some(file) = args.value_of("option") {
             let option = CalculatorOption::from_file(std::path::Path::new(file))?;
-            cal.set_option(&option);
+            cal.try_set_option(&option)?;
         }
 
-        if let Some(prob) = args.value_of("prob") {
-            let probability = utils::get_prob_alap(prob, None)?;
-            cal.set_probability(probability, true)?;
+        if args.is_present("strictprob") {
+            cal.set_strict_prob(true);
         }
 
-        if let Some(value) = args.value_of("value") {
-            let value = value
-                .parse::<f32>()
-                .map_err(|_| GcalcError::ParseError("Value should be a number".to_owned()))?;
-            cal.set_value(value);
+        if let Some(prob) = args.value_of("prob") {
+            let probability = cal.parse_probability(prob)?;
+            cal.set_probability(probability, true)?;
         }
 
         if let Some(cost) = args.value_of("cost") {
@@ -335,13 +886,18 @@ impl Cli {
             cal.set_cost(cost, true);
         }
 
-        if let Some(cost) = args.value_of("constant") {
-            let constant = cost
-                .parse()
-                .map_err(|_| GcalcError::ParseError("Constant should be a number".to_owned()))?;
+        if let Some(constant) = args.value_of("constant") {
+            let constant = cal.parse_probability(constant)?;
             cal.set_constant(constant, true)?;
         }
 
+        if let Some(pity) = args.value_of("pity") {
+            let pity = pity.parse().map_err(|_| {
+                GcalcError::ParseError("Pity should be a positive integer (usize)".to_owned())
+            })?;
+            cal.set_pity(pity);
+        }
+
         // Reference and refin is mutual exclusive
         if let Some(csv_file) = args.value_of("reference") {
             cal.set_csv_file(CsvRef::File(PathBuf::from(csv_file)));
@@ -352,30 +908,31 @@ impl Cli {
             cal.set_csv_file(CsvRef::Raw(string));
         }
 
-        if let Some(format) = args.value_of("format") {
-            cal.set_table_format(TableFormat::from_str(format)?);
+        if let Some(fallback) = args.value_of("fallback") {
+            cal.set_csv_value_fallback(fallback)?;
         }
 
-        if let Some(precision) = args.value_of("precision") {
-            let precision = precision.parse().map_err(|_| {
-                GcalcError::ParseError("Precision should be a positive integer (usize)".to_owned())
-            })?;
-            cal.set_precision(precision);
+        if let Some(defaults) = args.value_of("default") {
+            cal.set_csv_value_default(defaults)?;
         }
 
-        if let Some(prob_type) = args.value_of("probtype") {
-            cal.set_prob_type(ProbType::from_str(prob_type)?);
-        }
-
-        if let Some(file) = args.value_of("out") {
-            cal.set_out_file(std::path::Path::new(file));
+        if args.is_present("strict") {
+            cal.set_record_behaviour(CsvRecordBehaviour::Panic);
+        } else if args.is_present("cycle") {
+            cal.set_record_behaviour(CsvRecordBehaviour::Cycle);
+        } else if args.is_present("stop") {
+            cal.set_record_behaviour(CsvRecordBehaviour::Stop);
         }
 
-        cal.set_csv_value_fallback(args.value_of("fallback").unwrap_or("none"))?;
+        Self::set_custom_column_order(cal, args)?;
 
-        cal.set_strict_csv(args.is_present("strict"));
+        if let Some(filter) = Self::get_filter(args) {
+            cal.set_filter(filter);
+        }
 
-        Self::set_custom_column_order(cal, args)?;
+        if let Some(dialect) = Self::get_dialect(args)? {
+            cal.set_dialect(dialect);
+        }
 
         // No header
         if args.is_present("noheader") {
@@ -386,6 +943,271 @@ impl Cli {
     }
 
     fn set_custom_column_order(cal: &mut Calculator, args: &ArgMatches) -> GcalcResult<()> {
+        if let Some(column_map) = Self::get_column_map(args)? {
+            cal.set_column_map(column_map);
+        }
+        Ok(())
+    }
+
+    fn get_filter(args: &ArgMatches) -> Option<RecordFilter> {
+        if !args.is_present("where") && !args.is_present("sort") {
+            return None;
+        }
+
+        let mut filter = RecordFilter::new();
+        if let Some(predicates) = args.values_of("where") {
+            for predicate in predicates {
+                filter = filter.predicate(predicate);
+            }
+        }
+        if let Some(order) = args.value_of("sort") {
+            filter = filter.order(order);
+        }
+        Some(filter)
+    }
+
+    fn get_dialect(args: &ArgMatches) -> GcalcResult<Option<CsvDialect>> {
+        if !["delimiter", "decimal", "thousands", "comment"]
+            .iter()
+            .any(|arg| args.is_present(arg))
+        {
+            return Ok(None);
+        }
+
+        let mut dialect = CsvDialect::new();
+        if let Some(delimiter) = args.value_of("delimiter") {
+            dialect = dialect.delimiter(CsvDialect::parse_separator(delimiter)?);
+        }
+        if let Some(decimal) = args.value_of("decimal") {
+            dialect = dialect.decimal(CsvDialect::parse_separator(decimal)?);
+        }
+        if let Some(thousands) = args.value_of("thousands") {
+            dialect = dialect.thousands(CsvDialect::parse_separator(thousands)?);
+        }
+        if let Some(comment) = args.value_of("comment") {
+            dialect = dialect.comment(comment);
+        }
+        dialect.validate()?;
+        Ok(Some(dialect))
+    }
+
+    /// Compared scenarios as names and paths of references or option files
+    fn get_scenarios(cal: &Calculator, args: &ArgMatches) -> GcalcResult<Vec<(String, PathBuf)>> {
+        let values = match args.values_of("compare") {
+            Some(values) => values,
+            None => return Ok(vec![]),
+        };
+        if args.is_present("refin") {
+            return Err(GcalcError::InvalidArgument(
+                "Compare cannot be used with a reference from stdin".to_owned(),
+            ));
+        }
+        if cal.chart_attribute().target == PlotTarget::Terminal {
+            return Err(GcalcError::InvalidArgument(
+                "Compare cannot be drawn on a terminal".to_owned(),
+            ));
+        }
+        Ok(values
+            .map(|value| {
+                let (name, path) = match value.split_once('=') {
+                    Some((name, path)) => (name.to_owned(), PathBuf::from(path)),
+                    None => (Self::scenario_name(value), PathBuf::from(value)),
+                };
+                (name, path)
+            })
+            .collect())
+    }
+
+    fn scenario_name(path: &str) -> String {
+        std::path::Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_owned())
+    }
+
+    /// Draw a base scenario from arguments and compared scenarios on one chart
+    ///
+    /// Base is a calculator and records that are already printed. Compared scenarios share
+    /// arguments with a base, while a reference or an option file replaces the one from
+    /// arguments.
+    fn draw_overlay(
+        args: &ArgMatches,
+        (base, records): (&Calculator, Vec<Record>),
+        scenarios: Vec<(String, PathBuf)>,
+        add: impl Fn(&mut Overlay, &str, &mut Calculator) -> GcalcResult<()>,
+    ) -> GcalcResult<()> {
+        if scenarios.is_empty() {
+            return Ok(());
+        }
+
+        let mut overlay = Overlay::new();
+        let base_name = args
+            .value_of("reference")
+            .map(Self::scenario_name)
+            .unwrap_or_else(|| "base".to_owned());
+        overlay.push(&base_name, base, records);
+
+        for (name, path) in scenarios {
+            let mut cal = Calculator::new()?;
+            Self::set_calculator_attribute(&mut cal, args)?;
+            let is_option = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+            if is_option {
+                cal.try_set_option(&CalculatorOption::from_file(&path)?)?;
+            } else {
+                cal.set_csv_file(CsvRef::File(path));
+            }
+            add(&mut overlay, &name, &mut cal)?;
+        }
+        overlay.draw()
+    }
+
+    /// Plot is enabled by a flag, while option file can also enable it
+    fn set_plot_attribute(cal: &mut Calculator, args: &ArgMatches) -> GcalcResult<()> {
+        if args.is_present("plot") {
+            cal.set_plot(true);
+        }
+        if args.value_of("plot").is_none()
+            && ![
+                "plotout",
+                "plotkind",
+                "plotmarkers",
+                "noannotations",
+                "logx",
+                "xrange",
+                "yrange",
+                "plotformat",
+                "plotsize",
+                "caption",
+                "probcaption",
+                "costcaption",
+                "font",
+                "fontsize",
+                "probcolor",
+                "costcolor",
+                "background",
+            ]
+            .iter()
+            .any(|arg| args.is_present(arg))
+        {
+            return Ok(());
+        }
+
+        let mut attribute = PlotAttribute::new();
+        if let Some(target) = args.value_of("plot") {
+            attribute = attribute.target(PlotTarget::from_str(target)?);
+        }
+        if let Some(path) = args.value_of("plotout") {
+            attribute = attribute.path(path);
+        }
+        if let Some(kind) = args.value_of("plotkind") {
+            attribute = attribute.kind(PlotKind::from_str(kind)?);
+        }
+        if args.is_present("plotmarkers") {
+            attribute = attribute.markers(true);
+        }
+        if args.is_present("noannotations") {
+            attribute = attribute.annotations(false);
+        }
+        if args.is_present("logx") {
+            attribute = attribute.log_x(true);
+        }
+        if let Some(range) = args.value_of("xrange") {
+            let (min, max) = PlotAttribute::parse_range(range)?;
+            attribute = attribute.x_range(min, max);
+        }
+        if let Some(range) = args.value_of("yrange") {
+            let (min, max) = PlotAttribute::parse_range(range)?;
+            attribute = attribute.y_range(min, max);
+        }
+        if let Some(format) = args.value_of("plotformat") {
+            attribute = attribute.format(PlotFormat::from_str(format)?);
+        }
+        if let Some(size) = args.value_of("plotsize") {
+            let (width, height) = PlotAttribute::parse_size(size)?;
+            attribute = attribute.size(width, height);
+        }
+        if let Some(caption) = args.value_of("caption") {
+            attribute = attribute.caption(caption);
+        }
+        if let Some(caption) = args.value_of("probcaption") {
+            attribute = attribute.prob_caption(caption);
+        }
+        if let Some(caption) = args.value_of("costcaption") {
+            attribute = attribute.cost_caption(caption);
+        }
+        if let Some(font) = args.value_of("font") {
+            attribute = attribute.font(font);
+        }
+        if let Some(size) = args.value_of("fontsize") {
+            let size = size.parse().map_err(|_| {
+                GcalcError::ParseError("Font size should be an integer within 1 ~ 255".to_owned())
+            })?;
+            attribute = attribute.font_size(size);
+        }
+        if let Some(color) = args.value_of("probcolor") {
+            attribute = attribute.prob_color(color);
+        }
+        if let Some(color) = args.value_of("costcolor") {
+            attribute = attribute.cost_color(color);
+        }
+        if let Some(color) = args.value_of("background") {
+            attribute = attribute.background(color);
+        }
+        cal.set_plot_attribute(attribute)
+    }
+
+    fn get_currency(args: &ArgMatches) -> GcalcResult<Option<CurrencyFormat>> {
+        if ![
+            "grouping",
+            "costprefix",
+            "costsuffix",
+            "moneyrate",
+            "moneyprefix",
+            "moneysuffix",
+            "moneyprecision",
+        ]
+        .iter()
+        .any(|arg| args.is_present(arg))
+        {
+            return Ok(None);
+        }
+
+        let mut currency = CurrencyFormat::new();
+        if let Some(thousands) = args.value_of("grouping") {
+            currency = currency.thousands(CsvDialect::parse_separator(thousands)?);
+        }
+        if let Some(prefix) = args.value_of("costprefix") {
+            currency = currency.prefix(prefix);
+        }
+        if let Some(suffix) = args.value_of("costsuffix") {
+            currency = currency.suffix(suffix);
+        }
+        if let Some(rate) = args.value_of("moneyrate") {
+            let rate = rate.parse().map_err(|_| {
+                GcalcError::ParseError(format!("Money rate \"{}\" is not a valid number", rate))
+            })?;
+            currency = currency.rate(rate);
+        }
+        if let Some(prefix) = args.value_of("moneyprefix") {
+            currency = currency.money_prefix(prefix);
+        }
+        if let Some(suffix) = args.value_of("moneysuffix") {
+            currency = currency.money_suffix(suffix);
+        }
+        if let Some(precision) = args.value_of("moneyprecision") {
+            let precision = precision.parse().map_err(|_| {
+                GcalcError::ParseError(
+                    "Money precision should be a positive integer (usize)".to_owned(),
+                )
+            })?;
+            currency = currency.money_precision(precision);
+        }
+        Ok(Some(currency))
+    }
+
+    fn get_column_map(args: &ArgMatches) -> GcalcResult<Option<HashMap<String, String>>> {
         if let Some(order) = args.value_of("column") {
             let split_orders = order.split(',').collect::<Vec<&str>>();
             let mut pair_map = HashMap::from_iter(
@@ -411,9 +1233,79 @@ impl Cli {
                     pair_map.insert(pair_type.to_owned(), pair_split[1].to_owned());
                 }
             }
-            cal.set_column_map(pair_map)
+            return Ok(Some(pair_map));
         }
-        Ok(())
+        Ok(None)
+    }
+
+    fn subcommand_lint(args: &ArgMatches) -> GcalcResult<()> {
+        let (source, csv_value) = if let Some(csv_file) = args.value_of("reference") {
+            (csv_file.to_owned(), std::fs::read_to_string(csv_file)?)
+        } else if args.is_present("refin") {
+            let mut string = String::new();
+            std::io::stdin().lock().read_to_string(&mut string)?;
+            ("<stdin>".to_owned(), string)
+        } else {
+            return Err(GcalcError::InvalidArgument(
+                "Lint requires a reference file or a reference from stdin".to_owned(),
+            ));
+        };
+
+        let mut linter = Linter::new(&source);
+        if let Some(column_map) = Self::get_column_map(args)? {
+            linter = linter.column_map(column_map);
+        }
+        if let Some(filter) = Self::get_filter(args) {
+            linter = linter.filter(filter);
+        }
+        if let Some(dialect) = Self::get_dialect(args)? {
+            linter = linter.dialect(dialect);
+        }
+        if args.is_present("strictprob") {
+            linter = linter.strict_prob(true);
+        }
+        let diagnostics = linter.lint(&csv_value)?;
+
+        let mut stdout = std::io::stdout();
+        for diagnostic in &diagnostics {
+            writeln!(stdout, "{}", diagnostic)?;
+        }
+
+        linter.check(&diagnostics, args.is_present("deny"))
+    }
+
+    /// Start an interactive session from a calculator of given arguments
+    fn subcommand_repl(args: &ArgMatches) -> GcalcResult<()> {
+        // Commands are read from stdin
+        if args.is_present("refin") {
+            return Err(GcalcError::InvalidArgument(
+                "Repl cannot be used with a reference from stdin".to_owned(),
+            ));
+        }
+        let mut cal = Calculator::new()?;
+        Self::set_calculator_attribute(&mut cal, args)?;
+        Repl::new(cal).run()
+    }
+
+    /// Start a full screen session from a calculator of given arguments
+    #[cfg(feature = "tui")]
+    fn subcommand_tui(args: &ArgMatches) -> GcalcResult<()> {
+        // Keys are read from stdin
+        if args.is_present("refin") {
+            return Err(GcalcError::InvalidArgument(
+                "Tui cannot be used with a reference from stdin".to_owned(),
+            ));
+        }
+        let mut cal = Calculator::new()?;
+        Self::set_calculator_attribute(&mut cal, args)?;
+        Tui::new(cal)?.run()
+    }
+
+    fn subcommand_batch(args: &ArgMatches) -> GcalcResult<()> {
+        let batch = Batch::from_file(std::path::Path::new(
+            args.value_of("manifest").unwrap_or_default(),
+        ))?;
+        batch.run(args.value_of("outdir").map(std::path::Path::new))
     }
 
     fn subcommand_reference() -> GcalcResult<()> {
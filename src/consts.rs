@@ -2,3 +2,6 @@ pub const COUNT_INDEX: usize = 0;
 pub const PROB_INDEX: usize = 1;
 pub const COST_INDEX: usize = 2;
 pub const CONSTANT_INDEX: usize = 3;
+
+// Quantiles printed as statistics
+pub const STATISTIC_QUANTILES: [f32; 3] = [0.5, 0.9, 0.99];
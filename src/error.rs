@@ -18,6 +18,8 @@ pub enum GcalcError {
     #[cfg(feature = "plotters")]
     PlotError(String),
     CIndexError(CIndexError),
+    LintError(String),
+    BatchError(String),
 }
 
 impl std::fmt::Display for GcalcError {
@@ -40,6 +42,28 @@ impl std::fmt::Display for GcalcError {
             #[cfg(feature = "plotters")]
             Self::PlotError(err) => write!(f, "Failed to create plot image \n= {}", err),
             Self::CIndexError(err) => write!(f, "{}", err),
+            Self::LintError(err) => write!(f, "Reference has lint errors\n= {}", err),
+            Self::BatchError(err) => write!(f, "Batch has failed scenarios\n= {}", err),
+        }
+    }
+}
+
+impl GcalcError {
+    /// Message of an error without a heading, which is used to wrap an error in another
+    pub(crate) fn detail(&self) -> String {
+        match self {
+            Self::InvalidArgument(err)
+            | Self::CsvError(err)
+            | Self::InvalidProb(err)
+            | Self::InvalidConversion(err)
+            | Self::InvalidConditional(err)
+            | Self::ParseError(err)
+            | Self::Unknown(err)
+            | Self::LintError(err)
+            | Self::BatchError(err) => err.clone(),
+            #[cfg(feature = "plotters")]
+            Self::PlotError(err) => err.clone(),
+            _ => self.to_string(),
         }
     }
 }
@@ -1,30 +1,276 @@
 use std::error::Error;
 #[cfg(feature = "tabled")]
-use tabled::{Style, Table};
+use tabled::papergrid::{AlignmentHorizontal, Entity, Grid, Settings};
+#[cfg(feature = "tabled")]
+use tabled::{Style, TableOption};
 
-use crate::{
-    models::{Qualficiation, Record},
-    GcalcResult,
-};
+#[cfg(feature = "json")]
+use crate::models::ColumnKind;
+use crate::models::{InputSummary, OutputColumn};
+use crate::GcalcError;
+use crate::{models::Record, GcalcResult};
+#[cfg(feature = "json")]
+use serde::ser::{Serialize, SerializeMap, Serializer};
 
 #[cfg(windows)]
 const LINE_ENDING: &str = "\r\n";
 #[cfg(not(windows))]
 const LINE_ENDING: &str = "\n";
 
+// Json documents are serialized from typed structs rather than json values, so that
+// field order is kept and f32 numbers are not widened
+#[cfg(feature = "json")]
+#[derive(serde::Serialize)]
+struct JsonInput<'a> {
+    input: &'a InputSummary,
+}
+
+#[cfg(feature = "json")]
+#[derive(serde::Serialize)]
+struct JsonRecords<'a> {
+    input: &'a InputSummary,
+    records: Vec<JsonRow<'a>>,
+}
+
+#[cfg(feature = "json")]
+#[derive(serde::Serialize)]
+struct JsonQualification<'a> {
+    input: &'a InputSummary,
+    result: JsonRow<'a>,
+}
+
+/// Record with selected columns
+///
+/// Probability like columns are raw fractions and formatted values follow with a "_formatted"
+/// suffix.
+#[cfg(feature = "json")]
+struct JsonRow<'a> {
+    record: &'a Record,
+    columns: &'a [OutputColumn],
+}
+
+#[cfg(feature = "json")]
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for column in self.columns {
+            let header = column.header();
+            if let Some((raw, formatted)) = self.record.probability_cell(column.kind) {
+                map.serialize_entry(&header, &raw)?;
+                map.serialize_entry(&format!("{}_formatted", header), formatted)?;
+                continue;
+            }
+            match column.kind {
+                ColumnKind::Count => map.serialize_entry(&header, &self.record.count)?,
+                ColumnKind::Cost => map.serialize_entry(&header, &self.record.cost)?,
+                ColumnKind::Constant => map.serialize_entry(&header, &self.record.constant)?,
+                ColumnKind::Value => map.serialize_entry(&header, &self.record.value)?,
+                ColumnKind::Money => {
+                    let (raw, formatted) = match &self.record.money {
+                        Some((raw, formatted)) => (Some(*raw), Some(formatted.as_str())),
+                        None => (None, None),
+                    };
+                    map.serialize_entry(&header, &raw)?;
+                    map.serialize_entry(&format!("{}_formatted", header), &formatted)?;
+                }
+                // Milestone target of a plain record
+                _ => {
+                    map.serialize_entry(&header, &None::<f32>)?;
+                    map.serialize_entry(&format!("{}_formatted", header), &None::<String>)?;
+                }
+            }
+        }
+        map.end()
+    }
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Gcalc result</title>
+<style>
+body { font-family: Helvetica, Arial, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #999; padding: 0.3em 1em; text-align: center; }
+th { background: #eee; }
+svg { max-width: 100%; height: auto; }
+</style>
+</head>
+<body>
+<h1>Gcalc result</h1>
+"#;
+
+const HTML_TAIL: &str = "</body>\n</html>\n";
+
+/// Self contained html report with an input summary, a table and an optional chart
+pub(crate) struct HtmlReport {
+    body: String,
+}
+
+impl HtmlReport {
+    pub fn new(input: &InputSummary) -> Self {
+        let mut report = Self {
+            body: String::from(HTML_HEAD),
+        };
+        report.body.push_str("<h2>Input</h2>\n<table>\n");
+        for (name, value) in input.entries() {
+            report.body.push_str(&format!(
+                "<tr><th>{}</th><td>{}</td></tr>\n",
+                name,
+                escape_html(&value)
+            ));
+        }
+        report.body.push_str("</table>\n");
+        report
+    }
+
+    pub fn table(mut self, title: &str, columns: &[OutputColumn], rows: Vec<Vec<String>>) -> Self {
+        self.body
+            .push_str(&format!("<h2>{}</h2>\n<table>\n<tr>", escape_html(title)));
+        for column in columns {
+            self.body
+                .push_str(&format!("<th>{}</th>", escape_html(&column.header())));
+        }
+        self.body.push_str("</tr>\n");
+        for row in rows {
+            self.body.push_str("<tr>");
+            for cell in row {
+                self.body
+                    .push_str(&format!("<td>{}</td>", escape_html(&cell)));
+            }
+            self.body.push_str("</tr>\n");
+        }
+        self.body.push_str("</table>\n");
+        self
+    }
+
+    /// Embed svg document as it is
+    pub fn chart(mut self, svg: &str) -> Self {
+        self.body.push_str("<h2>Chart</h2>\n");
+        self.body.push_str(svg);
+        self.body.push('\n');
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        self.body.push_str(HTML_TAIL);
+        self.body
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Quote a csv cell if it contains a comma, e.g. from a thousands separator
+fn csv_cell(cell: String) -> String {
+    if cell.contains(',') || cell.contains('"') {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell
+    }
+}
+
+/// Records within a range, where range is inclusive
+fn scoped(records: &[Record], range: Option<(usize, usize)>) -> impl Iterator<Item = &Record> {
+    let (min, max) = range.unwrap_or((0, records.len()));
+    records
+        .iter()
+        .enumerate()
+        .filter(move |(index, _)| *index >= min && *index <= max)
+        .map(|(_, record)| record)
+}
+
+fn display_rows<'a>(
+    records: impl Iterator<Item = &'a Record>,
+    columns: &[OutputColumn],
+) -> Vec<Vec<String>> {
+    records
+        .map(|record| {
+            columns
+                .iter()
+                .map(|column| record.display_cell(column.kind))
+                .collect()
+        })
+        .collect()
+}
+
+/// Create a styled table with dynamic columns
+///
+/// Tabled derives headers from a type, thus a grid is built directly in the same way as tabled
+/// builds one for a table.
+#[cfg(feature = "tabled")]
+fn styled_table(columns: &[OutputColumn], rows: Vec<Vec<String>>, mut style: Style) -> String {
+    let mut grid = Grid::new(rows.len() + 1, columns.len());
+    grid.set(
+        Entity::Global,
+        Settings::new()
+            .indent(1, 1, 0, 0)
+            .alignment(AlignmentHorizontal::Center),
+    );
+    for (index, column) in columns.iter().enumerate() {
+        grid.set(
+            Entity::Cell(0, index),
+            Settings::new().text(column.header()),
+        );
+    }
+    for (row, cells) in rows.iter().enumerate() {
+        for (index, cell) in cells.iter().enumerate() {
+            grid.set(Entity::Cell(row + 1, index), Settings::new().text(cell));
+        }
+    }
+    style.change(&mut grid);
+    grid.to_string()
+}
+
 pub(crate) struct QualFormatter;
 
 impl QualFormatter {
-    // pub fn to_csv_table( qual :Qualficiation) -> Result<String, Box<dyn Error>> {
-    pub fn to_csv_table(qual: Qualficiation) -> GcalcResult<String> {
-        let data = qual.join_as_csv();
-        Ok(data)
+    /// Qualification as csv with a header row, in the same way as records
+    pub fn to_csv_table(qual: &Record, columns: &[OutputColumn]) -> GcalcResult<String> {
+        RecordFormatter::to_raw_csv(std::slice::from_ref(qual), None, columns)
+            .map_err(GcalcError::FormatFail)
     }
 
     #[cfg(feature = "tabled")]
-    pub fn to_styled_table(qual: Qualficiation, style: Style) -> String {
-        let table: Table = Table::new(vec![qual]).with(style);
-        table.to_string()
+    pub fn to_styled_table(qual: &Record, columns: &[OutputColumn], style: Style) -> String {
+        styled_table(columns, display_rows(std::iter::once(qual), columns), style)
+    }
+
+    pub fn to_html(qual: &Record, columns: &[OutputColumn], input: &InputSummary) -> String {
+        HtmlReport::new(input)
+            .table(
+                "Result",
+                columns,
+                display_rows(std::iter::once(qual), columns),
+            )
+            .finish()
+    }
+
+    /// Qualification as a json document, or as json lines if stream is set
+    #[cfg(feature = "json")]
+    pub fn to_json(
+        qual: &Record,
+        columns: &[OutputColumn],
+        input: &InputSummary,
+        stream: bool,
+    ) -> GcalcResult<String> {
+        let format_err = |err: serde_json::Error| GcalcError::FormatFail(Box::new(err));
+        let result = JsonRow {
+            record: qual,
+            columns,
+        };
+        if stream {
+            let input = serde_json::to_string(&JsonInput { input }).map_err(format_err)?;
+            let result = serde_json::to_string(&result).map_err(format_err)?;
+            Ok(format!("{}{}{}", input, LINE_ENDING, result))
+        } else {
+            serde_json::to_string_pretty(&JsonQualification { input, result }).map_err(format_err)
+        }
     }
 }
 
@@ -32,19 +278,23 @@ pub(crate) struct RecordFormatter;
 
 impl RecordFormatter {
     pub fn to_raw_csv(
-        records: &Vec<Record>,
+        records: &[Record],
         range: Option<(usize, usize)>,
+        columns: &[OutputColumn],
     ) -> Result<String, Box<dyn Error>> {
-        let mut string_records = vec!["count,probability,cost,constant,value".to_string()];
-        let (min, max) = if let Some((min, max)) = range {
-            (min, max)
-        } else {
-            (0, records.len())
-        };
-        for (index, record) in records.iter().enumerate() {
-            if index >= min && index <= max {
-                string_records.push(record.join_as_csv());
-            }
+        let mut string_records = vec![columns
+            .iter()
+            .map(|column| csv_cell(column.header()))
+            .collect::<Vec<_>>()
+            .join(",")];
+        for record in scoped(records, range) {
+            string_records.push(
+                columns
+                    .iter()
+                    .map(|column| csv_cell(record.cell(column.kind)))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
         }
 
         let data = string_records.join(LINE_ENDING);
@@ -52,37 +302,255 @@ impl RecordFormatter {
         Ok(data)
     }
 
+    /// Records as a html report, chart is embedded if given
+    pub fn to_html(
+        records: &[Record],
+        range: Option<(usize, usize)>,
+        columns: &[OutputColumn],
+        input: &InputSummary,
+        chart: Option<&str>,
+    ) -> String {
+        let report = HtmlReport::new(input).table(
+            "Records",
+            columns,
+            display_rows(scoped(records, range), columns),
+        );
+        match chart {
+            Some(svg) => report.chart(svg),
+            None => report,
+        }
+        .finish()
+    }
+
+    /// Records as a json document, or as json lines if stream is set
+    ///
+    /// The first line of json lines is an input summary and each following line is a record.
+    #[cfg(feature = "json")]
+    pub fn to_json(
+        records: &[Record],
+        range: Option<(usize, usize)>,
+        columns: &[OutputColumn],
+        input: &InputSummary,
+        stream: bool,
+    ) -> GcalcResult<String> {
+        let rows: Vec<JsonRow> = scoped(records, range)
+            .map(|record| JsonRow { record, columns })
+            .collect();
+
+        let data = if stream {
+            std::iter::once(serde_json::to_string(&JsonInput { input }))
+                .chain(rows.iter().map(serde_json::to_string))
+                .collect::<Result<Vec<_>, _>>()
+                .map(|lines| lines.join(LINE_ENDING))
+        } else {
+            serde_json::to_string_pretty(&JsonRecords {
+                input,
+                records: rows,
+            })
+        };
+        data.map_err(|err| GcalcError::FormatFail(Box::new(err)))
+    }
+
     #[cfg(feature = "tabled")]
     pub fn to_styled_table(
-        values: &Vec<Record>,
+        records: &[Record],
         range: Option<(usize, usize)>,
+        columns: &[OutputColumn],
         style: Style,
     ) -> String {
-        let (min, max) = if let Some((min, max)) = range {
-            (min, max)
-        } else {
-            (0, values.len())
-        };
+        styled_table(
+            columns,
+            display_rows(scoped(records, range), columns),
+            style,
+        )
+    }
+}
 
-        //  Convert to format record
-        let values: Vec<_> = values.iter().map(FormatRecord::from_record).collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ColumnKind;
+    use crate::Calculator;
 
-        // If range is for whole values(records)
-        // skip range check and create whole table from values
+    fn records() -> Vec<Record> {
+        (1..=3)
+            .map(|count| {
+                let probability = 1.0 - 0.5f32.powi(count as i32);
+                Record::new(
+                    count,
+                    probability,
+                    format!("{:.2}", probability),
+                    10.0 * count as f32,
+                    0.0,
+                    0.0,
+                )
+            })
+            .collect()
+    }
 
-        use crate::models::FormatRecord;
-        let table = if (min, max) == (0, values.len()) {
-            Table::new(values).with(style)
-        } else {
-            let mut scoped_records = vec![];
-            for (index, row) in values.iter().enumerate() {
-                if index >= min && index <= max {
-                    scoped_records.push(row);
-                }
-            }
-            Table::new(values).with(style)
+    fn input() -> InputSummary {
+        Calculator::new()
+            .unwrap()
+            .probability(0.5)
+            .unwrap()
+            .cost(10.0)
+            .input_summary("range")
+    }
+
+    fn columns(kinds: &[ColumnKind]) -> Vec<OutputColumn> {
+        kinds
+            .iter()
+            .map(|&kind| OutputColumn::new(kind, None))
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn json_document_has_input_and_selected_columns() {
+        let columns = vec![
+            OutputColumn::new(ColumnKind::Count, Some("Tries")),
+            OutputColumn::new(ColumnKind::Probability, None),
+        ];
+        let json =
+            RecordFormatter::to_json(&records(), Some((1, 2)), &columns, &input(), false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["input"]["mode"], "range");
+        assert_eq!(value["input"]["probability"], 0.5);
+        let records = value["records"].as_array().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["Tries"], 2);
+        assert_eq!(records[0]["probability"], 0.75);
+        assert_eq!(records[0]["probability_formatted"], "0.75");
+        // Only selected columns are serialized, in selected order
+        let row = JsonRow {
+            record: &self::records()[1],
+            columns: &columns,
         };
+        assert_eq!(
+            serde_json::to_string(&row).unwrap(),
+            r#"{"Tries":2,"probability":0.75,"probability_formatted":"0.75"}"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn ndjson_has_input_line_and_record_lines() {
+        let columns = columns(&[ColumnKind::Count, ColumnKind::Cost, ColumnKind::Money]);
+        let ndjson = RecordFormatter::to_json(&records(), None, &columns, &input(), true).unwrap();
+        let lines: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0]["input"]["cost"], 10.0);
+        assert!(lines[0].get("records").is_none());
+        assert_eq!(lines[3]["count"], 3);
+        assert_eq!(lines[3]["cost"], 30.0);
+        // Money without a conversion rate is null rather than missing
+        assert!(lines[3]["money"].is_null());
+        assert!(lines[3]["money_formatted"].is_null());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn qualification_json_has_a_single_result() {
+        let record = &records()[1];
+        let columns = columns(&[ColumnKind::Count, ColumnKind::Target]);
+        let json = QualFormatter::to_json(record, &columns, &input(), false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["result"]["count"], 2);
+        // Target of a plain record is null
+        assert!(value["result"]["target"].is_null());
+
+        let ndjson = QualFormatter::to_json(record, &columns, &input(), true).unwrap();
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(lines[1]).unwrap()["count"],
+            2
+        );
+    }
+
+    #[test]
+    fn html_report_has_input_table_and_chart() {
+        let columns = vec![
+            OutputColumn::new(ColumnKind::Count, Some("<Tries>")),
+            OutputColumn::new(ColumnKind::Probability, None),
+        ];
+        let html = RecordFormatter::to_html(
+            &records(),
+            Some((0, 1)),
+            &columns,
+            &input(),
+            Some("<svg id=\"chart\"></svg>"),
+        );
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.ends_with("</body>\n</html>\n"));
+        assert!(html.contains("<tr><th>mode</th><td>range</td></tr>"));
+        assert!(html.contains("<tr><th>probability</th><td>0.5</td></tr>"));
+        // Headers are escaped while a chart is embedded as it is
+        assert!(html.contains(
+            "<h2>Records</h2>\n<table>\n<tr><th>&lt;Tries&gt;</th><th>probability</th></tr>"
+        ));
+        assert!(html.contains("<tr><td>2</td><td>0.75</td></tr>"));
+        assert!(!html.contains("<td>3</td>"));
+        assert!(html.contains("<h2>Chart</h2>\n<svg id=\"chart\"></svg>"));
+    }
+
+    #[test]
+    fn html_qualification_has_a_result_table_without_chart() {
+        let html = QualFormatter::to_html(
+            &records()[0],
+            &columns(&[ColumnKind::Count, ColumnKind::Cost]),
+            &input(),
+        );
+        assert!(html.contains("<h2>Result</h2>"));
+        assert!(html.contains("<tr><td>1</td><td>10</td></tr>"));
+        assert!(!html.contains("<h2>Chart</h2>"));
+    }
+
+    #[test]
+    fn html_cells_are_escaped() {
+        assert_eq!(
+            escape_html("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+        let report = HtmlReport::new(&input())
+            .table(
+                "A & B",
+                &columns(&[ColumnKind::Count]),
+                vec![vec!["<1>".to_owned()]],
+            )
+            .finish();
+        assert!(report.contains("<h2>A &amp; B</h2>"));
+        assert!(report.contains("<td>&lt;1&gt;</td>"));
+    }
+
+    #[test]
+    fn qualification_csv_has_header_of_selected_columns() {
+        let qual = &records()[1];
+        let csv =
+            QualFormatter::to_csv_table(qual, &columns(&[ColumnKind::Count, ColumnKind::Cost]))
+                .unwrap();
+        assert_eq!(csv, format!("count,cost{}2,20", LINE_ENDING));
+
+        let columns = OutputColumn::parse_list("cost:Gems,count:Tries,value").unwrap();
+        let csv = QualFormatter::to_csv_table(qual, &columns).unwrap();
+        assert_eq!(csv, format!("Gems,Tries,value{}20,2,0", LINE_ENDING));
+    }
 
-        table.to_string()
+    #[test]
+    fn record_csv_quotes_headers_and_cells_with_commas() {
+        let mut records = records();
+        records[0].cost_formatted.replace("1,000".to_owned());
+        let columns = vec![
+            OutputColumn::new(ColumnKind::Count, Some("Tries, total")),
+            OutputColumn::new(ColumnKind::Cost, None),
+        ];
+        let csv = RecordFormatter::to_raw_csv(&records, Some((0, 0)), &columns).unwrap();
+        assert_eq!(
+            csv,
+            format!("\"Tries, total\",cost{}1,\"1,000\"", LINE_ENDING)
+        );
     }
 }
@@ -20,19 +20,40 @@
 //!
 //! ```
 
+#[cfg(feature = "binary")]
+mod batch;
 mod calc;
 #[cfg(feature = "binary")]
 pub mod cli;
 mod consts;
 mod error;
 mod formatter;
+mod lint;
 mod models;
 #[cfg(feature = "plotters")]
 mod plot;
+#[cfg(feature = "binary")]
+mod repl;
+mod sweep;
+mod term;
+#[cfg(feature = "tui")]
+mod tui;
 mod utils;
+#[cfg(feature = "json")]
+mod vega;
 #[cfg(feature = "wasm")]
 mod wasm;
+#[cfg(feature = "binary")]
+mod watch;
 
 pub use calc::{Calculator, TableFormat};
 pub use error::GcalcError;
-pub use models::{GcalcResult, ProbType};
+pub use lint::{Diagnostic, Linter, Severity};
+pub use models::{
+    ColumnKind, CsvDialect, CsvRecordBehaviour, CurrencyFormat, GcalcResult, InputSummary,
+    NumberFormat, OutputColumn, PlotAttribute, PlotFormat, PlotKind, PlotTarget, ProbType,
+    RecordFilter, RoundingMode,
+};
+#[cfg(feature = "plotters")]
+pub use plot::Overlay;
+pub use sweep::{Sweep, SweepAxis, SweepGrid, SweepMetric, SweepParameter};
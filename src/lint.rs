@@ -0,0 +1,523 @@
+use std::collections::HashMap;
+use std::iter::FromIterator;
+use std::str::FromStr;
+
+use cindex::{Indexer, Query};
+
+use crate::models::{CsvDialect, ProbType, RecordFilter};
+use crate::utils;
+use crate::{GcalcError, GcalcResult};
+
+/// Severity of a lint diagnostic
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Single problem found in a reference
+///
+/// Row and column are 1-based positions in the source, where row 1 is a header.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub source: String,
+    pub row: usize,
+    pub column: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.column {
+            Some(column) => write!(
+                f,
+                "{}:{}:{}: {}: {}",
+                self.source, self.row, column, self.severity, self.message
+            ),
+            None => write!(
+                f,
+                "{}:{}: {}: {}",
+                self.source, self.row, self.severity, self.message
+            ),
+        }
+    }
+}
+
+/// Reference linter
+///
+/// Linter checks a reference with the same interpretation rules that calculator uses, so that
+/// mistakes are reported with positions instead of surfacing in the middle of a calculation.
+pub struct Linter {
+    source: String,
+    column_map: HashMap<String, String>,
+    filter: RecordFilter,
+    dialect: CsvDialect,
+    strict_prob: bool,
+}
+
+impl Linter {
+    pub fn new(source: &str) -> Self {
+        let column_map = HashMap::from_iter(
+            ["count", "prob", "cost", "constant"]
+                .iter()
+                .map(|v| (v.to_string(), v.to_string())),
+        );
+        Self {
+            source: source.to_owned(),
+            column_map,
+            filter: RecordFilter::new(),
+            dialect: CsvDialect::new(),
+            strict_prob: false,
+        }
+    }
+
+    pub fn column_map(mut self, column_map: HashMap<String, String>) -> Self {
+        self.column_map = column_map;
+        self
+    }
+
+    pub fn filter(mut self, filter: RecordFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn dialect(mut self, dialect: CsvDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Reject ambiguous bare probability numbers as calculator does in strict mode
+    pub fn strict_prob(mut self, strict: bool) -> Self {
+        self.strict_prob = strict;
+        self
+    }
+
+    /// Lint csv value and return every diagnostic found
+    pub fn lint(&self, csv_value: &str) -> GcalcResult<Vec<Diagnostic>> {
+        let mut diagnostics = vec![];
+        let mut indexer = Indexer::new();
+        self.dialect.add_table(&mut indexer, "ref", csv_value)?;
+        let mut table = indexer
+            .index_get_records(Query::from_str("SELECT * FROM ref FLAG PHD")?)?
+            .into_iter();
+        let header = table.next().unwrap_or_default();
+        let mut rows: Vec<Vec<String>> = table.collect();
+        let mut lines = Self::line_numbers(&self.dialect.prepare(csv_value), rows.len());
+
+        // Only lint rows that are used by calculator
+        if !self.filter.is_empty() {
+            let filtered = indexer.index_get_records(Query::from_str(&format!(
+                "SELECT * FROM ref{}",
+                self.filter.to_clause(&self.column_map)?
+            ))?)?;
+            let mut used = vec![false; rows.len()];
+            lines = filtered
+                .iter()
+                .map(|row| {
+                    let index = (0..rows.len())
+                        .find(|&i| !used[i] && &rows[i] == row)
+                        .unwrap_or_default();
+                    used[index] = true;
+                    lines[index]
+                })
+                .collect();
+            rows = filtered;
+
+            for predicate in self.filter.comparisons(&self.column_map)? {
+                let index = Self::header_position(&header, &predicate.column, "Filter")?;
+                let (kept_rows, kept_lines) = rows
+                    .into_iter()
+                    .zip(lines)
+                    .filter(|(row, _)| {
+                        let cell = row.get(index).map(|c| self.dialect.normalize_number(c));
+                        predicate.matches(&cell.unwrap_or_default())
+                    })
+                    .unzip();
+                rows = kept_rows;
+                lines = kept_lines;
+            }
+        }
+        if let Some((column, descending)) = self.filter.sort_order(&self.column_map)? {
+            let index = Self::header_position(&header, &column, "Sort")?;
+            let mut sorted: Vec<(String, Vec<String>, usize)> = rows
+                .into_iter()
+                .zip(lines)
+                .map(|(row, line)| {
+                    let cell = row.get(index).map(|c| self.dialect.normalize_number(c));
+                    (cell.unwrap_or_default(), row, line)
+                })
+                .collect();
+            RecordFilter::sort_rows(&mut sorted, descending, |(cell, _, _)| cell);
+            (rows, lines) = sorted.into_iter().map(|(_, row, line)| (row, line)).unzip();
+        }
+
+        // Position of each mapped column in a header, if any
+        let mut positions = HashMap::new();
+        for key in ["count", "prob", "cost", "constant"] {
+            let name = self.column_map.get(key).map(|s| s.as_str()).unwrap_or(key);
+            match header.iter().position(|h| h == name) {
+                Some(index) => {
+                    positions.insert(key, index);
+                }
+                None => {
+                    // Missing count and cost are supplemented with sane values,
+                    // while missing probability or constant fails every row.
+                    let (severity, effect) = match key {
+                        "count" => (Severity::Warning, "every row is applied in order"),
+                        "cost" => (Severity::Warning, "every cost is treated as 0"),
+                        _ => (Severity::Error, "every row will be invalid"),
+                    };
+                    diagnostics.push(self.diagnostic(
+                        1,
+                        None,
+                        severity,
+                        format!(
+                            "Missing column \"{}\" mapped as \"{}\", {}",
+                            name, key, effect
+                        ),
+                    ));
+                }
+            }
+        }
+
+        if rows.is_empty() {
+            diagnostics.push(self.diagnostic(
+                1,
+                None,
+                Severity::Warning,
+                "Reference has no rows".to_owned(),
+            ));
+        }
+
+        let mut previous_count: Option<usize> = None;
+        let mut guaranteed_at: Option<usize> = None;
+
+        for (index, row) in rows.iter().enumerate() {
+            let line = lines[index];
+            // Mapped columns are numeric, thus compared in a canonical form
+            let row: Vec<String> = row
+                .iter()
+                .map(|v| self.dialect.normalize_number(v))
+                .collect();
+            let cell = |key: &str| positions.get(key).map(|&pos| (pos + 1, row[pos].as_str()));
+
+            if let Some(count) = guaranteed_at {
+                diagnostics.push(self.diagnostic(
+                    line,
+                    None,
+                    Severity::Warning,
+                    format!(
+                        "Row is unreachable because success is guaranteed at count {}",
+                        count
+                    ),
+                ));
+            }
+
+            // Count
+            let mut count = index + 1;
+            if let Some((column, value)) = cell("count") {
+                if !value.is_empty() {
+                    match value.parse::<usize>() {
+                        // Count starts from 1
+                        Ok(parsed) if parsed > 0 => {
+                            count = parsed;
+                            match previous_count {
+                                Some(prev) if parsed == prev => {
+                                    diagnostics.push(self.diagnostic(
+                                        line,
+                                        Some(column),
+                                        Severity::Error,
+                                        format!("Duplicate count \"{}\", row will be applied to the next try", value),
+                                    ))
+                                }
+                                Some(prev) if parsed < prev => {
+                                    diagnostics.push(self.diagnostic(
+                                        line,
+                                        Some(column),
+                                        Severity::Error,
+                                        format!(
+                                            "Count \"{}\" is smaller than previous count \"{}\"",
+                                            value, prev
+                                        ),
+                                    ))
+                                }
+                                _ => (),
+                            }
+                            previous_count.replace(parsed);
+                        }
+                        _ => diagnostics.push(self.diagnostic(
+                            line,
+                            Some(column),
+                            Severity::Error,
+                            format!("Count \"{}\" is not a positive integer", value),
+                        )),
+                    }
+                }
+            }
+
+            // Probability and constant
+            let prob = self.lint_probability(cell("prob"), "Probability", line, &mut diagnostics);
+            let constant =
+                self.lint_probability(cell("constant"), "Constant", line, &mut diagnostics);
+
+            // Cost
+            if let Some((column, value)) = cell("cost") {
+                if !value.is_empty() {
+                    match value.parse::<f32>() {
+                        Ok(cost) if cost < 0.0 => diagnostics.push(self.diagnostic(
+                            line,
+                            Some(column),
+                            Severity::Warning,
+                            format!("Cost \"{}\" is a negative number", value),
+                        )),
+                        Ok(_) => (),
+                        Err(_) => diagnostics.push(self.diagnostic(
+                            line,
+                            Some(column),
+                            Severity::Error,
+                            format!("Cost \"{}\" is not a number", value),
+                        )),
+                    }
+                }
+            }
+
+            if guaranteed_at.is_none() && prob.unwrap_or(0.0) + constant.unwrap_or(0.0) >= 1.0 {
+                guaranteed_at.replace(count);
+            }
+        }
+
+        Ok(diagnostics)
+    }
+
+    /// Fail if diagnostics have an error, or a warning when warnings are denied
+    ///
+    /// Failure is a lint error, which makes the program exit with a nonzero code.
+    pub fn check(&self, diagnostics: &[Diagnostic], deny_warnings: bool) -> GcalcResult<()> {
+        let errors = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+        let warnings = diagnostics.len() - errors;
+        if errors > 0 || (warnings > 0 && deny_warnings) {
+            return Err(GcalcError::LintError(format!(
+                "{} error(s), {} warning(s) in \"{}\"",
+                errors, warnings, self.source
+            )));
+        }
+        Ok(())
+    }
+
+    /// Lint a probability formatted cell and return interpreted value
+    fn lint_probability(
+        &self,
+        cell: Option<(usize, &str)>,
+        name: &str,
+        line: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<f32> {
+        // Missing column is already reported
+        let (column, value) = cell?;
+        if value.is_empty() {
+            diagnostics.push(self.diagnostic(
+                line,
+                Some(column),
+                Severity::Error,
+                format!("{} is empty", name),
+            ));
+            return None;
+        }
+
+        match utils::parse_probability(value, self.strict_prob) {
+            Ok(prob) => {
+                // Bare numbers from 1 to 100 are read as percentages
+                if !self.strict_prob && utils::parse_probability(value, true).is_err() {
+                    diagnostics.push(self.diagnostic(
+                        line,
+                        Some(column),
+                        Severity::Warning,
+                        format!(
+                            "{} \"{}\" is ambiguous and interpreted as {}",
+                            name,
+                            value,
+                            utils::get_prob_as_formatted(prob, &ProbType::Percentage, &None)
+                        ),
+                    ));
+                }
+                Some(prob)
+            }
+            Err(err) => {
+                diagnostics.push(self.diagnostic(
+                    line,
+                    Some(column),
+                    Severity::Error,
+                    format!("{} is invalid : {}", name, err.detail()),
+                ));
+                None
+            }
+        }
+    }
+
+    fn diagnostic(
+        &self,
+        row: usize,
+        column: Option<usize>,
+        severity: Severity,
+        message: String,
+    ) -> Diagnostic {
+        Diagnostic {
+            source: self.source.clone(),
+            row,
+            column,
+            severity,
+            message,
+        }
+    }
+
+    /// Position of a column that is required by a filter
+    fn header_position(header: &[String], column: &str, kind: &str) -> GcalcResult<usize> {
+        header.iter().position(|h| h == column).ok_or_else(|| {
+            GcalcError::InvalidArgument(format!("{} column \"{}\" doesn't exist", kind, column))
+        })
+    }
+
+    /// Map data rows to line numbers of a source
+    ///
+    /// Reader skips blank lines, thus line numbers are counted the same way. If the mapping
+    /// doesn't match (e.g. quoted line breaks), fall back to the position after a header.
+    fn line_numbers(csv_value: &str, row_count: usize) -> Vec<usize> {
+        let lines: Vec<usize> = csv_value
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .skip(1)
+            .map(|(index, _)| index + 1)
+            .collect();
+
+        if lines.len() == row_count {
+            lines
+        } else {
+            (0..row_count).map(|index| index + 2).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(csv_value: &str) -> Vec<(usize, Option<usize>, Severity, String)> {
+        Linter::new("ref.csv")
+            .lint(csv_value)
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.row, d.column, d.severity, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn valid_reference_has_no_diagnostics() {
+        assert!(lint("count,prob,cost,constant\n1,0.1,10,0\n2,0.2,10,0\n").is_empty());
+    }
+
+    #[test]
+    fn invalid_counts_are_errors() {
+        let diagnostics = lint("count,prob,cost,constant\n0,0.1,10,0\n3,0.1,10,0\n3,0.1,10,0\n2,0.1,10,0\na,0.1,10,0\n");
+        let messages: Vec<(usize, &str)> = diagnostics
+            .iter()
+            .map(|(row, _, severity, message)| {
+                assert_eq!(*severity, Severity::Error);
+                (*row, message.as_str())
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (2, "Count \"0\" is not a positive integer"),
+                (
+                    4,
+                    "Duplicate count \"3\", row will be applied to the next try"
+                ),
+                (5, "Count \"2\" is smaller than previous count \"3\""),
+                (6, "Count \"a\" is not a positive integer"),
+            ]
+        );
+    }
+
+    #[test]
+    fn probabilities_are_checked_by_notation() {
+        let csv_value = "count,prob,cost,constant\n1,5,10,0\n2,1/200,10,0\n3,,10,0\n4,abc,10,0\n";
+        let diagnostics = lint(csv_value);
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].0, 2);
+        assert_eq!(diagnostics[0].1, Some(2));
+        assert_eq!(diagnostics[0].2, Severity::Warning);
+        assert!(diagnostics[0]
+            .3
+            .starts_with("Probability \"5\" is ambiguous"));
+        assert_eq!(
+            diagnostics[1],
+            (
+                4,
+                Some(2),
+                Severity::Error,
+                "Probability is empty".to_owned()
+            )
+        );
+        assert!(diagnostics[2].3.starts_with("Probability is invalid : "));
+
+        // Strict mode rejects an ambiguous number instead of warning
+        let strict = Linter::new("ref.csv")
+            .strict_prob(true)
+            .lint(csv_value)
+            .unwrap();
+        assert_eq!(strict[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn missing_columns_and_unreachable_rows_are_reported() {
+        let diagnostics = lint("count,prob\n1,0.5\n2,100%\n3,0.5\n");
+        let summary: Vec<(usize, Severity)> = diagnostics
+            .iter()
+            .map(|(row, _, severity, _)| (*row, *severity))
+            .collect();
+        // Missing cost is supplemented while missing constant fails every row
+        assert_eq!(
+            summary,
+            [
+                (1, Severity::Warning),
+                (1, Severity::Error),
+                (4, Severity::Warning)
+            ]
+        );
+        assert!(diagnostics[2].3.contains("guaranteed at count 2"));
+    }
+
+    #[test]
+    fn check_fails_on_errors_or_denied_warnings() {
+        let linter = Linter::new("ref.csv");
+        let warned = linter.lint("count,prob,cost,constant\n1,5,10,0\n").unwrap();
+        assert!(linter.check(&warned, false).is_ok());
+        assert!(matches!(
+            linter.check(&warned, true),
+            Err(GcalcError::LintError(_))
+        ));
+
+        let failed = linter
+            .lint("count,prob,cost,constant\n0,0.1,10,0\n")
+            .unwrap();
+        assert!(matches!(
+            linter.check(&failed, false),
+            Err(GcalcError::LintError(_))
+        ));
+    }
+}
@@ -1,63 +1,19 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::str::FromStr;
 
+use crate::utils;
 use crate::GcalcError;
+use cindex::{Indexer, ReaderOption};
 #[cfg(feature = "option")]
 use serde::Deserialize;
-#[cfg(feature = "option")]
+#[cfg(feature = "json")]
 use serde::Serialize;
-#[cfg(feature = "tabled")]
-use tabled::Tabled;
 
 pub type GcalcResult<T> = Result<T, GcalcError>;
 
-#[cfg_attr(feature = "tabled", derive(Tabled))]
-pub(crate) struct Qualficiation {
-    pub count: usize,
-    pub probability: String,
-    pub cost: f32,
-    pub value: f32,
-}
-
-impl Qualficiation {
-    pub fn new(count: usize, cost: f32, probability: &str, value: f32) -> Self {
-        Self {
-            count,
-            cost,
-            probability: probability.to_owned(),
-            value,
-        }
-    }
-
-    pub fn join_as_csv(&self) -> String {
-        let mut joined = self.count.to_string();
-        joined.push_str(&format!(",{}", self.probability));
-        joined.push_str(&format!(",{}", self.cost));
-        joined
-    }
-}
-
-#[cfg_attr(feature = "tabled", derive(Tabled))]
-pub(crate) struct FormatRecord {
-    pub count: usize,
-    pub probability: String,
-    pub cost: f32,
-    pub constant: f32,
-    pub value: f32,
-}
-
-impl FormatRecord {
-    pub fn from_record(record: &Record) -> Self {
-        Self {
-            count: record.count,
-            probability: record.probability.to_string(),
-            cost: record.cost,
-            constant: record.constant,
-            value: record.value.round(),
-        }
-    }
-}
-
+#[derive(Clone)]
 pub(crate) struct Record {
     pub count: usize,
     pub probability_src: f32, // Used by plots
@@ -65,6 +21,16 @@ pub(crate) struct Record {
     pub cost: f32,
     pub constant: f32,
     pub value: f32,
+    pub target: Option<(f32, String)>, // Raw and formatted target of a milestone
+    // Per-try marginal values as raw and formatted pairs
+    pub rate: (f32, String),          // Effective single try rate
+    pub first_success: (f32, String), // Probability that the first success is at this try
+    pub survival: (f32, String),      // Probability that no success happened until this try
+    // Cost and value formatted with a number and currency format, if any is given
+    pub cost_formatted: Option<String>,
+    pub value_formatted: Option<String>,
+    pub value_display: Option<String>, // Value for human readable tables
+    pub money: Option<(f32, String)>,  // Raw and formatted real money of a cost
 }
 
 impl Record {
@@ -83,16 +49,343 @@ impl Record {
             cost,
             constant,
             value,
+            target: None,
+            rate: (0.0, String::new()),
+            first_success: (0.0, String::new()),
+            survival: (0.0, String::new()),
+            cost_formatted: None,
+            value_formatted: None,
+            value_display: None,
+            money: None,
+        }
+    }
+
+    /// Format cost and value when number format has a precision or currency is given
+    ///
+    /// Real money is also calculated when currency has a conversion rate.
+    pub fn formatted_amount(mut self, format: &NumberFormat, currency: &CurrencyFormat) -> Self {
+        if format.precision.is_some() || !currency.is_plain() {
+            let display = match format.precision {
+                Some(_) => format.format(self.value),
+                None => self.value.round().to_string(),
+            };
+            self.cost_formatted
+                .replace(currency.apply(&format.format(self.cost)));
+            self.value_formatted
+                .replace(currency.apply(&format.format(self.value)));
+            self.value_display.replace(currency.apply(&display));
+        }
+        if let Some(rate) = currency.rate {
+            let money = self.cost * rate;
+            let format = NumberFormat {
+                precision: Some(currency.money_precision),
+                significant: None,
+                ..*format
+            };
+            self.money
+                .replace((money, currency.apply_money(&format.format(money))));
+        }
+        self
+    }
+
+    /// Set per-try marginal values
+    pub fn marginal(
+        mut self,
+        rate: (f32, String),
+        first_success: (f32, String),
+        survival: (f32, String),
+    ) -> Self {
+        self.rate = rate;
+        self.first_success = first_success;
+        self.survival = survival;
+        self
+    }
+
+    /// Raw and formatted value of a probability like column
+    pub fn probability_cell(&self, kind: ColumnKind) -> Option<(f32, &str)> {
+        let (raw, formatted) = match kind {
+            ColumnKind::Probability => (self.probability_src, self.probability.as_str()),
+            ColumnKind::Target => {
+                let (raw, formatted) = self.target.as_ref()?;
+                (*raw, formatted.as_str())
+            }
+            ColumnKind::Rate => (self.rate.0, self.rate.1.as_str()),
+            ColumnKind::FirstSuccess => (self.first_success.0, self.first_success.1.as_str()),
+            ColumnKind::Survival => (self.survival.0, self.survival.1.as_str()),
+            _ => return None,
+        };
+        Some((raw, formatted))
+    }
+
+    /// Mark record as a milestone that reached given target
+    pub fn milestone(mut self, target: f32, formatted: String) -> Self {
+        self.target.replace((target, formatted));
+        self
+    }
+
+    /// Text of a column as it is
+    pub fn cell(&self, kind: ColumnKind) -> String {
+        match kind {
+            ColumnKind::Count => self.count.to_string(),
+            ColumnKind::Probability => self.probability.clone(),
+            ColumnKind::Cost => self
+                .cost_formatted
+                .clone()
+                .unwrap_or_else(|| self.cost.to_string()),
+            ColumnKind::Constant => self.constant.to_string(),
+            ColumnKind::Value => self
+                .value_formatted
+                .clone()
+                .unwrap_or_else(|| self.value.to_string()),
+            ColumnKind::Money => self
+                .money
+                .as_ref()
+                .map(|(_, formatted)| formatted.clone())
+                .unwrap_or_default(),
+            ColumnKind::Target
+            | ColumnKind::Rate
+            | ColumnKind::FirstSuccess
+            | ColumnKind::Survival => self
+                .probability_cell(kind)
+                .map(|(_, formatted)| formatted.to_owned())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Text of a column for human readable tables
+    pub fn display_cell(&self, kind: ColumnKind) -> String {
+        match kind {
+            ColumnKind::Value => self
+                .value_display
+                .clone()
+                .unwrap_or_else(|| self.value.round().to_string()),
+            _ => self.cell(kind),
+        }
+    }
+}
+
+/// Column of an output table
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColumnKind {
+    Count,
+    Probability,
+    Cost,
+    Constant,
+    Value,
+    /// Target probability of a milestone
+    Target,
+    /// Effective single try rate, which is probability with constant
+    Rate,
+    /// Probability that the first success happens exactly at a try
+    FirstSuccess,
+    /// Probability that no success happened until a try
+    Survival,
+    /// Cost converted into real money
+    Money,
+}
+
+impl ColumnKind {
+    /// Columns of range and conditional records
+    pub const RECORD: &'static [ColumnKind] = &[
+        Self::Count,
+        Self::Probability,
+        Self::Cost,
+        Self::Constant,
+        Self::Value,
+        Self::Money,
+        Self::Rate,
+        Self::FirstSuccess,
+        Self::Survival,
+    ];
+    /// Columns of a qualification result
+    pub const QUALIFICATION: &'static [ColumnKind] = &[
+        Self::Count,
+        Self::Probability,
+        Self::Cost,
+        Self::Value,
+        Self::Money,
+        Self::Rate,
+        Self::FirstSuccess,
+        Self::Survival,
+    ];
+    /// Columns of milestones
+    pub const MILESTONE: &'static [ColumnKind] = &[
+        Self::Target,
+        Self::Count,
+        Self::Probability,
+        Self::Cost,
+        Self::Value,
+        Self::Money,
+        Self::Rate,
+        Self::FirstSuccess,
+        Self::Survival,
+    ];
+
+    /// Extra columns are only printed when selected
+    pub fn is_extra(&self) -> bool {
+        matches!(self, Self::Rate | Self::FirstSuccess | Self::Survival)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Count => "count",
+            Self::Probability => "probability",
+            Self::Cost => "cost",
+            Self::Constant => "constant",
+            Self::Value => "value",
+            Self::Target => "target",
+            Self::Rate => "rate",
+            Self::FirstSuccess => "first_success",
+            Self::Survival => "survival",
+            Self::Money => "money",
+        }
+    }
+}
+
+impl FromStr for ColumnKind {
+    type Err = GcalcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "count" => Ok(Self::Count),
+            "probability" | "prob" => Ok(Self::Probability),
+            "cost" => Ok(Self::Cost),
+            "constant" => Ok(Self::Constant),
+            "value" => Ok(Self::Value),
+            "target" => Ok(Self::Target),
+            "rate" | "hazard" => Ok(Self::Rate),
+            "first_success" | "pmf" => Ok(Self::FirstSuccess),
+            "survival" => Ok(Self::Survival),
+            "money" => Ok(Self::Money),
+            _ => Err(GcalcError::InvalidConversion(format!(
+                "{} is not a valid output column",
+                s
+            ))),
+        }
+    }
+}
+
+/// Output column with an optional custom header
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
+#[derive(Clone, PartialEq, Debug)]
+pub struct OutputColumn {
+    pub kind: ColumnKind,
+    pub header: Option<String>,
+}
+
+impl OutputColumn {
+    pub fn new(kind: ColumnKind, header: Option<&str>) -> Self {
+        Self {
+            kind,
+            header: header.map(|s| s.to_owned()),
+        }
+    }
+
+    pub fn header(&self) -> String {
+        self.header
+            .clone()
+            .unwrap_or_else(|| self.kind.name().to_owned())
+    }
+
+    /// Parse columns from text
+    ///
+    /// Text is a comma separated list of columns with optional headers, e.g.
+    /// "count,probability:Chance,cost:Gems".
+    pub fn parse_list(text: &str) -> GcalcResult<Vec<Self>> {
+        text.split(',')
+            .map(|item| {
+                let (kind, header) = match item.split_once(':') {
+                    Some((kind, header)) => (kind.trim(), Some(header.trim())),
+                    None => (item.trim(), None),
+                };
+                Ok(Self::new(ColumnKind::from_str(kind)?, header))
+            })
+            .collect()
+    }
+
+    /// Resolve selected columns against columns that an output has
+    ///
+    /// Empty selection means every available column except extra columns. Unavailable columns
+    /// are skipped and a column is printed only once.
+    pub(crate) fn resolve(columns: &[Self], available: &[ColumnKind]) -> GcalcResult<Vec<Self>> {
+        if columns.is_empty() {
+            return Ok(available
+                .iter()
+                .filter(|kind| !kind.is_extra())
+                .map(|&kind| Self::new(kind, None))
+                .collect());
+        }
+
+        let mut resolved: Vec<Self> = vec![];
+        for column in columns {
+            if available.contains(&column.kind) && !resolved.iter().any(|c| c.kind == column.kind) {
+                resolved.push(column.clone());
+            }
         }
+        if resolved.is_empty() {
+            return Err(GcalcError::InvalidArgument(
+                "None of selected columns is available for the output".to_owned(),
+            ));
+        }
+        Ok(resolved)
     }
+}
+
+/// Inputs used for a calculation
+///
+/// Structured outputs carry this alongside results so that a result can be reproduced.
+#[cfg_attr(feature = "json", derive(Serialize))]
+pub struct InputSummary {
+    /// Calculation mode, one of "range", "cond" and "qual"
+    pub mode: String,
+    pub probability: f32,
+    pub constant: f32,
+    pub cost: f32,
+    pub count: Option<usize>,
+    pub budget: Option<f32>,
+    pub pity: Option<usize>,
+    pub target: Option<f32>,
+    /// Milestone targets
+    pub targets: Vec<f32>,
+    pub value: Option<f32>,
+    /// Reference file path, "<raw>" for a raw value
+    pub reference: Option<String>,
+    pub filter: Vec<String>,
+    /// Conversion rate of cost into real money
+    pub money_rate: Option<f32>,
+}
 
-    pub fn join_as_csv(&self) -> String {
-        let mut joined = self.count.to_string();
-        joined.push_str(&format!(",{}", self.probability));
-        joined.push_str(&format!(",{}", self.cost));
-        joined.push_str(&format!(",{}", self.constant));
-        joined.push_str(&format!(",{}", self.value));
-        joined
+impl InputSummary {
+    /// Name and value pairs of given inputs, for human readable outputs
+    pub(crate) fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut entries = vec![
+            ("mode", self.mode.clone()),
+            ("probability", self.probability.to_string()),
+            ("constant", self.constant.to_string()),
+            ("cost", self.cost.to_string()),
+        ];
+        let optionals = [
+            ("count", self.count.map(|v| v.to_string())),
+            ("budget", self.budget.map(|v| v.to_string())),
+            ("pity", self.pity.map(|v| v.to_string())),
+            ("target", self.target.map(|v| v.to_string())),
+            ("value", self.value.map(|v| v.to_string())),
+            ("reference", self.reference.clone()),
+            ("money rate", self.money_rate.map(|v| v.to_string())),
+        ];
+        entries.extend(
+            optionals
+                .iter()
+                .filter_map(|(name, value)| value.clone().map(|v| (*name, v))),
+        );
+        if !self.targets.is_empty() {
+            let targets: Vec<String> = self.targets.iter().map(|t| t.to_string()).collect();
+            entries.push(("targets", targets.join(",")));
+        }
+        if !self.filter.is_empty() {
+            entries.push(("filter", self.filter.join(" AND ")));
+        }
+        entries
     }
 }
 
@@ -104,8 +397,404 @@ pub enum CsvRef {
     None,
 }
 
-#[cfg_attr(feature = "option", derive(Serialize, Deserialize, Clone, Copy))]
-#[derive(PartialEq)]
+/// Row filter applied to a reference before calculation
+///
+/// Predicates are joined with AND and have a form of "column=value". "=" and "!=" compare text
+/// while ">", ">=", "<" and "<=" compare numbers, which never match a non-numeric cell.
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize, Clone))]
+#[derive(Default, PartialEq)]
+pub struct RecordFilter {
+    pub predicates: Vec<String>,
+    /// Column to sort rows with, e.g. "count" or "count:desc"
+    pub order: Option<String>,
+}
+
+impl RecordFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn predicate(mut self, predicate: &str) -> Self {
+        self.predicates.push(predicate.to_owned());
+        self
+    }
+
+    pub fn order(mut self, order: &str) -> Self {
+        self.order.replace(order.to_owned());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.predicates.is_empty() && self.order.is_none()
+    }
+
+    /// Convert equality predicates into a query clause
+    ///
+    /// Column names that are keys of a column map (e.g. "count") are replaced with mapped names.
+    /// Numeric comparisons are not a part of the clause, see [`RecordFilter::comparisons`].
+    pub(crate) fn to_clause(&self, column_map: &HashMap<String, String>) -> GcalcResult<String> {
+        let mut clause = String::new();
+        for predicate in self.parse_predicates(column_map)? {
+            if predicate.is_comparison() {
+                continue;
+            }
+            clause.push_str(if clause.is_empty() {
+                " WHERE "
+            } else {
+                " AND "
+            });
+            clause.push_str(&format!(
+                "{} {} {}",
+                predicate.column,
+                predicate.operator,
+                Self::quote_value(&predicate.value)
+            ));
+        }
+        Ok(clause)
+    }
+
+    /// Predicates that compare numbers, which are applied to queried rows
+    ///
+    /// Query compares cells as text which puts "10" before "2", thus these are matched here.
+    pub(crate) fn comparisons(
+        &self,
+        column_map: &HashMap<String, String>,
+    ) -> GcalcResult<Vec<Predicate>> {
+        Ok(self
+            .parse_predicates(column_map)?
+            .into_iter()
+            .filter(|predicate| predicate.is_comparison())
+            .collect())
+    }
+
+    /// Mapped column name to sort with and whether an order is descending
+    pub(crate) fn sort_order(
+        &self,
+        column_map: &HashMap<String, String>,
+    ) -> GcalcResult<Option<(String, bool)>> {
+        let order = match &self.order {
+            Some(order) => order,
+            None => return Ok(None),
+        };
+        let mut split = order.split(|c: char| c == ':' || c.is_whitespace());
+        let column = split.next().unwrap_or_default();
+        let descending = match split.next().map(|s| s.to_lowercase()).as_deref() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(other) => {
+                return Err(GcalcError::InvalidArgument(format!(
+                    "\"{}\" is not a valid sort order, use asc or desc",
+                    other
+                )))
+            }
+        };
+        if column.is_empty() {
+            return Err(GcalcError::InvalidArgument(
+                "Sort requires a column name".to_owned(),
+            ));
+        }
+        Ok(Some((Self::map_column(column_map, column), descending)))
+    }
+
+    /// Sort rows by a cell of each row
+    ///
+    /// Query sorts cells as text which puts "10" before "2", thus rows are sorted here.
+    /// Numeric cells are compared as numbers and always come before other cells, which are compared
+    /// as text. Sort is stable so that rows with the same cell keep a source order.
+    pub(crate) fn sort_rows<T>(rows: &mut [T], descending: bool, cell: impl Fn(&T) -> &str) {
+        rows.sort_by(|a, b| {
+            let (a, b) = (cell(a).trim(), cell(b).trim());
+            let ordering = match (a.parse::<f64>(), b.parse::<f64>()) {
+                (Ok(x), Ok(y)) => x.total_cmp(&y),
+                (Ok(_), Err(_)) => return Ordering::Less,
+                (Err(_), Ok(_)) => return Ordering::Greater,
+                (Err(_), Err(_)) => a.cmp(b),
+            };
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    fn map_column(column_map: &HashMap<String, String>, column: &str) -> String {
+        column_map
+            .get(column)
+            .map(|s| s.to_owned())
+            .unwrap_or_else(|| column.to_owned())
+    }
+
+    fn parse_predicates(
+        &self,
+        column_map: &HashMap<String, String>,
+    ) -> GcalcResult<Vec<Predicate>> {
+        self.predicates
+            .iter()
+            .map(|predicate| {
+                let mut predicate = Self::parse_predicate(predicate)?;
+                predicate.column = Self::map_column(column_map, &predicate.column);
+                Ok(predicate)
+            })
+            .collect()
+    }
+
+    fn parse_predicate(predicate: &str) -> GcalcResult<Predicate> {
+        // Find the leftmost operator, prefer longer operator for same position
+        let (pos, operator) = [">=", "<=", "!=", "=", ">", "<"]
+            .iter()
+            .filter_map(|op| predicate.find(op).map(|pos| (pos, *op)))
+            .min_by(|a, b| a.0.cmp(&b.0).then(b.1.len().cmp(&a.1.len())))
+            .ok_or_else(|| {
+                GcalcError::InvalidArgument(format!(
+                    "Filter \"{}\" doesn't have a valid operator, use =, !=, >, >=, < or <=",
+                    predicate
+                ))
+            })?;
+
+        let column = predicate[..pos].trim();
+        let value = predicate[pos + operator.len()..].trim();
+        if column.is_empty() || value.is_empty() {
+            return Err(GcalcError::InvalidArgument(format!(
+                "Filter \"{}\" requires both column and value",
+                predicate
+            )));
+        }
+        if column.contains(char::is_whitespace) {
+            return Err(GcalcError::InvalidArgument(format!(
+                "Filter \"{}\" has an invalid column \"{}\"",
+                predicate, column
+            )));
+        }
+        // Query treats these as separators regardless of quotes
+        if value == "AND" || value == "OR" {
+            return Err(GcalcError::InvalidArgument(format!(
+                "Filter \"{}\" can't compare with \"{}\"",
+                predicate, value
+            )));
+        }
+
+        let parsed = Predicate {
+            column: column.to_owned(),
+            operator,
+            value: value.to_owned(),
+        };
+        if parsed.is_comparison() && parsed.number().is_none() {
+            return Err(GcalcError::InvalidArgument(format!(
+                "Filter \"{}\" compares numbers but \"{}\" is not a number",
+                predicate, value
+            )));
+        }
+        Ok(parsed)
+    }
+
+    /// Quote a value so that it is a single query argument
+    ///
+    /// Query toggles quotation with a backslash as well, thus a quote or a backslash is written
+    /// outside of quotation as an escape sequence followed by a quote that closes it.
+    fn quote_value(value: &str) -> String {
+        let mut quoted = String::from("'");
+        for c in value.chars() {
+            if c == '\'' || c == '\\' {
+                quoted.push_str("'\\");
+                quoted.push(c);
+                quoted.push_str("''");
+            } else {
+                quoted.push(c);
+            }
+        }
+        quoted.push('\'');
+        quoted
+    }
+}
+
+/// Single predicate of a [`RecordFilter`]
+#[derive(Debug, PartialEq)]
+pub(crate) struct Predicate {
+    pub column: String,
+    pub operator: &'static str,
+    pub value: String,
+}
+
+impl Predicate {
+    /// Whether a predicate compares numbers rather than text
+    pub fn is_comparison(&self) -> bool {
+        !matches!(self.operator, "=" | "!=")
+    }
+
+    /// Check if a cell satisfies a numeric comparison
+    pub fn matches(&self, cell: &str) -> bool {
+        let (cell, value) = match (cell.trim().parse::<f64>(), self.number()) {
+            (Ok(cell), Some(value)) => (cell, value),
+            _ => return false,
+        };
+        match self.operator {
+            ">" => cell > value,
+            ">=" => cell >= value,
+            "<" => cell < value,
+            _ => cell <= value,
+        }
+    }
+
+    fn number(&self) -> Option<f64> {
+        self.value.parse().ok()
+    }
+}
+
+/// Dialect of a reference csv
+///
+/// Byte order mark is always stripped. Comment lines are kept as empty lines so that line
+/// numbers stay the same with a source.
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "option", serde(default))]
+#[derive(Clone, PartialEq)]
+pub struct CsvDialect {
+    pub delimiter: char,
+    /// Decimal separator of numeric cells
+    pub decimal: char,
+    /// Thousands separator of numeric cells
+    pub thousands: Option<char>,
+    /// Prefix of comment lines, e.g. "#"
+    pub comment: Option<String>,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CsvDialect {
+    pub fn new() -> Self {
+        Self {
+            delimiter: ',',
+            decimal: '.',
+            thousands: None,
+            comment: None,
+        }
+    }
+
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn decimal(mut self, decimal: char) -> Self {
+        self.decimal = decimal;
+        self
+    }
+
+    pub fn thousands(mut self, thousands: char) -> Self {
+        self.thousands.replace(thousands);
+        self
+    }
+
+    pub fn comment(mut self, comment: &str) -> Self {
+        self.comment.replace(comment.to_owned());
+        self
+    }
+
+    /// Parse a separator character from text
+    ///
+    /// Names such as "tab" or "semicolon" are accepted alongside a single character.
+    pub fn parse_separator(text: &str) -> GcalcResult<char> {
+        let separator = match text.to_lowercase().as_str() {
+            "tab" | "\\t" => '\t',
+            "comma" => ',',
+            "semicolon" => ';',
+            "pipe" => '|',
+            "space" => ' ',
+            "dot" | "period" => '.',
+            "apostrophe" => '\'',
+            _ => {
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => {
+                        return Err(GcalcError::InvalidArgument(format!(
+                            "\"{}\" is not a valid separator, use a single character",
+                            text
+                        )))
+                    }
+                }
+            }
+        };
+        Ok(separator)
+    }
+
+    /// Check if separators are distinguishable
+    pub fn validate(&self) -> GcalcResult<()> {
+        if self.decimal == self.delimiter || self.thousands == Some(self.delimiter) {
+            return Err(GcalcError::InvalidArgument(
+                "Decimal and thousands separators should differ from delimiter".to_owned(),
+            ));
+        }
+        if self.thousands == Some(self.decimal) {
+            return Err(GcalcError::InvalidArgument(
+                "Decimal and thousands separators should differ".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Strip byte order mark and blank out comment lines
+    pub fn prepare(&self, csv_value: &str) -> String {
+        let csv_value = csv_value.trim_start_matches('\u{feff}');
+        match &self.comment {
+            Some(prefix) if !prefix.is_empty() => csv_value
+                .lines()
+                .map(|line| {
+                    if line.trim_start().starts_with(prefix.as_str()) {
+                        ""
+                    } else {
+                        line
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => csv_value.to_owned(),
+        }
+    }
+
+    /// Add csv value to indexer as a table
+    pub(crate) fn add_table(
+        &self,
+        indexer: &mut Indexer,
+        table: &str,
+        csv_value: &str,
+    ) -> GcalcResult<()> {
+        self.validate()?;
+        let mut option = ReaderOption::new();
+        option.consume_dquote = true;
+        option.ignore_empty_row = true;
+        option.delimiter.replace(self.delimiter);
+        indexer.add_table_with_option(table, self.prepare(csv_value).as_bytes(), option)?;
+        Ok(())
+    }
+
+    /// Convert a numeric cell into a canonical form
+    ///
+    /// Thousands separator is only removed between digits, so that notations like "1 in 300"
+    /// are kept intact with a space separator.
+    pub fn normalize_number(&self, cell: &str) -> String {
+        let chars: Vec<char> = cell.chars().collect();
+        let mut normalized = String::with_capacity(cell.len());
+        for (index, &c) in chars.iter().enumerate() {
+            if Some(c) == self.thousands
+                && index > 0
+                && chars[index - 1].is_ascii_digit()
+                && chars.get(index + 1).is_some_and(|n| n.is_ascii_digit())
+            {
+                continue;
+            }
+            normalized.push(if c == self.decimal { '.' } else { c });
+        }
+        normalized
+    }
+}
+
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum CSVInvalidBehaviour {
     Rollback,
     Ignore,
@@ -117,7 +806,7 @@ impl CSVInvalidBehaviour {
         let varirant = match text.to_lowercase().as_str() {
             "rollback" => Self::Rollback,
             "ignore" => Self::Ignore,
-            "none" => Self::None,
+            "none" | "error" => Self::None,
             _ => {
                 return Err(GcalcError::InvalidConversion(format!(
                     "{} is not a valid csv fallback behaviour variant",
@@ -129,6 +818,149 @@ impl CSVInvalidBehaviour {
     }
 }
 
+/// Fallback policy of a single reference column
+#[derive(Clone, Copy)]
+pub struct ColumnFallback {
+    pub behaviour: CSVInvalidBehaviour,
+    /// Value to use when a cell is empty
+    pub default: Option<f32>,
+}
+
+impl ColumnFallback {
+    pub fn new(behaviour: CSVInvalidBehaviour, default: Option<f32>) -> Self {
+        Self { behaviour, default }
+    }
+
+    /// Resolve a cell into a value
+    ///
+    /// Empty cell uses default value if any, otherwise it is treated as an invalid value.
+    /// Invalid value is handled by fallback behaviour.
+    pub fn resolve(
+        &self,
+        cell: &str,
+        current: f32,
+        initial: f32,
+        parse: impl Fn(&str) -> GcalcResult<f32>,
+    ) -> GcalcResult<f32> {
+        let result = if cell.is_empty() {
+            self.default
+                .ok_or_else(|| GcalcError::ParseError("Value is empty".to_owned()))
+        } else {
+            parse(cell)
+        };
+
+        match result {
+            Ok(value) => Ok(value),
+            Err(err) => match self.behaviour {
+                CSVInvalidBehaviour::None => Err(err),
+                CSVInvalidBehaviour::Ignore => Ok(current),
+                CSVInvalidBehaviour::Rollback => Ok(initial),
+            },
+        }
+    }
+}
+
+/// Fallback policies for reference columns
+#[derive(Clone, Copy)]
+pub struct CsvFallback {
+    pub probability: ColumnFallback,
+    pub constant: ColumnFallback,
+    pub cost: ColumnFallback,
+}
+
+impl CsvFallback {
+    /// Create fallback with the same behaviour for every column
+    ///
+    /// Empty cost is 0 by default while other empty values are invalid.
+    pub fn new(behaviour: CSVInvalidBehaviour) -> Self {
+        Self {
+            probability: ColumnFallback::new(behaviour, None),
+            constant: ColumnFallback::new(behaviour, None),
+            cost: ColumnFallback::new(behaviour, Some(0.0)),
+        }
+    }
+
+    pub fn column_mut(&mut self, column: &str) -> GcalcResult<&mut ColumnFallback> {
+        match column.to_lowercase().as_str() {
+            "prob" | "probability" => Ok(&mut self.probability),
+            "constant" => Ok(&mut self.constant),
+            "cost" => Ok(&mut self.cost),
+            _ => Err(GcalcError::InvalidConversion(format!(
+                "{} is not a column with fallback",
+                column
+            ))),
+        }
+    }
+
+    /// Set behaviours from text
+    ///
+    /// Text is a comma separated list of either a behaviour for every column (e.g. "rollback")
+    /// or a behaviour for a column (e.g. "constant=rollback"), applied in order.
+    pub fn set_behaviours(&mut self, text: &str) -> GcalcResult<()> {
+        for item in text.split(',').map(|s| s.trim()) {
+            if let Some((column, behaviour)) = item.split_once('=') {
+                self.column_mut(column.trim())?.behaviour =
+                    CSVInvalidBehaviour::from_str(behaviour.trim())?;
+            } else {
+                let behaviour = CSVInvalidBehaviour::from_str(item)?;
+                self.probability.behaviour = behaviour;
+                self.constant.behaviour = behaviour;
+                self.cost.behaviour = behaviour;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set empty cell defaults from text (e.g. "cost=0,constant=0.5%")
+    ///
+    /// Probability like columns accept the same notation with other probabilities, where strict
+    /// mode rejects ambiguous bare numbers.
+    pub fn set_defaults(&mut self, text: &str, strict: bool) -> GcalcResult<()> {
+        for item in text.split(',').map(|s| s.trim()) {
+            let (column, value) = item.split_once('=').ok_or_else(|| {
+                GcalcError::InvalidArgument(format!(
+                    "Could not make a column default from given value : \"{}\"",
+                    item
+                ))
+            })?;
+            let (column, value) = (column.trim(), value.trim());
+            let value = if Self::is_probability(column) {
+                utils::parse_probability(value, strict).map_err(|err| {
+                    GcalcError::ParseError(format!(
+                        "Default of \"{}\" is invalid : {}",
+                        column,
+                        err.detail()
+                    ))
+                })?
+            } else {
+                value.parse::<f32>().map_err(|err| {
+                    GcalcError::ParseError(format!(
+                        "Default of \"{}\" should be a number : {}",
+                        column, err
+                    ))
+                })?
+            };
+            self.column_mut(column)?.default.replace(value);
+        }
+        Ok(())
+    }
+
+    /// Set empty cell default of a column from a number of an option file
+    #[cfg(feature = "option")]
+    pub fn set_default(&mut self, column: &str, mut value: f32) -> GcalcResult<()> {
+        // Probability like columns follow the same interpretation with other numeric inputs
+        if Self::is_probability(column) {
+            value = utils::get_number_as_fraction(value)?;
+        }
+        self.column_mut(column)?.default.replace(value);
+        Ok(())
+    }
+
+    fn is_probability(column: &str) -> bool {
+        !column.eq_ignore_ascii_case("cost")
+    }
+}
+
 #[cfg_attr(feature = "option", derive(Serialize, Deserialize, Clone))]
 pub enum OutOption {
     Console,
@@ -138,24 +970,1282 @@ pub enum OutOption {
 pub enum RecordCursor {
     Next,
     Stay,
+    End,
 }
 
-#[cfg_attr(feature = "option", derive(Serialize, Deserialize, Clone, Copy))]
-pub enum ProbType {
-    Percentage,
-    Fraction,
+/// Which behaviour to take when reference rows end
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum CsvRecordBehaviour {
+    /// Reuse the last row
+    Repeat,
+    /// Return error (Strict mode)
+    Panic,
+    /// Loop back to the first row, counts are relative to each cycle
+    Cycle,
+    /// End calculation at the last row
+    Stop,
 }
 
-impl FromStr for ProbType {
+/// Rounding mode of formatted numbers
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum RoundingMode {
+    #[default]
+    Truncate,
+    HalfUp,
+    HalfEven,
+    Ceil,
+    Floor,
+}
+
+impl FromStr for RoundingMode {
     type Err = GcalcError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "percentage" | "percent" => Ok(Self::Percentage),
-            "float" => Ok(Self::Fraction),
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "truncate" | "trunc" => Ok(Self::Truncate),
+            "half-up" | "round" => Ok(Self::HalfUp),
+            "half-even" | "bankers" => Ok(Self::HalfEven),
+            "ceil" => Ok(Self::Ceil),
+            "floor" => Ok(Self::Floor),
             _ => Err(GcalcError::InvalidConversion(format!(
-                "{} is not a valid table format",
+                "{} is not a valid rounding mode",
                 s
             ))),
         }
     }
 }
+
+/// Number formatting of output columns
+///
+/// Significant digits extend precision for small numbers, e.g. 0.00042% with a precision of 2
+/// and 2 significant digits. Numbers are printed as they are without precision.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct NumberFormat {
+    pub precision: Option<usize>,
+    pub rounding: RoundingMode,
+    pub significant: Option<usize>,
+}
+
+impl NumberFormat {
+    pub fn format(&self, num: f32) -> String {
+        utils::format_number(num, self)
+    }
+}
+
+/// Currency format of cost and value columns
+///
+/// Cost is converted into real money with a conversion rate, e.g. gems into USD, which is
+/// printed as a money column.
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "option", serde(default))]
+#[derive(Clone, PartialEq, Debug)]
+pub struct CurrencyFormat {
+    /// Thousands separator of integer digits
+    pub thousands: Option<char>,
+    pub prefix: String,
+    pub suffix: String,
+    /// Real money per a unit of cost
+    pub rate: Option<f32>,
+    pub money_prefix: String,
+    pub money_suffix: String,
+    pub money_precision: usize,
+}
+
+impl Default for CurrencyFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CurrencyFormat {
+    pub fn new() -> Self {
+        Self {
+            thousands: None,
+            prefix: String::new(),
+            suffix: String::new(),
+            rate: None,
+            money_prefix: String::new(),
+            money_suffix: String::new(),
+            money_precision: 2,
+        }
+    }
+
+    pub fn thousands(mut self, thousands: char) -> Self {
+        self.thousands.replace(thousands);
+        self
+    }
+
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    pub fn suffix(mut self, suffix: &str) -> Self {
+        self.suffix = suffix.to_owned();
+        self
+    }
+
+    pub fn rate(mut self, rate: f32) -> Self {
+        self.rate.replace(rate);
+        self
+    }
+
+    pub fn money_prefix(mut self, prefix: &str) -> Self {
+        self.money_prefix = prefix.to_owned();
+        self
+    }
+
+    pub fn money_suffix(mut self, suffix: &str) -> Self {
+        self.money_suffix = suffix.to_owned();
+        self
+    }
+
+    pub fn money_precision(mut self, precision: usize) -> Self {
+        self.money_precision = precision;
+        self
+    }
+
+    /// Check if separators and a conversion rate are valid
+    pub fn validate(&self) -> GcalcResult<()> {
+        if let Some(thousands) = self.thousands {
+            if thousands == '.' || thousands == '-' || thousands.is_ascii_digit() {
+                return Err(GcalcError::InvalidArgument(format!(
+                    "\"{}\" cannot be used as a thousands separator",
+                    thousands
+                )));
+            }
+        }
+        if let Some(rate) = self.rate {
+            if !(rate.is_finite() && rate > 0.0) {
+                return Err(GcalcError::InvalidArgument(format!(
+                    "Conversion rate \"{}\" should be a positive number",
+                    rate
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether cost and value are printed as they are
+    pub fn is_plain(&self) -> bool {
+        self.thousands.is_none() && self.prefix.is_empty() && self.suffix.is_empty()
+    }
+
+    /// Decorate a formatted cost or value
+    pub fn apply(&self, number: &str) -> String {
+        self.decorate(number, &self.prefix, &self.suffix)
+    }
+
+    /// Decorate a formatted money
+    pub fn apply_money(&self, number: &str) -> String {
+        self.decorate(number, &self.money_prefix, &self.money_suffix)
+    }
+
+    fn decorate(&self, number: &str, prefix: &str, suffix: &str) -> String {
+        let (sign, unsigned) = match number.strip_prefix('-') {
+            Some(unsigned) => ("-", unsigned),
+            None => ("", number),
+        };
+        let (integer, fraction) = match unsigned.find('.') {
+            Some(index) => unsigned.split_at(index),
+            None => (unsigned, ""),
+        };
+
+        let mut grouped = String::new();
+        match self.thousands {
+            // Scientific notations or non finite numbers are kept as they are
+            Some(thousands) if integer.bytes().all(|b| b.is_ascii_digit()) => {
+                for (index, digit) in integer.chars().enumerate() {
+                    if index > 0 && (integer.len() - index) % 3 == 0 {
+                        grouped.push(thousands);
+                    }
+                    grouped.push(digit);
+                }
+            }
+            _ => grouped.push_str(integer),
+        }
+        format!("{}{}{}{}{}", sign, prefix, grouped, fraction, suffix)
+    }
+}
+
+/// Image format of a chart
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlotFormat {
+    Svg,
+    Png,
+    /// Vega-Lite specification with embedded data
+    VegaLite,
+}
+
+impl PlotFormat {
+    /// Extension of a chart file of this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Svg => "svg",
+            Self::Png => "png",
+            Self::VegaLite => "json",
+        }
+    }
+}
+
+impl FromStr for PlotFormat {
+    type Err = GcalcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "svg" => Ok(Self::Svg),
+            "png" => Ok(Self::Png),
+            "vega" | "vega-lite" | "vl" => Ok(Self::VegaLite),
+            _ => Err(GcalcError::InvalidConversion(format!(
+                "{} is not a valid plot format",
+                s
+            ))),
+        }
+    }
+}
+
+/// Kind of a chart
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum PlotKind {
+    /// Cumulative probability and cost lines
+    #[default]
+    Cumulative,
+    /// Per-try first success probability bars
+    Histogram,
+    /// Probability of no success yet, on a logarithmic axis
+    Survival,
+}
+
+impl FromStr for PlotKind {
+    type Err = GcalcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cumulative" | "line" => Ok(Self::Cumulative),
+            "histogram" | "first_success" | "pmf" => Ok(Self::Histogram),
+            "survival" | "tail" => Ok(Self::Survival),
+            _ => Err(GcalcError::InvalidConversion(format!(
+                "{} is not a valid plot kind",
+                s
+            ))),
+        }
+    }
+}
+
+/// Where a chart is drawn
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum PlotTarget {
+    /// Image file of a path
+    #[default]
+    File,
+    /// Braille characters printed after a table
+    Terminal,
+}
+
+impl FromStr for PlotTarget {
+    type Err = GcalcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "file" => Ok(Self::File),
+            "term" | "terminal" => Ok(Self::Terminal),
+            _ => Err(GcalcError::InvalidConversion(format!(
+                "{} is not a valid plot target",
+                s
+            ))),
+        }
+    }
+}
+
+/// Attributes of a chart
+///
+/// Colours are either hex codes such as "#ff0000" or basic colour names. Image format follows
+/// an extension of a path when it is not given.
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "option", serde(default))]
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlotAttribute {
+    pub kind: PlotKind,
+    pub target: PlotTarget,
+    /// Median, 90th percentile and pity markers of a histogram
+    pub markers: bool,
+    /// Target, budget, pity and milestone annotations
+    pub annotations: bool,
+    /// Logarithmic count axis
+    pub log_x: bool,
+    /// Count range to zoom into
+    pub x_range: Option<(f64, f64)>,
+    /// Probability range to zoom into
+    pub y_range: Option<(f64, f64)>,
+    pub path: PathBuf,
+    pub format: Option<PlotFormat>,
+    pub caption: String,
+    pub prob_caption: String,
+    pub cost_caption: String,
+    pub font_type: String,
+    pub font_size: u8,
+    pub img_size: (u32, u32),
+    pub prob_color: String,
+    pub cost_color: String,
+    pub background: String,
+}
+
+impl Default for PlotAttribute {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlotAttribute {
+    pub fn new() -> Self {
+        Self {
+            kind: PlotKind::Cumulative,
+            target: PlotTarget::File,
+            markers: false,
+            annotations: true,
+            log_x: false,
+            x_range: None,
+            y_range: None,
+            path: PathBuf::from("out.svg"),
+            format: None,
+            caption: "Gcalc result".to_owned(),
+            prob_caption: "Prob (r)".to_owned(),
+            cost_caption: "Cost (b)".to_owned(),
+            font_type: "Helvetica".to_owned(),
+            font_size: 50,
+            img_size: (1000, 1000),
+            prob_color: "red".to_owned(),
+            cost_color: "blue".to_owned(),
+            background: "white".to_owned(),
+        }
+    }
+
+    pub fn kind(mut self, kind: PlotKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn target(mut self, target: PlotTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn markers(mut self, tv: bool) -> Self {
+        self.markers = tv;
+        self
+    }
+
+    pub fn annotations(mut self, tv: bool) -> Self {
+        self.annotations = tv;
+        self
+    }
+
+    pub fn log_x(mut self, tv: bool) -> Self {
+        self.log_x = tv;
+        self
+    }
+
+    pub fn x_range(mut self, min: f64, max: f64) -> Self {
+        self.x_range.replace((min, max));
+        self
+    }
+
+    pub fn y_range(mut self, min: f64, max: f64) -> Self {
+        self.y_range.replace((min, max));
+        self
+    }
+
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    pub fn format(mut self, format: PlotFormat) -> Self {
+        self.format.replace(format);
+        self
+    }
+
+    pub fn caption(mut self, caption: &str) -> Self {
+        self.caption = caption.to_owned();
+        self
+    }
+
+    pub fn prob_caption(mut self, caption: &str) -> Self {
+        self.prob_caption = caption.to_owned();
+        self
+    }
+
+    pub fn cost_caption(mut self, caption: &str) -> Self {
+        self.cost_caption = caption.to_owned();
+        self
+    }
+
+    pub fn font(mut self, font_type: &str) -> Self {
+        self.font_type = font_type.to_owned();
+        self
+    }
+
+    pub fn font_size(mut self, font_size: u8) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.img_size = (width, height);
+        self
+    }
+
+    pub fn prob_color(mut self, color: &str) -> Self {
+        self.prob_color = color.to_owned();
+        self
+    }
+
+    pub fn cost_color(mut self, color: &str) -> Self {
+        self.cost_color = color.to_owned();
+        self
+    }
+
+    pub fn background(mut self, color: &str) -> Self {
+        self.background = color.to_owned();
+        self
+    }
+
+    /// Image format from a format or an extension of a path
+    pub fn image_format(&self) -> PlotFormat {
+        match self.format {
+            Some(format) => format,
+            None => match self.path.extension().and_then(|ext| ext.to_str()) {
+                Some(ext) if ext.eq_ignore_ascii_case("png") => PlotFormat::Png,
+                Some(ext) if ext.eq_ignore_ascii_case("json") => PlotFormat::VegaLite,
+                _ => PlotFormat::Svg,
+            },
+        }
+    }
+
+    /// Parse an image size such as "1280x720"
+    pub fn parse_size(text: &str) -> GcalcResult<(u32, u32)> {
+        let size = text
+            .to_lowercase()
+            .split_once('x')
+            .and_then(|(width, height)| {
+                Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+            });
+        match size {
+            Some((width, height)) if width > 0 && height > 0 => Ok((width, height)),
+            _ => Err(GcalcError::InvalidArgument(format!(
+                "\"{}\" is not a valid image size, use a form of WIDTHxHEIGHT",
+                text
+            ))),
+        }
+    }
+
+    /// Parse an axis range such as "1:500"
+    pub fn parse_range(text: &str) -> GcalcResult<(f64, f64)> {
+        let range = text
+            .split_once(':')
+            .and_then(|(min, max)| Some((min.trim().parse().ok()?, max.trim().parse().ok()?)));
+        match range {
+            Some(range) => Ok(range),
+            None => Err(GcalcError::InvalidArgument(format!(
+                "\"{}\" is not a valid axis range, use a form of MIN:MAX",
+                text
+            ))),
+        }
+    }
+
+    /// Parse a colour into rgb values
+    pub fn parse_color(text: &str) -> GcalcResult<(u8, u8, u8)> {
+        let color = match text.to_lowercase().as_str() {
+            "black" => (0, 0, 0),
+            "white" => (255, 255, 255),
+            "red" => (255, 0, 0),
+            "green" => (0, 255, 0),
+            "blue" => (0, 0, 255),
+            "yellow" => (255, 255, 0),
+            "cyan" => (0, 255, 255),
+            "magenta" => (255, 0, 255),
+            "gray" | "grey" => (128, 128, 128),
+            hex => {
+                let digits = hex.strip_prefix('#').unwrap_or(hex);
+                let channel =
+                    |index: usize| u8::from_str_radix(digits.get(index..index + 2)?, 16).ok();
+                match (digits.len(), channel(0), channel(2), channel(4)) {
+                    (6, Some(r), Some(g), Some(b)) => (r, g, b),
+                    _ => {
+                        return Err(GcalcError::InvalidArgument(format!(
+                            "\"{}\" is not a valid colour, use a name or a hex code",
+                            text
+                        )))
+                    }
+                }
+            }
+        };
+        Ok(color)
+    }
+
+    /// Check if colours, a size and axis ranges are valid
+    pub fn validate(&self) -> GcalcResult<()> {
+        for color in [&self.prob_color, &self.cost_color, &self.background].iter() {
+            Self::parse_color(color)?;
+        }
+        if self.img_size.0 == 0 || self.img_size.1 == 0 {
+            return Err(GcalcError::InvalidArgument(
+                "Image size should be greater than 0".to_owned(),
+            ));
+        }
+        for (name, range) in [("X", self.x_range), ("Y", self.y_range)].iter() {
+            if let Some((min, max)) = range {
+                if !min.is_finite() || !max.is_finite() || min >= max {
+                    return Err(GcalcError::InvalidArgument(format!(
+                        "{} range \"{}:{}\" should have a minimum below a maximum",
+                        name, min, max
+                    )));
+                }
+            }
+        }
+        // Logarithm is undefined for zero or negative values
+        if self.log_x && self.x_range.is_some_and(|(min, _)| min <= 0.0) {
+            return Err(GcalcError::InvalidArgument(
+                "X range of a logarithmic axis should start above 0".to_owned(),
+            ));
+        }
+        if self.kind == PlotKind::Survival && self.y_range.is_some_and(|(min, _)| min <= 0.0) {
+            return Err(GcalcError::InvalidArgument(
+                "Y range of a survival chart should start above 0".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Conditions of a calculation which are drawn over a chart
+#[cfg(any(feature = "plotters", feature = "json"))]
+#[derive(Clone, Default)]
+pub(crate) struct Annotation {
+    /// Target probability which ends a calculation
+    pub target: Option<f32>,
+    /// Milestone target probabilities
+    pub milestones: Vec<f32>,
+    pub budget: Option<f32>,
+    /// Count where success is guaranteed
+    pub pity: Option<usize>,
+}
+
+#[cfg(any(feature = "plotters", feature = "json"))]
+impl Annotation {
+    /// Index of the last record within budget, if budget runs out there
+    pub fn budget_index(&self, data: &[Record]) -> Option<usize> {
+        let budget = self.budget?;
+        let index = data.iter().rposition(|record| record.cost <= budget)?;
+        // Without a next record, a next try is assumed to cost as much as the last one
+        let next_cost = match data.get(index + 1) {
+            Some(next) => next.cost,
+            None => {
+                let previous = index.checked_sub(1).map_or(0.0, |i| data[i].cost);
+                data[index].cost * 2.0 - previous
+            }
+        };
+        if next_cost > budget {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    pub fn pity_index(&self, data: &[Record]) -> Option<usize> {
+        let pity = self.pity?;
+        data.iter().position(|record| record.count == pity)
+    }
+
+    /// Count and label of a budget line, e.g. "budget 100 @ 10"
+    pub fn budget_label(&self, data: &[Record]) -> Option<(usize, String)> {
+        let budget = self.budget?;
+        let count = data[self.budget_index(data)?].count;
+        Some((count, format!("budget {} @ {}", budget, count)))
+    }
+
+    /// Count and label of a pity line
+    pub fn pity_label(&self, data: &[Record]) -> Option<(usize, String)> {
+        let count = data[self.pity_index(data)?].count;
+        Some((count, format!("pity {}", count)))
+    }
+
+    /// Index of a first record which reaches each target
+    pub fn crossings(&self, data: &[Record]) -> Vec<(f32, usize)> {
+        let mut targets = self.milestones.clone();
+        if let Some(target) = self.target {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        targets
+            .into_iter()
+            .filter_map(|target| {
+                data.iter()
+                    .position(|record| record.probability_src >= target)
+                    .map(|index| (target, index))
+            })
+            .collect()
+    }
+
+    /// Labelled counts of a histogram
+    ///
+    /// Median, 90th percentile and where a guarantee starts are markers, while budget and pity
+    /// are annotations. Targets are not included because probability axis is not cumulative.
+    pub fn histogram_markers(&self, attr: &PlotAttribute, data: &[Record]) -> Vec<HistogramMarker> {
+        let mut markers: Vec<HistogramMarker> = vec![];
+        if attr.markers {
+            for (kind, name, quantile) in [
+                (MarkerKind::Median, "median", 0.5f32),
+                (MarkerKind::Percentile, "p90", 0.9f32),
+            ]
+            .iter()
+            {
+                if let Some(record) = data.iter().find(|r| r.probability_src >= *quantile) {
+                    markers.push(HistogramMarker::new(
+                        *kind,
+                        format!("{} {}", name, record.count),
+                        record.count,
+                    ));
+                }
+            }
+            // Only where a guarantee starts, because rate stays 1 until a reference changes it
+            let mut guaranteed = false;
+            for record in data {
+                if record.rate.0 >= 1.0 && !guaranteed {
+                    markers.push(HistogramMarker::new(
+                        MarkerKind::Pity,
+                        format!("pity {}", record.count),
+                        record.count,
+                    ));
+                }
+                guaranteed = record.rate.0 >= 1.0;
+            }
+        }
+        if attr.annotations {
+            if let Some((count, label)) = self.budget_label(data) {
+                markers.push(HistogramMarker::new(MarkerKind::Budget, label, count));
+            }
+            // Pity annotation is redundant if a guarantee is already marked
+            if let Some((count, label)) = self.pity_label(data) {
+                if !markers.iter().any(|marker| marker.kind == MarkerKind::Pity) {
+                    markers.push(HistogramMarker::new(MarkerKind::Pity, label, count));
+                }
+            }
+        }
+        markers
+    }
+}
+
+/// Kind of a histogram marker
+#[cfg(any(feature = "plotters", feature = "json"))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum MarkerKind {
+    Median,
+    /// 90th percentile
+    Percentile,
+    /// Count where a success is guaranteed
+    Pity,
+    Budget,
+}
+
+/// Labelled count of a histogram
+#[cfg(any(feature = "plotters", feature = "json"))]
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) struct HistogramMarker {
+    pub kind: MarkerKind,
+    pub label: String,
+    pub count: usize,
+}
+
+#[cfg(any(feature = "plotters", feature = "json"))]
+impl HistogramMarker {
+    pub fn new(kind: MarkerKind, label: String, count: usize) -> Self {
+        Self { kind, label, count }
+    }
+}
+
+#[cfg_attr(feature = "option", derive(Serialize, Deserialize, Clone, Copy))]
+pub enum ProbType {
+    Percentage,
+    Fraction,
+}
+
+impl FromStr for ProbType {
+    type Err = GcalcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "percentage" | "percent" => Ok(Self::Percentage),
+            "float" => Ok(Self::Fraction),
+            _ => Err(GcalcError::InvalidConversion(format!(
+                "{} is not a valid table format",
+                s
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column_map() -> HashMap<String, String> {
+        HashMap::from([("count".to_owned(), "try".to_owned())])
+    }
+
+    #[test]
+    fn filter_joins_predicates_with_mapped_columns() {
+        let filter = RecordFilter::new()
+            .predicate("banner=limited")
+            .predicate("count >= 5")
+            .predicate("name != two words");
+        assert_eq!(
+            filter.to_clause(&column_map()).unwrap(),
+            " WHERE banner = 'limited' AND name != 'two words'"
+        );
+        assert_eq!(
+            filter.comparisons(&column_map()).unwrap(),
+            vec![Predicate {
+                column: "try".to_owned(),
+                operator: ">=",
+                value: "5".to_owned(),
+            }]
+        );
+        assert_eq!(RecordFilter::new().to_clause(&column_map()).unwrap(), "");
+    }
+
+    #[test]
+    fn filter_rejects_invalid_predicates() {
+        for predicate in [
+            "banner",
+            "=limited",
+            "banner=",
+            "banner like limited",
+            "banner in a b",
+            "ban ner=limited",
+            "count >= five",
+            "banner=AND",
+        ] {
+            let filter = RecordFilter::new().predicate(predicate);
+            assert!(filter.to_clause(&column_map()).is_err(), "{}", predicate);
+            assert!(filter.comparisons(&column_map()).is_err(), "{}", predicate);
+        }
+    }
+
+    #[test]
+    fn filter_values_are_escaped_for_query() {
+        let csv = "name,count\nit's,1\nback\\slash,2\n'quoted',3\nit,4\n";
+        for value in ["it's", "back\\slash", "'quoted'"] {
+            let mut indexer = Indexer::new();
+            CsvDialect::new()
+                .add_table(&mut indexer, "ref", csv)
+                .unwrap();
+            let clause = RecordFilter::new()
+                .predicate(&format!("name={}", value))
+                .to_clause(&column_map())
+                .unwrap();
+            let rows = indexer
+                .index_get_records(
+                    cindex::Query::from_str(&format!("SELECT name FROM ref{} FLAG SUP", clause))
+                        .unwrap(),
+                )
+                .unwrap();
+            assert_eq!(rows, vec![vec![value.to_owned()]], "{}", clause);
+        }
+    }
+
+    #[test]
+    fn comparisons_match_numbers_numerically() {
+        let predicate = |predicate: &str| RecordFilter::parse_predicate(predicate).unwrap();
+        assert!(predicate("count >= 5").matches("10"));
+        assert!(predicate("count >= 5").matches("5.0"));
+        assert!(!predicate("count >= 5").matches("4"));
+        assert!(predicate("count>2").matches("10"));
+        assert!(!predicate("count>2").matches("2"));
+        assert!(predicate("cost < 1.5").matches("1"));
+        assert!(predicate("cost <= 1.5").matches(" 1.5 "));
+        assert!(!predicate("cost < 1.5").matches("abc"));
+        assert!(!predicate("cost < 1.5").matches(""));
+    }
+
+    #[test]
+    fn sort_order_is_parsed_with_mapped_column() {
+        let order = |order: &str| RecordFilter::new().order(order).sort_order(&column_map());
+        assert_eq!(order("count").unwrap(), Some(("try".to_owned(), false)));
+        assert_eq!(order("cost:desc").unwrap(), Some(("cost".to_owned(), true)));
+        assert_eq!(order("cost asc").unwrap(), Some(("cost".to_owned(), false)));
+        assert!(order("cost:up").is_err());
+        assert!(order("cost:asec").is_err());
+        assert!(order(":desc").is_err());
+        assert_eq!(RecordFilter::new().sort_order(&column_map()).unwrap(), None);
+    }
+
+    #[test]
+    fn dialect_separators_are_parsed_by_name_or_character() {
+        assert_eq!(CsvDialect::parse_separator("tab").unwrap(), '\t');
+        assert_eq!(CsvDialect::parse_separator("\\t").unwrap(), '\t');
+        assert_eq!(CsvDialect::parse_separator("Semicolon").unwrap(), ';');
+        assert_eq!(CsvDialect::parse_separator("space").unwrap(), ' ');
+        assert_eq!(CsvDialect::parse_separator("apostrophe").unwrap(), '\'');
+        assert_eq!(CsvDialect::parse_separator("|").unwrap(), '|');
+        assert!(CsvDialect::parse_separator("").is_err());
+        assert!(CsvDialect::parse_separator(";;").is_err());
+    }
+
+    #[test]
+    fn dialect_rejects_conflicting_separators() {
+        assert!(CsvDialect::new().validate().is_ok());
+        assert!(CsvDialect::new().decimal(',').validate().is_err());
+        assert!(CsvDialect::new().thousands(',').validate().is_err());
+        assert!(CsvDialect::new().thousands('.').validate().is_err());
+        assert!(CsvDialect::new()
+            .delimiter(';')
+            .decimal(',')
+            .thousands('.')
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn dialect_strips_bom_and_blanks_comment_lines() {
+        let dialect = CsvDialect::new().comment("#");
+        assert_eq!(
+            dialect.prepare("\u{feff}count,prob\n  # note\n1,0.1"),
+            "count,prob\n\n1,0.1"
+        );
+        // Without a comment prefix lines are kept as they are
+        assert_eq!(
+            CsvDialect::new().prepare("\u{feff}# count\n1"),
+            "# count\n1"
+        );
+    }
+
+    #[test]
+    fn dialect_normalizes_numbers_between_digits() {
+        let dialect = CsvDialect::new().delimiter(';').decimal(',').thousands('.');
+        assert_eq!(dialect.normalize_number("1.234,5"), "1234.5");
+        assert_eq!(dialect.normalize_number("0,5%"), "0.5%");
+        let dialect = CsvDialect::new().thousands(' ');
+        assert_eq!(dialect.normalize_number("12 000"), "12000");
+        assert_eq!(dialect.normalize_number("1 in 300"), "1 in 300");
+    }
+
+    #[test]
+    fn dialect_reads_table_with_delimiter_and_comments() {
+        let dialect = CsvDialect::new().delimiter(';').decimal(',').comment("//");
+        let mut indexer = Indexer::new();
+        dialect
+            .add_table(&mut indexer, "ref", "count;prob\n// first\n1;0,5\n2;0,25\n")
+            .unwrap();
+        let rows = indexer
+            .index_get_records(cindex::Query::from_str("SELECT prob FROM ref").unwrap())
+            .unwrap();
+        let probs: Vec<String> = rows
+            .iter()
+            .map(|row| dialect.normalize_number(&row[0]))
+            .collect();
+        assert_eq!(probs, ["0.5", "0.25"]);
+
+        let invalid = CsvDialect::new().decimal(',');
+        assert!(invalid
+            .add_table(&mut Indexer::new(), "ref", "count,prob\n1,0.1\n")
+            .is_err());
+    }
+
+    #[test]
+    fn currency_groups_integer_digits() {
+        let comma = CurrencyFormat::new().thousands(',');
+        assert_eq!(comma.apply("1234567.891"), "1,234,567.891");
+        assert_eq!(comma.apply("123456"), "123,456");
+        assert_eq!(comma.apply("999"), "999");
+        assert_eq!(comma.apply("-1234.5"), "-1,234.5");
+        // Non finite or scientific numbers are kept as they are
+        assert_eq!(comma.apply("inf"), "inf");
+        assert_eq!(comma.apply("1.5e10"), "1.5e10");
+        assert_eq!(
+            CurrencyFormat::new().thousands(' ').apply("12000"),
+            "12 000"
+        );
+        assert_eq!(CurrencyFormat::new().apply("12000"), "12000");
+    }
+
+    #[test]
+    fn currency_affixes_wrap_grouped_number_after_sign() {
+        let gems = CurrencyFormat::new()
+            .thousands(',')
+            .prefix("~")
+            .suffix(" gems");
+        assert!(!gems.is_plain());
+        assert_eq!(gems.apply("12000"), "~12,000 gems");
+        assert_eq!(gems.apply("-5.5"), "-~5.5 gems");
+
+        let money = CurrencyFormat::new()
+            .money_prefix("$")
+            .money_suffix(" USD")
+            .rate(0.0125);
+        // Money affixes don't change cost and value
+        assert!(money.is_plain());
+        assert_eq!(money.apply("100"), "100");
+        assert_eq!(money.apply_money("1000.50"), "$1000.50 USD");
+    }
+
+    #[test]
+    fn currency_rejects_invalid_separator_and_rate() {
+        assert!(CurrencyFormat::new().validate().is_ok());
+        assert!(CurrencyFormat::new().thousands('\'').validate().is_ok());
+        for thousands in ['.', '-', '5'] {
+            assert!(CurrencyFormat::new()
+                .thousands(thousands)
+                .validate()
+                .is_err());
+        }
+        assert!(CurrencyFormat::new().rate(0.01).validate().is_ok());
+        for rate in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let err = CurrencyFormat::new().rate(rate).validate().unwrap_err();
+            assert!(err.detail().contains("positive"), "{}", rate);
+        }
+    }
+
+    #[test]
+    fn record_money_uses_rate_and_money_precision() {
+        let currency = CurrencyFormat::new()
+            .thousands(',')
+            .rate(0.5)
+            .money_prefix("$")
+            .money_precision(1);
+        let record = Record::new(1, 0.5, "0.5".to_owned(), 2500.0, 0.0, 0.0)
+            .formatted_amount(&NumberFormat::default(), &currency);
+        assert_eq!(record.money, Some((1250.0, "$1,250.0".to_owned())));
+        assert_eq!(record.cost_formatted.as_deref(), Some("2,500"));
+    }
+
+    #[test]
+    fn plot_colors_are_names_or_hex_codes() {
+        assert_eq!(PlotAttribute::parse_color("Red").unwrap(), (255, 0, 0));
+        assert_eq!(PlotAttribute::parse_color("grey").unwrap(), (128, 128, 128));
+        assert_eq!(
+            PlotAttribute::parse_color("#1E90ff").unwrap(),
+            (30, 144, 255)
+        );
+        assert_eq!(PlotAttribute::parse_color("00ff00").unwrap(), (0, 255, 0));
+        for color in ["", "#fff", "#12345g", "#1234567", "orange", "#ffé000"] {
+            assert!(PlotAttribute::parse_color(color).is_err(), "{}", color);
+        }
+    }
+
+    #[test]
+    fn plot_sizes_are_width_by_height() {
+        assert_eq!(PlotAttribute::parse_size("1280x720").unwrap(), (1280, 720));
+        assert_eq!(PlotAttribute::parse_size("800 X 600").unwrap(), (800, 600));
+        for size in ["1280", "0x720", "1280x", "-1x5", "axb"] {
+            assert!(PlotAttribute::parse_size(size).is_err(), "{}", size);
+        }
+    }
+
+    #[test]
+    fn plot_format_follows_extension_unless_given() {
+        let format = |path: &str| PlotAttribute::new().path(path).image_format();
+        assert_eq!(format("out.svg"), PlotFormat::Svg);
+        assert_eq!(format("charts/out.PNG"), PlotFormat::Png);
+        assert_eq!(format("out.json"), PlotFormat::VegaLite);
+        assert_eq!(format("out"), PlotFormat::Svg);
+        assert_eq!(
+            PlotAttribute::new()
+                .path("out.svg")
+                .format(PlotFormat::Png)
+                .image_format(),
+            PlotFormat::Png
+        );
+    }
+
+    #[test]
+    fn plot_attribute_validates_colors_and_size() {
+        assert!(PlotAttribute::new().validate().is_ok());
+        assert!(PlotAttribute::new()
+            .prob_color("#ff8800")
+            .background("black")
+            .validate()
+            .is_ok());
+        assert!(PlotAttribute::new()
+            .prob_color("redish")
+            .validate()
+            .is_err());
+        assert!(PlotAttribute::new().cost_color("#12").validate().is_err());
+        assert!(PlotAttribute::new().background("").validate().is_err());
+        assert!(PlotAttribute::new().size(0, 100).validate().is_err());
+        assert!(PlotAttribute::new().size(100, 0).validate().is_err());
+    }
+
+    #[test]
+    fn plot_ranges_are_validated_for_axes() {
+        assert_eq!(
+            PlotAttribute::parse_range(" 1 : 500 ").unwrap(),
+            (1.0, 500.0)
+        );
+        for text in ["1-500", "1:", "a:b"].iter() {
+            assert!(PlotAttribute::parse_range(text).is_err(), "{}", text);
+        }
+
+        assert!(PlotAttribute::new().x_range(1.0, 10.0).validate().is_ok());
+        assert!(PlotAttribute::new().x_range(10.0, 10.0).validate().is_err());
+        assert!(PlotAttribute::new().y_range(1.0, 0.5).validate().is_err());
+        assert!(PlotAttribute::new()
+            .x_range(0.0, f64::INFINITY)
+            .validate()
+            .is_err());
+
+        // Logarithmic axes start above 0
+        let log = PlotAttribute::new().log_x(true);
+        assert!(log.clone().validate().is_ok());
+        assert!(log.clone().x_range(1.0, 100.0).validate().is_ok());
+        assert!(log.x_range(0.0, 100.0).validate().is_err());
+        let survival = PlotAttribute::new().kind(PlotKind::Survival);
+        assert!(survival.clone().y_range(0.001, 1.0).validate().is_ok());
+        assert!(survival.y_range(0.0, 1.0).validate().is_err());
+        // Linear probability axis can start at 0
+        assert!(PlotAttribute::new().y_range(0.0, 0.5).validate().is_ok());
+    }
+
+    /// Records of per-try rates, where cost of each try is 10
+    #[cfg(any(feature = "plotters", feature = "json"))]
+    fn rate_records(rates: &[f32]) -> Vec<Record> {
+        let mut survival = 1.0;
+        rates
+            .iter()
+            .enumerate()
+            .map(|(index, &rate)| {
+                let first = survival * rate;
+                survival -= first;
+                Record::new(
+                    index + 1,
+                    1.0 - survival,
+                    String::new(),
+                    10.0 * (index + 1) as f32,
+                    0.0,
+                    0.0,
+                )
+                .marginal(
+                    (rate, String::new()),
+                    (first, String::new()),
+                    (survival, String::new()),
+                )
+            })
+            .collect()
+    }
+
+    #[cfg(any(feature = "plotters", feature = "json"))]
+    fn marker_kinds(markers: &[HistogramMarker]) -> Vec<(MarkerKind, &str, usize)> {
+        markers
+            .iter()
+            .map(|marker| (marker.kind, marker.label.as_str(), marker.count))
+            .collect()
+    }
+
+    #[test]
+    #[cfg(any(feature = "plotters", feature = "json"))]
+    fn histogram_markers_are_quantiles_and_guarantee() {
+        let data = rate_records(&[0.5, 0.5, 0.5, 1.0, 1.0]);
+        let attr = PlotAttribute::new().markers(true).annotations(false);
+        let markers = Annotation::default().histogram_markers(&attr, &data);
+        assert_eq!(
+            marker_kinds(&markers),
+            [
+                (MarkerKind::Median, "median 1", 1),
+                (MarkerKind::Percentile, "p90 4", 4),
+                // Only where a guarantee starts
+                (MarkerKind::Pity, "pity 4", 4),
+            ]
+        );
+
+        let attr = PlotAttribute::new().markers(false).annotations(false);
+        assert!(Annotation::default()
+            .histogram_markers(&attr, &data)
+            .is_empty());
+    }
+
+    #[test]
+    #[cfg(any(feature = "plotters", feature = "json"))]
+    fn histogram_pity_annotation_is_not_repeated() {
+        let data = rate_records(&[0.1, 0.1, 1.0, 0.1]);
+        let annotation = Annotation {
+            budget: Some(25.0),
+            pity: Some(3),
+            ..Annotation::default()
+        };
+
+        let attr = PlotAttribute::new().markers(false);
+        assert_eq!(
+            marker_kinds(&annotation.histogram_markers(&attr, &data)),
+            [
+                (MarkerKind::Budget, "budget 25 @ 2", 2),
+                (MarkerKind::Pity, "pity 3", 3),
+            ]
+        );
+
+        // Guarantee is already marked, thus pity annotation is skipped
+        let attr = PlotAttribute::new().markers(true);
+        let markers = annotation.histogram_markers(&attr, &data);
+        let pity: Vec<_> = markers
+            .iter()
+            .filter(|marker| marker.kind == MarkerKind::Pity)
+            .collect();
+        assert_eq!(pity.len(), 1);
+        assert_eq!(pity[0].count, 3);
+    }
+
+    #[test]
+    #[cfg(any(feature = "plotters", feature = "json"))]
+    fn annotation_crossings_are_first_records_reaching_targets() {
+        // Probabilities are 0.5, 0.75, 0.875 and 0.9375
+        let data = rate_records(&[0.5, 0.5, 0.5, 0.5]);
+        let annotation = Annotation {
+            target: Some(0.5),
+            milestones: vec![0.75, 0.99],
+            ..Annotation::default()
+        };
+        // Unreached milestone has no crossing
+        assert_eq!(annotation.crossings(&data), [(0.75, 1), (0.5, 0)]);
+
+        // Target which is also a milestone is crossed once
+        let annotation = Annotation {
+            target: Some(0.75),
+            milestones: vec![0.75],
+            ..Annotation::default()
+        };
+        assert_eq!(annotation.crossings(&data), [(0.75, 1)]);
+    }
+
+    #[test]
+    #[cfg(any(feature = "plotters", feature = "json"))]
+    fn annotation_labels_only_counts_within_records() {
+        // Costs are 10, 20, 30 and 40
+        let data = rate_records(&[0.1, 0.1, 0.1, 0.1]);
+        let label = |budget: f32, pity: usize| {
+            let annotation = Annotation {
+                budget: Some(budget),
+                pity: Some(pity),
+                ..Annotation::default()
+            };
+            (annotation.budget_label(&data), annotation.pity_label(&data))
+        };
+
+        assert_eq!(
+            label(25.0, 2),
+            (
+                Some((2, "budget 25 @ 2".to_owned())),
+                Some((2, "pity 2".to_owned()))
+            )
+        );
+        // Last record is labelled if a next try would be over budget
+        assert_eq!(label(45.0, 4).0, Some((4, "budget 45 @ 4".to_owned())));
+        // Budget which runs out before a first try or after records, and pity after records
+        assert_eq!(label(5.0, 5), (None, None));
+        assert_eq!(label(100.0, 10), (None, None));
+        assert!(Annotation::default().budget_label(&data).is_none());
+        assert!(Annotation::default().pity_label(&data).is_none());
+    }
+
+    fn parse_cell(cell: &str) -> GcalcResult<f32> {
+        cell.parse()
+            .map_err(|_| GcalcError::ParseError(format!("\"{}\" is not a number", cell)))
+    }
+
+    #[test]
+    fn column_fallback_resolves_invalid_cells_by_behaviour() {
+        let resolve = |behaviour, cell: &str| {
+            ColumnFallback::new(behaviour, None).resolve(cell, 0.3, 0.1, parse_cell)
+        };
+        assert_eq!(resolve(CSVInvalidBehaviour::None, "0.5").unwrap(), 0.5);
+        assert!(resolve(CSVInvalidBehaviour::None, "x").is_err());
+        assert!(resolve(CSVInvalidBehaviour::None, "").is_err());
+        assert_eq!(resolve(CSVInvalidBehaviour::Ignore, "x").unwrap(), 0.3);
+        assert_eq!(resolve(CSVInvalidBehaviour::Ignore, "").unwrap(), 0.3);
+        assert_eq!(resolve(CSVInvalidBehaviour::Rollback, "x").unwrap(), 0.1);
+        assert_eq!(resolve(CSVInvalidBehaviour::Rollback, "").unwrap(), 0.1);
+    }
+
+    #[test]
+    fn column_fallback_uses_default_for_empty_cells_only() {
+        let fallback = ColumnFallback::new(CSVInvalidBehaviour::None, Some(0.2));
+        assert_eq!(fallback.resolve("", 0.3, 0.1, parse_cell).unwrap(), 0.2);
+        assert!(fallback.resolve("x", 0.3, 0.1, parse_cell).is_err());
+    }
+
+    #[test]
+    fn csv_fallback_behaviours_apply_in_order() {
+        let mut fallback = CsvFallback::new(CSVInvalidBehaviour::None);
+        // Empty cost is 0 unless a default is given
+        assert_eq!(fallback.cost.default, Some(0.0));
+        assert_eq!(fallback.probability.default, None);
+
+        fallback
+            .set_behaviours("ignore, Constant=rollback, prob=none")
+            .unwrap();
+        assert_eq!(fallback.probability.behaviour, CSVInvalidBehaviour::None);
+        assert_eq!(fallback.constant.behaviour, CSVInvalidBehaviour::Rollback);
+        assert_eq!(fallback.cost.behaviour, CSVInvalidBehaviour::Ignore);
+
+        assert!(fallback.set_behaviours("count=ignore").is_err());
+        assert!(fallback.set_behaviours("cost=skip").is_err());
+    }
+
+    #[test]
+    fn csv_fallback_defaults_parse_probability_notation() {
+        let mut fallback = CsvFallback::new(CSVInvalidBehaviour::None);
+        fallback
+            .set_defaults("prob=0.5%, constant=1/200, cost=15", false)
+            .unwrap();
+        assert_eq!(fallback.probability.default, Some(0.005));
+        assert_eq!(fallback.constant.default, Some(0.005));
+        assert_eq!(fallback.cost.default, Some(15.0));
+
+        // Bare numbers above 1 are percentages unless strict
+        fallback.set_defaults("prob=50", false).unwrap();
+        assert_eq!(fallback.probability.default, Some(0.5));
+        assert!(fallback.set_defaults("prob=50", true).is_err());
+        fallback.set_defaults("prob=50%", true).unwrap();
+        assert_eq!(fallback.probability.default, Some(0.5));
+
+        // Cost is not a probability
+        fallback.set_defaults("cost=150", true).unwrap();
+        assert_eq!(fallback.cost.default, Some(150.0));
+
+        for defaults in ["prob", "prob=abc", "prob=150%", "cost=5%", "count=1"] {
+            assert!(
+                fallback.set_defaults(defaults, false).is_err(),
+                "{}",
+                defaults
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "option")]
+    fn csv_fallback_numeric_default_is_fraction() {
+        let mut fallback = CsvFallback::new(CSVInvalidBehaviour::None);
+        fallback.set_default("constant", 5.0).unwrap();
+        fallback.set_default("cost", 5.0).unwrap();
+        assert_eq!(fallback.constant.default, Some(0.05));
+        assert_eq!(fallback.cost.default, Some(5.0));
+        assert!(fallback.set_default("prob", 150.0).is_err());
+    }
+
+    #[test]
+    fn sort_rows_compares_numbers_numerically() {
+        let mut rows = vec!["10", "2", "9", "1.5"];
+        RecordFilter::sort_rows(&mut rows, false, |row| row);
+        assert_eq!(rows, ["1.5", "2", "9", "10"]);
+        RecordFilter::sort_rows(&mut rows, true, |row| row);
+        assert_eq!(rows, ["10", "9", "2", "1.5"]);
+    }
+
+    #[test]
+    fn sort_rows_puts_text_after_numbers() {
+        let mut rows = vec![("b", 0), ("10", 1), ("a", 2), ("2", 3), ("a", 4)];
+        RecordFilter::sort_rows(&mut rows, false, |row| row.0);
+        assert_eq!(rows, [("2", 3), ("10", 1), ("a", 2), ("a", 4), ("b", 0)]);
+        RecordFilter::sort_rows(&mut rows, true, |row| row.0);
+        assert_eq!(rows, [("10", 1), ("2", 3), ("b", 0), ("a", 2), ("a", 4)]);
+    }
+}
@@ -1,58 +1,406 @@
-use crate::models::{GcalcResult, Record};
-use crate::GcalcError;
+use crate::models::{Annotation, GcalcResult, PlotAttribute, PlotFormat, PlotKind, Record};
+use crate::sweep::{SweepGrid, SweepMetric};
+#[cfg(feature = "json")]
+use crate::vega::VegaRenderer;
+use crate::{Calculator, GcalcError};
+use plotters::coord::ranged1d::{AsRangedCoord, ValueFormatter};
+use plotters::coord::Shift;
 use plotters::prelude::*;
-use serde::{Deserialize, Serialize};
-use std::path::Path;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use std::ops::Range;
+
+/// Call a chart function of a renderer with a linear or a logarithmic x axis
+///
+/// Both axes are built from a range of counts, so that a chart function is generic over them.
+macro_rules! with_x_axis {
+    ($attr:expr, $range:expr, $draw:ident($($arg:expr),*)) => {
+        if $attr.log_x {
+            Self::$draw($($arg,)* (log_start($range.start)..$range.end).log_scale())
+        } else {
+            Self::$draw($($arg,)* $range)
+        }
+    };
+}
 
 pub(crate) struct Renderer;
 
+/// Probability curves of several scenarios on one chart
+///
+/// Chart attribute is taken from the first added calculator unless it is given.
+///
+/// ```no_run
+/// use gcalc::{Calculator, Overlay};
+///
+/// let mut base = Calculator::new().unwrap().probability(0.01).unwrap();
+/// let mut boosted = Calculator::new().unwrap().probability(0.02).unwrap();
+///
+/// let mut overlay = Overlay::new();
+/// overlay.add_range("base", &mut base, Some(100)).unwrap();
+/// overlay.add_range("boosted", &mut boosted, Some(100)).unwrap();
+/// overlay.draw().unwrap();
+/// ```
+#[derive(Default)]
+pub struct Overlay {
+    attribute: Option<PlotAttribute>,
+    series: Vec<(String, Vec<Record>)>,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn attribute(mut self, attribute: PlotAttribute) -> GcalcResult<Self> {
+        attribute.validate()?;
+        self.attribute.replace(attribute);
+        Ok(self)
+    }
+
+    /// Add records of a range calculation as a named series
+    pub fn add_range(
+        &mut self,
+        name: &str,
+        calculator: &mut Calculator,
+        count: Option<usize>,
+    ) -> GcalcResult<()> {
+        let records = calculator.range_records(count)?;
+        self.push(name, calculator, records);
+        Ok(())
+    }
+
+    /// Add records of a conditional calculation as a named series
+    pub fn add_conditional(&mut self, name: &str, calculator: &mut Calculator) -> GcalcResult<()> {
+        let records = calculator.conditional_records()?;
+        self.push(name, calculator, records);
+        Ok(())
+    }
+
+    /// Draw chart into a file of a path from an attribute
+    pub fn draw(&self) -> GcalcResult<()> {
+        Renderer::draw_overlay(&self.chart_attribute(), &self.series)
+    }
+
+    /// Render chart as a svg document
+    pub fn render_svg(&self) -> GcalcResult<String> {
+        Renderer::render_overlay_svg(&self.chart_attribute(), &self.series)
+    }
+
+    /// Render chart as a Vega-Lite specification
+    #[cfg(feature = "json")]
+    pub fn render_vega_lite(&self) -> GcalcResult<String> {
+        VegaRenderer::render(&VegaRenderer::overlay(
+            &self.chart_attribute(),
+            &self.series,
+        )?)
+    }
+
+    /// Add records that are already calculated as a named series
+    pub(crate) fn push(&mut self, name: &str, calculator: &Calculator, records: Vec<Record>) {
+        if self.attribute.is_none() {
+            self.attribute.replace(calculator.chart_attribute().clone());
+        }
+        self.series.push((name.to_owned(), records));
+    }
+
+    fn chart_attribute(&self) -> PlotAttribute {
+        self.attribute.clone().unwrap_or_default()
+    }
+}
+
+/// Data of a chart
+#[derive(Clone, Copy)]
+enum Chart<'a> {
+    Records(&'a [Record], &'a Annotation),
+    Overlay(&'a [(String, Vec<Record>)]),
+    Heatmap(&'a SweepGrid),
+}
+
 impl Renderer {
-    pub fn draw_chart(attr: PlotAttribute, data: &Vec<Record>) -> GcalcResult<()> {
-        let root_area = SVGBackend::new(Path::new("out.svg"), attr.img_size).into_drawing_area();
-        root_area.fill(&WHITE).unwrap();
+    /// Draw chart into a file of a path from given attribute
+    pub fn draw_chart(
+        attr: &PlotAttribute,
+        data: &[Record],
+        annotation: &Annotation,
+    ) -> GcalcResult<()> {
+        Self::write(attr, Chart::Records(data, annotation))
+    }
+
+    /// Draw probability curves of named series into a file
+    pub fn draw_overlay(attr: &PlotAttribute, series: &[(String, Vec<Record>)]) -> GcalcResult<()> {
+        Self::write(attr, Chart::Overlay(series))
+    }
+
+    /// Render chart as a svg document
+    pub fn render_svg(
+        attr: &PlotAttribute,
+        data: &[Record],
+        annotation: &Annotation,
+    ) -> GcalcResult<String> {
+        Self::render(attr, Chart::Records(data, annotation))
+    }
+
+    /// Render probability curves of named series as a svg document
+    pub fn render_overlay_svg(
+        attr: &PlotAttribute,
+        series: &[(String, Vec<Record>)],
+    ) -> GcalcResult<String> {
+        Self::render(attr, Chart::Overlay(series))
+    }
 
-        if data.is_empty() {
+    /// Draw heatmap of a sweep grid into a file
+    pub fn draw_heatmap(attr: &PlotAttribute, grid: &SweepGrid) -> GcalcResult<()> {
+        Self::write(attr, Chart::Heatmap(grid))
+    }
+
+    /// Render heatmap of a sweep grid as a svg document
+    pub fn render_heatmap_svg(attr: &PlotAttribute, grid: &SweepGrid) -> GcalcResult<String> {
+        Self::render(attr, Chart::Heatmap(grid))
+    }
+
+    fn write(attr: &PlotAttribute, chart: Chart) -> GcalcResult<()> {
+        let write_err = || {
+            GcalcError::PlotError(format!(
+                "Failed to write chart to a file \"{}\"",
+                attr.path.display()
+            ))
+        };
+        match attr.image_format() {
+            PlotFormat::Svg => {
+                let root_area = SVGBackend::new(&attr.path, attr.img_size).into_drawing_area();
+                Self::draw_on(&root_area, attr, chart)?;
+                root_area.present().map_err(|_| write_err())
+            }
+            #[cfg(feature = "png")]
+            PlotFormat::Png => {
+                let root_area = BitMapBackend::new(&attr.path, attr.img_size).into_drawing_area();
+                Self::draw_on(&root_area, attr, chart)?;
+                root_area.present().map_err(|_| write_err())
+            }
+            #[cfg(not(feature = "png"))]
+            PlotFormat::Png => Err(GcalcError::PlotError(
+                "Png output requires a feature \"png\"".to_string(),
+            )),
+            #[cfg(feature = "json")]
+            PlotFormat::VegaLite => {
+                let spec = match chart {
+                    Chart::Records(data, annotation) => {
+                        VegaRenderer::records(attr, data, annotation)?
+                    }
+                    Chart::Overlay(series) => VegaRenderer::overlay(attr, series)?,
+                    Chart::Heatmap(grid) => VegaRenderer::heatmap(attr, grid)?,
+                };
+                VegaRenderer::write(attr, &spec)
+            }
+            #[cfg(not(feature = "json"))]
+            PlotFormat::VegaLite => Err(GcalcError::PlotError(
+                "Vega-Lite output requires a feature \"json\"".to_string(),
+            )),
+        }
+    }
+
+    fn render(attr: &PlotAttribute, chart: Chart) -> GcalcResult<String> {
+        let mut svg = String::new();
+        {
+            let root_area = SVGBackend::with_string(&mut svg, attr.img_size).into_drawing_area();
+            Self::draw_on(&root_area, attr, chart)?;
+            root_area
+                .present()
+                .map_err(|_| GcalcError::PlotError("Failed to render chart".to_string()))?;
+        }
+        Ok(svg)
+    }
+
+    fn draw_on<DB: DrawingBackend>(
+        root_area: &DrawingArea<DB, Shift>,
+        attr: &PlotAttribute,
+        chart: Chart,
+    ) -> GcalcResult<()> {
+        let background = color(&attr.background)?;
+        root_area
+            .fill(&background)
+            .map_err(|_| GcalcError::PlotError("Failed to fill chart background".to_string()))?;
+
+        let empty = match chart {
+            Chart::Records(data, _) => data.is_empty(),
+            Chart::Overlay(series) => series.iter().all(|(_, data)| data.is_empty()),
+            Chart::Heatmap(grid) => grid.cells.iter().all(|row| row.is_empty()),
+        };
+        if empty {
             return Err(GcalcError::PlotError("Plot data is empty".to_string()));
         }
 
-        let mut max_cost = &data
-            .iter()
-            .max_by(|&x, &y| x.probability.partial_cmp(&y.probability).unwrap())
-            .unwrap()
-            .cost;
+        match (chart, attr.kind) {
+            (Chart::Overlay(series), _) => {
+                let x = x_bounds(attr, series.iter().flat_map(|(_, data)| data), 0.0);
+                with_x_axis!(attr, x, draw_series(root_area, attr, series))
+            }
+            (Chart::Heatmap(grid), _) => Self::draw_heatmap_on(root_area, attr, grid),
+            (Chart::Records(data, annotation), PlotKind::Cumulative) => {
+                let x = x_bounds(attr, data.iter(), 0.0);
+                with_x_axis!(attr, x, draw_cumulative(root_area, attr, data, annotation))
+            }
+            (Chart::Records(data, annotation), PlotKind::Survival) => {
+                let x = x_bounds(attr, data.iter(), 0.0);
+                with_x_axis!(attr, x, draw_survival(root_area, attr, data, annotation))
+            }
+            (Chart::Records(data, annotation), PlotKind::Histogram) => {
+                // Bars are centred on counts
+                let x = x_bounds(attr, data.iter(), 0.5);
+                with_x_axis!(attr, x, draw_histogram(root_area, attr, data, annotation))
+            }
+        }
+    }
 
-        // TO make chart look consistent
-        if *max_cost == 0.0 {
-            max_cost = &1.0;
+    /// Cumulative probability lines of named series with a legend
+    ///
+    /// The first series uses probability colour and the others follow a palette.
+    fn draw_series<DB: DrawingBackend, X>(
+        root_area: &DrawingArea<DB, Shift>,
+        attr: &PlotAttribute,
+        series: &[(String, Vec<Record>)],
+        x: X,
+    ) -> GcalcResult<()>
+    where
+        X: AsRangedCoord<Value = f64>,
+        X::CoordDescType: ValueFormatter<f64>,
+    {
+        let prob_color = color(&attr.prob_color)?;
+        let embed_err = |_| GcalcError::PlotError("Failed to embed data into a chart".to_string());
+
+        let (ft, fs) = (&attr.font_type, attr.font_size);
+        let area_size = fs as f32 * 2.5;
+        let (y_min, y_max) = attr.y_range.unwrap_or((0.0, 1.0));
+
+        let mut ctx = ChartBuilder::on(root_area)
+            .margin(10u32)
+            .x_label_area_size(50.0f32)
+            .y_label_area_size(area_size)
+            .caption(&attr.caption, (ft.as_str(), fs as f64))
+            .build_cartesian_2d(x, y_min..y_max)
+            .map_err(|_| GcalcError::PlotError("Failed to create chart".to_string()))?;
+
+        ctx.configure_mesh()
+            .x_labels(x_label_count(attr))
+            .y_labels(20)
+            .disable_x_mesh()
+            .disable_y_mesh()
+            .x_label_formatter(&count_label)
+            .y_desc(&attr.prob_caption)
+            .label_style((ft.as_str(), fs as f64 * 0.5))
+            .axis_desc_style((ft.as_str(), fs as f64))
+            .draw()
+            .map_err(|_| GcalcError::PlotError("Failed to configure mesh for chart".to_string()))?;
+
+        let visible = visible_points(&ctx);
+        for (index, (name, data)) in series.iter().enumerate() {
+            let series_color = if index == 0 {
+                prob_color.to_rgba()
+            } else {
+                Palette99::pick(index).to_rgba()
+            };
+            ctx.draw_series(LineSeries::new(
+                data.iter()
+                    .map(|record| (record.count as f64, record.probability_src as f64))
+                    .filter(|&point| visible(point)),
+                Into::<ShapeStyle>::into(&series_color).stroke_width(2),
+            ))
+            .map_err(embed_err)?
+            .label(name.as_str())
+            .legend(move |(x, y)| {
+                PathElement::new(vec![(x, y), (x + 20, y)], series_color.stroke_width(2))
+            });
         }
 
-        let (ft, fs) = (attr.font_type, attr.font_size);
+        ctx.configure_series_labels()
+            .position(SeriesLabelPosition::LowerRight)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .label_font((ft.as_str(), fs as f64 * 0.5))
+            .draw()
+            .map_err(|_| GcalcError::PlotError("Failed to draw a legend".to_string()))?;
+
+        Ok(())
+    }
+
+    /// Cumulative probability line with cost line on a secondary axis
+    ///
+    /// Cost axis follows the highest cost within an x range, so that a zoomed chart keeps a
+    /// readable cost line.
+    fn draw_cumulative<DB: DrawingBackend, X>(
+        root_area: &DrawingArea<DB, Shift>,
+        attr: &PlotAttribute,
+        data: &[Record],
+        annotation: &Annotation,
+        x: X,
+    ) -> GcalcResult<()>
+    where
+        X: AsRangedCoord<Value = f64> + Clone,
+        X::CoordDescType: ValueFormatter<f64>,
+    {
+        let prob_color = color(&attr.prob_color)?;
+        let cost_color = color(&attr.cost_color)?;
+        let embed_err = |_| GcalcError::PlotError("Failed to embed data into a chart".to_string());
+
+        let (ft, fs) = (&attr.font_type, attr.font_size);
         let area_size = fs as f32 * 2.5;
-        let column_count = data.len();
+        let (y_min, y_max) = attr.y_range.unwrap_or((0.0, 1.0));
 
-        let mut ctx = ChartBuilder::on(&root_area)
+        let ctx = ChartBuilder::on(root_area)
             .margin(10u32)
             .x_label_area_size(50.0f32)
             .y_label_area_size(area_size)
             .right_y_label_area_size(area_size)
             .caption(&attr.caption, (ft.as_str(), fs as f64))
-            .build_cartesian_2d(1..column_count, 0f64..1.0)
-            .map_err(|_| GcalcError::PlotError("Failed to create chart".to_string()))?
-            .set_secondary_coord(1..column_count, 0f32..*max_cost);
+            .build_cartesian_2d(x.clone(), y_min..y_max)
+            .map_err(|_| GcalcError::PlotError("Failed to create chart".to_string()))?;
+
+        let x_range = ctx.x_range();
+        let max_cost = data
+            .iter()
+            .filter(|record| x_range.contains(&(record.count as f64)))
+            .map(|record| record.cost)
+            .fold(0f32, f32::max);
+        // TO make chart look consistent
+        let max_cost = if max_cost == 0.0 { 1.0 } else { max_cost };
+        let mut ctx = ctx.set_secondary_coord(x, 0f32..max_cost);
 
         // Mesh configuration
         ctx.configure_mesh()
-            .x_labels(20)
+            .x_labels(x_label_count(attr))
             .y_labels(20)
             .disable_x_mesh()
             .disable_y_mesh()
-            .bold_line_style(&WHITE.mix(0.3))
+            .bold_line_style(WHITE.mix(0.3))
+            .x_label_formatter(&count_label)
             .y_desc(&attr.prob_caption)
             .label_style((ft.as_str(), fs as f64 * 0.5))
             .axis_desc_style((ft.as_str(), fs as f64))
             .draw()
             .map_err(|_| GcalcError::PlotError("Failed to configure mesh for chart".to_string()))?;
 
+        // Prob series
+        let visible = visible_points(&ctx);
+        let points = || {
+            data.iter()
+                .map(|record| (record.count as f64, record.probability_src as f64))
+                .filter(|&point| visible(point))
+        };
+        ctx.draw_series(LineSeries::new(
+            points(),
+            Into::<ShapeStyle>::into(&prob_color)
+                .stroke_width(2)
+                .filled(),
+        ))
+        .map_err(embed_err)?;
+
+        // Point
+        ctx.draw_series(points().map(|point| Circle::new(point, 3, prob_color.filled())))
+            .map_err(embed_err)?;
+
+        Self::draw_annotations(&mut ctx, attr, data, annotation, |probability| {
+            probability as f64
+        })?;
+
         ctx.configure_secondary_axes()
             .y_desc(&attr.cost_caption)
             .label_style((ft.as_str(), fs as f64 * 0.5))
@@ -62,61 +410,689 @@ impl Renderer {
                 GcalcError::PlotError("Failed to configure secondary mesh for chart".to_string())
             })?;
 
-        // Prob series
-        ctx.draw_series(LineSeries::new(
-            (1..)
-                .zip(data.iter())
-                .map(|(x, y)| (x, y.probability_src as f64)),
-            Into::<ShapeStyle>::into(&RED).stroke_width(2).filled(),
+        let costs = || {
+            data.iter()
+                .filter(|record| x_range.contains(&(record.count as f64)))
+                .map(|record| (record.count as f64, record.cost))
+        };
+        // Bar seires
+        ctx.draw_secondary_series(LineSeries::new(
+            costs(),
+            Into::<ShapeStyle>::into(&cost_color.mix(0.3)).stroke_width(2),
         ))
-        .map_err(|_| GcalcError::PlotError("Failed to embed data into a chart".to_string()))?;
+        .map_err(embed_err)?;
 
         // Point
-        ctx.draw_series(
-            (1..)
-                .zip(data.iter())
-                .map(|(x, y)| Circle::new((x, y.probability_src as f64), 3, RED.filled())),
+        ctx.draw_secondary_series(
+            costs().map(|point| Circle::new(point, 3, cost_color.mix(0.3).filled())),
         )
-        .unwrap();
+        .map_err(embed_err)?;
 
-        // Bar seires
-        ctx.draw_secondary_series(LineSeries::new(
-            (1usize..).zip(data.iter()).map(|(x, y)| (x, y.cost)),
-            Into::<ShapeStyle>::into(&BLUE.mix(0.3)).stroke_width(2),
+        Ok(())
+    }
+
+    /// Probability of no success yet on a logarithmic axis
+    ///
+    /// Survival is 1 - cumulative probability, so that a long tail of a low probability stays
+    /// readable. Counts with a guaranteed success have no survival and are not drawn.
+    fn draw_survival<DB: DrawingBackend, X>(
+        root_area: &DrawingArea<DB, Shift>,
+        attr: &PlotAttribute,
+        data: &[Record],
+        annotation: &Annotation,
+        x: X,
+    ) -> GcalcResult<()>
+    where
+        X: AsRangedCoord<Value = f64>,
+        X::CoordDescType: ValueFormatter<f64>,
+    {
+        let prob_color = color(&attr.prob_color)?;
+        let embed_err = |_| GcalcError::PlotError("Failed to embed data into a chart".to_string());
+        let survival = |probability: f32| 1.0 - probability as f64;
+
+        // Down to a whole decade below the lowest survival
+        let (y_min, y_max) = attr.y_range.unwrap_or_else(|| {
+            let lowest = data
+                .iter()
+                .map(|record| survival(record.probability_src))
+                .filter(|&value| value > 0.0)
+                .fold(1f64, f64::min);
+            (10f64.powf(lowest.log10().floor()).min(0.1), 1.0)
+        });
+
+        let (ft, fs) = (&attr.font_type, attr.font_size);
+        let area_size = fs as f32 * 2.5;
+
+        let mut ctx = ChartBuilder::on(root_area)
+            .margin(10u32)
+            .x_label_area_size(50.0f32)
+            .y_label_area_size(area_size)
+            .caption(&attr.caption, (ft.as_str(), fs as f64))
+            .build_cartesian_2d(x, (log_start(y_min)..y_max).log_scale())
+            .map_err(|_| GcalcError::PlotError("Failed to create chart".to_string()))?;
+
+        ctx.configure_mesh()
+            .x_labels(x_label_count(attr))
+            .y_labels(20)
+            .disable_x_mesh()
+            .disable_y_mesh()
+            .x_label_formatter(&count_label)
+            .y_label_formatter(&|value| {
+                // Digits down to a first significant digit of a decade
+                let digits = (-value.log10().floor()).max(0.0) as usize;
+                format!("{:.*}", digits, value)
+            })
+            .y_desc("1 - ".to_owned() + &attr.prob_caption)
+            .label_style((ft.as_str(), fs as f64 * 0.5))
+            .axis_desc_style((ft.as_str(), fs as f64))
+            .draw()
+            .map_err(|_| GcalcError::PlotError("Failed to configure mesh for chart".to_string()))?;
+
+        let visible = visible_points(&ctx);
+        let points = || {
+            data.iter()
+                .map(|record| (record.count as f64, survival(record.probability_src)))
+                .filter(|&point| visible(point))
+        };
+        ctx.draw_series(LineSeries::new(
+            points(),
+            Into::<ShapeStyle>::into(&prob_color).stroke_width(2),
         ))
-        .map_err(|_| GcalcError::PlotError("Failed to embed data into a chart".to_string()))?;
+        .map_err(embed_err)?;
+        ctx.draw_series(points().map(|point| Circle::new(point, 3, prob_color.filled())))
+            .map_err(embed_err)?;
 
-        // Point
-        ctx.draw_secondary_series(
-            (1..)
-                .zip(data.iter())
-                .map(|(x, y)| Circle::new((x, y.cost), 3, BLUE.mix(0.3).filled())),
+        Self::draw_annotations(&mut ctx, attr, data, annotation, survival)
+    }
+
+    /// Horizontal target line, vertical budget and pity lines and labelled points where
+    /// milestones are reached
+    ///
+    /// Probability converts a cumulative probability into a value of y axis. Annotations
+    /// outside visible ranges are skipped.
+    fn draw_annotations<DB: DrawingBackend, X, Y>(
+        ctx: &mut ChartContext<DB, Cartesian2d<X, Y>>,
+        attr: &PlotAttribute,
+        data: &[Record],
+        annotation: &Annotation,
+        probability: impl Fn(f32) -> f64,
+    ) -> GcalcResult<()>
+    where
+        X: Ranged<ValueType = f64>,
+        Y: Ranged<ValueType = f64>,
+    {
+        if !attr.annotations {
+            return Ok(());
+        }
+        let prob_color = color(&attr.prob_color)?;
+        let cost_color = color(&attr.cost_color)?;
+        let embed_err = |_| GcalcError::PlotError("Failed to embed data into a chart".to_string());
+
+        let font_size = attr.font_size as f64 * 0.4;
+        let (x_range, y_range) = (ctx.x_range(), ctx.y_range());
+        let visible = visible_points(ctx);
+        let mut lines = vec![];
+        let mut circles = vec![];
+        // Text, an anchor point, a pixel offset from the point and a colour
+        let mut labels = vec![];
+
+        if let Some(target) = annotation.target {
+            let y = probability(target);
+            if y_range.contains(&y) {
+                lines.push((
+                    vec![(x_range.start, y), (x_range.end, y)],
+                    prob_color.mix(0.6).stroke_width(1),
+                ));
+                labels.push((
+                    format!("target {}", target),
+                    (x_range.start, y),
+                    (5, -(font_size as i32) - 4),
+                    prob_color,
+                ));
+            }
+        }
+
+        let verticals = [
+            (annotation.budget_label(data), cost_color),
+            (annotation.pity_label(data), BLACK),
+        ];
+        for (offset, (vertical, line_color)) in verticals.iter().enumerate() {
+            let (count, label) = match vertical {
+                Some(vertical) => vertical,
+                None => continue,
+            };
+            let count = *count;
+            let x = count as f64;
+            if !x_range.contains(&x) {
+                continue;
+            }
+            lines.push((
+                vec![(x, y_range.start), (x, y_range.end)],
+                line_color.mix(0.6).stroke_width(2),
+            ));
+            labels.push((
+                label.clone(),
+                (x, y_range.start),
+                // Staggered so that close lines don't overlap
+                (0, -(font_size * (2.0 + 1.5 * offset as f64)) as i32),
+                *line_color,
+            ));
+        }
+
+        for (target, index) in annotation.crossings(data) {
+            let point = (
+                data[index].count as f64,
+                probability(data[index].probability_src),
+            );
+            if !visible(point) {
+                continue;
+            }
+            circles.push(point);
+            labels.push((
+                format!("{} @ {}", target, data[index].count),
+                point,
+                (0, 10),
+                prob_color,
+            ));
+        }
+
+        ctx.draw_series(
+            lines
+                .into_iter()
+                .map(|(points, style)| PathElement::new(points, style)),
         )
-        .unwrap();
+        .map_err(embed_err)?;
+        ctx.draw_series(
+            circles
+                .into_iter()
+                .map(|point| Circle::new(point, 6, prob_color.stroke_width(2))),
+        )
+        .map_err(embed_err)?;
+        let labels = labels
+            .into_iter()
+            .map(|(text, point, offset, label_color)| {
+                let style = (attr.font_type.as_str(), font_size)
+                    .into_font()
+                    .color(&label_color)
+                    .pos(label_pos(ctx, point.0));
+                EmptyElement::at(point) + Text::new(text, offset, style)
+            })
+            .collect::<Vec<_>>();
+        ctx.draw_series(labels).map_err(embed_err)?;
+        Ok(())
+    }
+
+    /// Per-try first success probability as bars
+    ///
+    /// Markers are drawn at median, 90th percentile and pity counts, where pity is a count
+    /// with a guaranteed success.
+    fn draw_histogram<DB: DrawingBackend, X>(
+        root_area: &DrawingArea<DB, Shift>,
+        attr: &PlotAttribute,
+        data: &[Record],
+        annotation: &Annotation,
+        x: X,
+    ) -> GcalcResult<()>
+    where
+        X: AsRangedCoord<Value = f64>,
+        X::CoordDescType: ValueFormatter<f64>,
+    {
+        let prob_color = color(&attr.prob_color)?;
+        let marker_color = color(&attr.cost_color)?;
+        let embed_err = |_| GcalcError::PlotError("Failed to embed data into a chart".to_string());
+
+        let (y_min, y_max) = attr.y_range.unwrap_or_else(|| {
+            let max_prob = data
+                .iter()
+                .map(|record| record.first_success.0 as f64)
+                .fold(0f64, f64::max);
+            // TO make chart look consistent
+            (0.0, if max_prob == 0.0 { 1.0 } else { max_prob * 1.1 })
+        });
+
+        let (ft, fs) = (&attr.font_type, attr.font_size);
+        let area_size = fs as f32 * 2.5;
+
+        let mut ctx = ChartBuilder::on(root_area)
+            .margin(10u32)
+            .x_label_area_size(50.0f32)
+            .y_label_area_size(area_size)
+            .caption(&attr.caption, (ft.as_str(), fs as f64))
+            .build_cartesian_2d(x, y_min..y_max)
+            .map_err(|_| GcalcError::PlotError("Failed to create chart".to_string()))?;
+
+        ctx.configure_mesh()
+            .x_labels(x_label_count(attr))
+            .y_labels(20)
+            .disable_x_mesh()
+            .disable_y_mesh()
+            .x_label_formatter(&count_label)
+            .y_desc(&attr.prob_caption)
+            .label_style((ft.as_str(), fs as f64 * 0.5))
+            .axis_desc_style((ft.as_str(), fs as f64))
+            .draw()
+            .map_err(|_| GcalcError::PlotError("Failed to configure mesh for chart".to_string()))?;
+
+        // Bars are cut at edges of ranges
+        let x_range = ctx.x_range();
+        ctx.draw_series(
+            data.iter()
+                .map(|record| (record.count as f64, record.first_success.0 as f64))
+                .filter(|(x, y)| x_range.contains(x) && *y > y_min)
+                .map(|(x, y)| {
+                    let left = (x - 0.4).max(x_range.start);
+                    let right = (x + 0.4).min(x_range.end);
+                    Rectangle::new([(left, y_min), (right, y.min(y_max))], prob_color.filled())
+                }),
+        )
+        .map_err(embed_err)?;
+
+        let mut markers = annotation.histogram_markers(attr, data);
+        markers.retain(|marker| x_range.contains(&(marker.count as f64)));
+
+        let font_size = fs as f64 * 0.5;
+        ctx.draw_series(markers.iter().map(|marker| {
+            let x = marker.count as f64;
+            PathElement::new(vec![(x, y_min), (x, y_max)], marker_color.stroke_width(2))
+        }))
+        .map_err(embed_err)?;
+        let labels = markers
+            .into_iter()
+            .enumerate()
+            .map(|(index, marker)| {
+                let x = marker.count as f64;
+                let style = (ft.as_str(), font_size)
+                    .into_font()
+                    .color(&marker_color)
+                    .pos(label_pos(&ctx, x));
+                // Labels are staggered so that close markers don't overlap
+                let offset = (font_size * (0.5 + 1.1 * index as f64)) as i32;
+                EmptyElement::at((x, y_max)) + Text::new(marker.label, (0, offset), style)
+            })
+            .collect::<Vec<_>>();
+        ctx.draw_series(labels).map_err(embed_err)?;
+
+        Ok(())
+    }
+
+    /// Grid of coloured cells with a colour scale on the right
+    ///
+    /// Low values use cost colour and high values use probability colour. Cells without a
+    /// value, such as a target that is never reached, are grey.
+    fn draw_heatmap_on<DB: DrawingBackend>(
+        root_area: &DrawingArea<DB, Shift>,
+        attr: &PlotAttribute,
+        grid: &SweepGrid,
+    ) -> GcalcResult<()> {
+        let low_color = color(&attr.cost_color)?;
+        let high_color = color(&attr.prob_color)?;
+        let embed_err = |_| GcalcError::PlotError("Failed to embed data into a chart".to_string());
+        let chart_err = |_| GcalcError::PlotError("Failed to create chart".to_string());
+        let mesh_err = |_| GcalcError::PlotError("Failed to configure mesh for chart".to_string());
+
+        let (ft, fs) = (&attr.font_type, attr.font_size);
+        let (x_count, y_count) = (grid.x.values.len(), grid.y.values.len());
+        let finite = grid.cells.iter().flatten().filter(|v| v.is_finite());
+        let min = finite.clone().fold(f32::INFINITY, |acc, &v| acc.min(v)) as f64;
+        let max = finite.fold(f32::NEG_INFINITY, |acc, &v| acc.max(v)) as f64;
+        // Every cell is empty or has a same value
+        let (min, max) = if min > max {
+            (0.0, 1.0)
+        } else if min == max {
+            (min - 0.5, max + 0.5)
+        } else {
+            (min, max)
+        };
+        let ratio = |value: f64| (value - min) / (max - min);
+        let format_value = |value: f32| match grid.metric {
+            SweepMetric::Success => format!("{:.3}", value),
+            SweepMetric::Tries => format!("{}", value),
+        };
+
+        let (width, _) = root_area.dim_in_pixel();
+        let (main_area, scale_area) = root_area.split_horizontally(width - width / 5);
+
+        let mut ctx = ChartBuilder::on(&main_area)
+            .margin(10u32)
+            .x_label_area_size(fs as f32 * 2.5)
+            .y_label_area_size(fs as f32 * 3.0)
+            .caption(&attr.caption, (ft.as_str(), fs as f64))
+            // Discrete range includes its end
+            .build_cartesian_2d(
+                (0..x_count - 1).into_segmented(),
+                (0..y_count - 1).into_segmented(),
+            )
+            .map_err(chart_err)?;
+
+        let axis_label = |values: &[f32], segment: &SegmentValue<usize>| match segment {
+            SegmentValue::CenterOf(index) => values
+                .get(*index)
+                .map(|value| value.to_string())
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+        ctx.configure_mesh()
+            .disable_x_mesh()
+            .disable_y_mesh()
+            .x_labels(x_count)
+            .y_labels(y_count)
+            .x_desc(grid.x.parameter.name())
+            .y_desc(grid.y.parameter.name())
+            .x_label_formatter(&|segment| axis_label(&grid.x.values, segment))
+            .y_label_formatter(&|segment| axis_label(&grid.y.values, segment))
+            .label_style((ft.as_str(), fs as f64 * 0.5))
+            .axis_desc_style((ft.as_str(), fs as f64))
+            .draw()
+            .map_err(mesh_err)?;
+
+        let cells = grid
+            .cells
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, &value)| (x, y, value)));
+        ctx.draw_series(cells.clone().map(|(x, y, value)| {
+            let fill = if value.is_finite() {
+                blend(&low_color, &high_color, ratio(value as f64))
+            } else {
+                RGBColor(190, 190, 190)
+            };
+            Rectangle::new(
+                [
+                    (SegmentValue::Exact(x), SegmentValue::Exact(y)),
+                    (SegmentValue::Exact(x + 1), SegmentValue::Exact(y + 1)),
+                ],
+                fill.filled(),
+            )
+        }))
+        .map_err(embed_err)?;
+
+        // Labels would overlap each other in a large grid
+        if x_count * y_count <= 100 {
+            let label_style = TextStyle::from((ft.as_str(), fs as f64 * 0.45))
+                .color(&BLACK)
+                .pos(Pos::new(HPos::Center, VPos::Center));
+            ctx.draw_series(cells.map(|(x, y, value)| {
+                let text = if value.is_finite() {
+                    format_value(value)
+                } else {
+                    "-".to_string()
+                };
+                Text::new(
+                    text,
+                    (SegmentValue::CenterOf(x), SegmentValue::CenterOf(y)),
+                    label_style.clone(),
+                )
+            }))
+            .map_err(embed_err)?;
+        }
+
+        // Colour scale with a metric name
+        const STEPS: usize = 50;
+        let step = (max - min) / STEPS as f64;
+        let mut scale = ChartBuilder::on(&scale_area)
+            .margin_top(fs as u32 + 20)
+            .margin_bottom(fs as f32 * 2.5 + 10.0)
+            .margin_right(fs as u32)
+            .y_label_area_size(fs as f32 * 2.0)
+            .build_cartesian_2d(0.0..1.0, min..max)
+            .map_err(chart_err)?;
+        scale
+            .configure_mesh()
+            .disable_x_mesh()
+            .disable_y_mesh()
+            .disable_x_axis()
+            .y_labels(5)
+            .y_label_formatter(&|value| match grid.metric {
+                SweepMetric::Success => format!("{:.2}", value),
+                SweepMetric::Tries => format!("{:.0}", value),
+            })
+            .y_desc(grid.metric.name())
+            .label_style((ft.as_str(), fs as f64 * 0.45))
+            .axis_desc_style((ft.as_str(), fs as f64 * 0.5))
+            .draw()
+            .map_err(mesh_err)?;
+        scale
+            .draw_series((0..STEPS).map(|index| {
+                let bottom = min + step * index as f64;
+                Rectangle::new(
+                    [(0.0, bottom), (1.0, bottom + step)],
+                    blend(&low_color, &high_color, ratio(bottom + step / 2.0)).filled(),
+                )
+            }))
+            .map_err(embed_err)?;
 
         Ok(())
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct PlotAttribute {
-    pub caption: String,
-    pub prob_caption: String,
-    pub cost_caption: String,
-    pub font_type: String,
-    pub font_size: u8,
-    pub img_size: (u32, u32),
+/// Count range of records unless an x range is given, where padding widens a range
+fn x_bounds<'a>(
+    attr: &PlotAttribute,
+    records: impl Iterator<Item = &'a Record>,
+    padding: f64,
+) -> Range<f64> {
+    if let Some((min, max)) = attr.x_range {
+        return min..max;
+    }
+    let (first, last) = records.fold((f64::MAX, f64::MIN), |(first, last), record| {
+        let count = record.count as f64;
+        (first.min(count), last.max(count))
+    });
+    // At least 1 so that a range is not empty
+    first - padding..last.max(first + 1.0) + padding
+}
+
+/// Whether a point is inside a chart, because points outside of ranges are not clipped
+fn visible_points<DB, X, Y>(
+    ctx: &ChartContext<DB, Cartesian2d<X, Y>>,
+) -> impl Fn((f64, f64)) -> bool
+where
+    DB: DrawingBackend,
+    X: Ranged<ValueType = f64>,
+    Y: Ranged<ValueType = f64>,
+{
+    let (x_range, y_range) = (ctx.x_range(), ctx.y_range());
+    move |(x, y)| x_range.contains(&x) && y_range.contains(&y)
+}
+
+/// Start of a logarithmic axis
+///
+/// Labels start from a first power of 10 within an axis, which floating point error can round
+/// up to a next power when an axis starts at a power, e.g. 0.1. Slightly lower start keeps it.
+fn log_start(min: f64) -> f64 {
+    min * (1.0 - 1e-9)
 }
 
-impl Default for PlotAttribute {
-    fn default() -> Self {
-        Self {
-            caption: "Gcalc result".to_owned(),
-            prob_caption: "Prob (r)".to_owned(),
-            cost_caption: "Cost (b)".to_owned(),
-            font_type: "Helvetica".to_owned(),
-            font_size: 50,
-            img_size: (1000, 1000),
+/// Fewer labels on a logarithmic axis, where many labels fill every decade with steps
+fn x_label_count(attr: &PlotAttribute) -> usize {
+    if attr.log_x {
+        10
+    } else {
+        20
+    }
+}
+
+/// Count without fraction unless an axis is zoomed into fractions
+fn count_label(count: &f64) -> String {
+    if count.fract() == 0.0 {
+        format!("{:.0}", count)
+    } else {
+        format!("{:.1}", count)
+    }
+}
+
+/// Anchor of a label at x
+///
+/// Labels near the right end are anchored on the right so that they stay inside a chart.
+/// Position is measured in pixels, which also works on a logarithmic axis.
+fn label_pos<DB, X, Y>(ctx: &ChartContext<DB, Cartesian2d<X, Y>>, x: f64) -> Pos
+where
+    DB: DrawingBackend,
+    X: Ranged<ValueType = f64>,
+    Y: Ranged<ValueType = f64>,
+{
+    let (pixels, _) = ctx.plotting_area().get_pixel_range();
+    let pixel = ctx.backend_coord(&(x, ctx.y_range().start)).0;
+    let ratio = (pixel - pixels.start) as f64 / (pixels.end - pixels.start).max(1) as f64;
+    let horizontal = if ratio > 0.8 { HPos::Right } else { HPos::Left };
+    Pos::new(horizontal, VPos::Top)
+}
+
+/// Linear interpolation between two colours where ratio is clamped into 0..=1
+fn blend(low: &RGBColor, high: &RGBColor, ratio: f64) -> RGBColor {
+    let ratio = ratio.clamp(0.0, 1.0);
+    let mix = |low: u8, high: u8| (low as f64 + (high as f64 - low as f64) * ratio).round() as u8;
+    RGBColor(mix(low.0, high.0), mix(low.1, high.1), mix(low.2, high.2))
+}
+
+fn color(text: &str) -> GcalcResult<RGBColor> {
+    let (r, g, b) = PlotAttribute::parse_color(text)?;
+    Ok(RGBColor(r, g, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calculator(probability: f32, caption: &str) -> Calculator {
+        let mut cal = Calculator::new().unwrap().probability(probability).unwrap();
+        cal.set_plot_attribute(PlotAttribute::new().caption(caption))
+            .unwrap();
+        cal
+    }
+
+    fn records(probability: f32, count: usize) -> Vec<Record> {
+        calculator(probability, "Chart")
+            .range_records(Some(count))
+            .unwrap()
+    }
+
+    /// Text elements of a svg document, which are rendered on their own lines
+    fn texts(svg: &str) -> Vec<&str> {
+        svg.lines().map(str::trim).collect()
+    }
+
+    #[test]
+    fn x_bounds_follow_counts_unless_given() {
+        let data = records(0.1, 5);
+        let attr = PlotAttribute::new();
+        assert_eq!(x_bounds(&attr, data.iter(), 0.0), 1.0..5.0);
+        assert_eq!(x_bounds(&attr, data.iter(), 0.5), 0.5..5.5);
+        // A single count still spans a range
+        assert_eq!(x_bounds(&attr, data[..1].iter(), 0.0), 1.0..2.0);
+
+        let attr = PlotAttribute::new().x_range(2.0, 4.0);
+        assert_eq!(x_bounds(&attr, data.iter(), 0.5), 2.0..4.0);
+    }
+
+    #[test]
+    fn count_labels_keep_fractions_of_zoomed_axis() {
+        assert_eq!(count_label(&100.0), "100");
+        assert_eq!(count_label(&2.5), "2.5");
+        assert_eq!(x_label_count(&PlotAttribute::new()), 20);
+        assert_eq!(x_label_count(&PlotAttribute::new().log_x(true)), 10);
+    }
+
+    #[test]
+    fn log_x_axis_spans_decades_of_counts() {
+        let data = records(0.01, 1000);
+        let attr = PlotAttribute::new().log_x(true);
+        let svg = Renderer::render_svg(&attr, &data, &Annotation::default()).unwrap();
+        let texts = texts(&svg);
+        for label in ["1", "10", "100", "1000"].iter() {
+            assert!(texts.contains(label), "{}", label);
+        }
+
+        // Zoomed range replaces counts of records
+        let attr = PlotAttribute::new().log_x(true).x_range(10.0, 100.0);
+        let svg = Renderer::render_svg(&attr, &data, &Annotation::default()).unwrap();
+        let texts = self::texts(&svg);
+        assert!(texts.contains(&"10"));
+        assert!(!texts.contains(&"1000"));
+    }
+
+    #[test]
+    fn survival_axis_is_logarithmic_down_to_a_decade() {
+        // Survival of the last record is 0.9^50, about 0.005
+        let data = records(0.1, 50);
+        let attr = PlotAttribute::new().kind(PlotKind::Survival);
+        let svg = Renderer::render_svg(&attr, &data, &Annotation::default()).unwrap();
+        let texts = texts(&svg);
+        assert!(texts.contains(&"1 - Prob (r)"));
+        // Every decade is labelled with digits down to its first significant digit
+        for label in ["0.001", "0.01", "0.1"].iter() {
+            assert!(texts.contains(label), "{}", label);
+        }
+
+        // Zoomed into a single decade
+        let attr = attr.y_range(0.1, 1.0);
+        let svg = Renderer::render_svg(&attr, &data, &Annotation::default()).unwrap();
+        let texts = self::texts(&svg);
+        assert!(texts.contains(&"0.1"));
+        assert!(texts.contains(&"0.5"));
+        assert!(!texts.contains(&"0.02"));
+    }
+
+    #[test]
+    fn overlay_series_follow_added_calculators() {
+        let mut overlay = Overlay::new();
+        overlay
+            .add_range("base", &mut calculator(0.1, "First"), Some(3))
+            .unwrap();
+        let mut boosted = calculator(0.5, "Second").target_probability(0.7);
+        overlay.add_conditional("boosted", &mut boosted).unwrap();
+
+        let names: Vec<&str> = overlay.series.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["base", "boosted"]);
+        assert_eq!(overlay.series[0].1.len(), 3);
+        // Conditional series ends where a target is reached
+        let counts: Vec<usize> = overlay.series[1].1.iter().map(|r| r.count).collect();
+        assert_eq!(counts, [1, 2]);
+        // Chart attribute is from the first calculator
+        assert_eq!(overlay.chart_attribute().caption, "First");
+    }
+
+    #[test]
+    fn overlay_attribute_overrides_calculators() {
+        let mut overlay = Overlay::new()
+            .attribute(PlotAttribute::new().caption("Given"))
+            .unwrap();
+        overlay
+            .add_range("base", &mut calculator(0.1, "First"), Some(2))
+            .unwrap();
+        assert_eq!(overlay.chart_attribute().caption, "Given");
+
+        assert!(Overlay::new()
+            .attribute(PlotAttribute::new().prob_color("reddish"))
+            .is_err());
+    }
+
+    #[test]
+    fn overlay_pushes_calculated_records_as_they_are() {
+        let mut cal = calculator(0.2, "First");
+        let records = cal.range_records(Some(4)).unwrap();
+        let mut overlay = Overlay::new();
+        overlay.push("printed", &cal, records.clone());
+        let pushed: Vec<f32> = overlay.series[0].1.iter().map(|r| r.cost).collect();
+        let original: Vec<f32> = records.iter().map(|r| r.cost).collect();
+        assert_eq!(pushed, original);
+    }
+
+    #[test]
+    fn overlay_svg_has_a_legend_of_series() {
+        let mut overlay = Overlay::new();
+        assert!(overlay.render_svg().is_err());
+
+        overlay
+            .add_range("base", &mut calculator(0.1, "First"), Some(5))
+            .unwrap();
+        overlay
+            .add_range("boosted", &mut calculator(0.3, "Second"), Some(5))
+            .unwrap();
+        let svg = overlay.render_svg().unwrap();
+        let texts: Vec<&str> = svg.lines().collect();
+        for text in ["First", "base", "boosted"] {
+            assert!(texts.contains(&text), "{}", text);
         }
     }
 }
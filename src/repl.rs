@@ -0,0 +1,365 @@
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::models::CsvRef;
+use crate::{Calculator, GcalcError, GcalcResult, PlotTarget, ProbType, TableFormat};
+
+const PROMPT: &str = "gcalc> ";
+const DEFAULT_OPTION_PATH: &str = "option.json";
+const HELP: &str = r#"Commands
+    set <key> <value>       Set a value, where key is one of
+                            probability, constant, cost, pity, budget, target, targets,
+                            value, format, precision, probtype and plot(off|file|term)
+    unset <key>             Clear a value, where key is one of target, targets, budget and pity
+    load <path>             Use a reference file
+    unload                  Stop using a reference file
+    range [count] [start]   Print range of calculations
+    cond [<key> <value>]..  Conditional calculation, after setting given values which stay set
+    qual [<key> <value>]..  Conditional calculation but only prints result
+    stats                   Print median, 90th and 99th percentile of tries
+    show                    Print current configuration as an option file
+    save [path]             Save current configuration as an option file [default: option.json]
+    history                 Print previous commands
+    help                    Print this message
+    exit                    End a session"#;
+
+/// Whether a session continues after a command
+enum Flow {
+    Continue,
+    Exit,
+}
+
+/// Interactive session over a single calculator
+///
+/// Each line is a command which either changes a configuration or runs a calculation, thus a
+/// value can be tuned without starting the program again. A failed command is reported to
+/// stderr and a session continues.
+pub(crate) struct Repl {
+    calculator: Calculator,
+    history: Vec<String>,
+}
+
+impl Repl {
+    pub fn new(calculator: Calculator) -> Self {
+        Self {
+            calculator,
+            history: vec![],
+        }
+    }
+
+    /// Read commands from stdin until an exit command or an end of input
+    ///
+    /// Prompt is printed only when stdin is a terminal, so that commands can be piped.
+    pub fn run(&mut self) -> GcalcResult<()> {
+        let stdin = std::io::stdin();
+        let interactive = stdin.is_terminal();
+        let mut stdout = std::io::stdout();
+        if interactive {
+            writeln!(stdout, "Type \"help\" for commands")?;
+        }
+
+        let mut line = String::new();
+        loop {
+            if interactive {
+                write!(stdout, "{}", PROMPT)?;
+                stdout.flush()?;
+            }
+            line.clear();
+            if stdin.lock().read_line(&mut line)? == 0 {
+                break;
+            }
+            let command = line.trim();
+            if command.is_empty() || command.starts_with('#') {
+                continue;
+            }
+
+            self.history.push(command.to_owned());
+            match self.execute(command) {
+                Ok(Flow::Continue) => (),
+                Ok(Flow::Exit) => break,
+                Err(err) => writeln!(std::io::stderr(), "{}", err)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, command: &str) -> GcalcResult<Flow> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let (name, args) = (words[0].to_lowercase(), &words[1..]);
+        match name.as_str() {
+            // Value can have spaces, e.g. "1 in 200"
+            "set" => match args {
+                [key, value @ ..] if !value.is_empty() => self.set(key, &value.join(" "))?,
+                _ => return Err(Self::usage("set <key> <value>")),
+            },
+            "unset" => match args {
+                [key] => self.unset(key)?,
+                _ => return Err(Self::usage("unset <key>")),
+            },
+            "load" => match args {
+                [path] => {
+                    if !Path::new(path).is_file() {
+                        return Err(GcalcError::InvalidArgument(format!(
+                            "Reference file \"{}\" doesn't exist",
+                            path
+                        )));
+                    }
+                    self.calculator
+                        .set_csv_file(CsvRef::File(PathBuf::from(path)));
+                }
+                _ => return Err(Self::usage("load <path>")),
+            },
+            "unload" => self.calculator.set_csv_file(CsvRef::None),
+            "range" => {
+                let count = args
+                    .first()
+                    .map(|count| Self::parse::<usize>(count, "Count should be a positive integer"))
+                    .transpose()?;
+                let start = args
+                    .get(1)
+                    .map(|start| {
+                        Self::parse::<usize>(start, "Start index should be a positive integer")
+                    })
+                    .transpose()?;
+                self.calculator.print_range(count, start)?;
+                Self::end_output()?;
+            }
+            "cond" | "qual" => {
+                if args.len() % 2 != 0 {
+                    return Err(Self::usage(&format!("{} [<key> <value>]..", name)));
+                }
+                for pair in args.chunks(2) {
+                    self.set(pair[0], pair[1])?;
+                }
+                if name == "cond" {
+                    self.calculator.print_conditional()?;
+                } else {
+                    self.calculator.print_qualfication()?;
+                }
+                Self::end_output()?;
+            }
+            "stats" => {
+                self.calculator.print_statistics()?;
+                Self::end_output()?;
+            }
+            "show" => writeln!(
+                std::io::stdout(),
+                "{}",
+                self.calculator.to_option().to_json()?
+            )?,
+            "save" => {
+                let path = args.first().copied().unwrap_or(DEFAULT_OPTION_PATH);
+                std::fs::write(path, self.calculator.to_option().to_json()?)?;
+                writeln!(std::io::stdout(), "Saved option file \"{}\"", path)?;
+            }
+            "history" => {
+                let mut stdout = std::io::stdout();
+                for (index, command) in self.history.iter().enumerate() {
+                    writeln!(stdout, "{:>4}  {}", index + 1, command)?;
+                }
+            }
+            "help" => writeln!(std::io::stdout(), "{}", HELP)?,
+            "exit" | "quit" => return Ok(Flow::Exit),
+            _ => {
+                return Err(GcalcError::InvalidArgument(format!(
+                    "\"{}\" is not a command, type \"help\" for commands",
+                    name
+                )))
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Set a value of a calculator with the same notation as command line arguments
+    fn set(&mut self, key: &str, value: &str) -> GcalcResult<()> {
+        let cal = &mut self.calculator;
+        match key.to_lowercase().as_str() {
+            "probability" | "prob" | "p" => {
+                let probability = cal.parse_probability(value)?;
+                cal.set_probability(probability, true)?;
+            }
+            "constant" => {
+                let constant = cal.parse_probability(value)?;
+                cal.set_constant(constant, true)?;
+            }
+            "cost" => cal.set_cost(Self::parse(value, "Cost should be a number")?, true),
+            "pity" => cal.set_pity(Self::parse(
+                value,
+                "Pity should be a positive integer (usize)",
+            )?),
+            "budget" => cal.set_budget(Self::parse(value, "Budget should be a number")?),
+            "target" => {
+                let target = cal.parse_probability(value)?;
+                cal.set_target_probability(target)?;
+            }
+            "targets" => {
+                let targets = value
+                    .split(',')
+                    .map(|target| cal.parse_probability(target))
+                    .collect::<GcalcResult<Vec<f32>>>()?;
+                cal.set_targets(targets)?;
+            }
+            "value" => cal.set_value(Self::parse(value, "Value should be a number")?),
+            "format" => cal.set_table_format(TableFormat::from_str(value)?),
+            "precision" => cal.set_precision(Self::parse(
+                value,
+                "Precision should be a positive integer (usize)",
+            )?),
+            "probtype" => cal.set_prob_type(ProbType::from_str(value)?),
+            "plot" => {
+                if value == "off" {
+                    cal.set_plot(false);
+                } else {
+                    let attribute = cal
+                        .chart_attribute()
+                        .clone()
+                        .target(PlotTarget::from_str(value)?);
+                    cal.set_plot_attribute(attribute)?;
+                    cal.set_plot(true);
+                }
+            }
+            _ => {
+                return Err(GcalcError::InvalidArgument(format!(
+                    "\"{}\" is not a key to set, type \"help\" for keys",
+                    key
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Clear an optional value which otherwise can't be undone by a set
+    fn unset(&mut self, key: &str) -> GcalcResult<()> {
+        let cal = &mut self.calculator;
+        match key.to_lowercase().as_str() {
+            "target" => cal.clear_target_probability(),
+            "targets" => cal.set_targets(vec![])?,
+            "budget" => cal.clear_budget(),
+            "pity" => cal.clear_pity(),
+            _ => {
+                return Err(GcalcError::InvalidArgument(format!(
+                    "\"{}\" is not a key to unset, type \"help\" for keys",
+                    key
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn parse<T: FromStr>(value: &str, message: &str) -> GcalcResult<T> {
+        value
+            .parse()
+            .map_err(|_| GcalcError::ParseError(message.to_owned()))
+    }
+
+    fn usage(usage: &str) -> GcalcError {
+        GcalcError::InvalidArgument(format!("Usage : {}", usage))
+    }
+
+    /// Csv table doesn't end with a new line, which would be followed by a prompt
+    fn end_output() -> GcalcResult<()> {
+        writeln!(std::io::stdout())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repl() -> Repl {
+        Repl::new(Calculator::new().unwrap())
+    }
+
+    fn run(repl: &mut Repl, command: &str) -> GcalcResult<()> {
+        repl.execute(command).map(|_| ())
+    }
+
+    /// Current configuration as it is shown
+    fn option(repl: &Repl) -> serde_json::Value {
+        serde_json::from_str(&repl.calculator.to_option().to_json().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn set_parses_values_with_spaces() {
+        let mut repl = repl();
+        run(&mut repl, "set probability 1 in 200").unwrap();
+        run(&mut repl, "SET constant 0.5%").unwrap();
+        run(&mut repl, "set cost 160").unwrap();
+        run(&mut repl, "set pity 90").unwrap();
+        run(&mut repl, "set targets 50%, 90%").unwrap();
+        let option = option(&repl);
+        assert_eq!(option["probability"], 0.005);
+        assert_eq!(option["constant"], 0.005);
+        assert_eq!(option["cost"], 160.0);
+        assert_eq!(option["pity"], 90);
+        assert_eq!(option["targets"], serde_json::json!([0.5, 0.9]));
+    }
+
+    #[test]
+    fn unset_clears_optional_values() {
+        let mut repl = repl();
+        run(&mut repl, "set budget 100").unwrap();
+        run(&mut repl, "set target 0.9").unwrap();
+        run(&mut repl, "set pity 10").unwrap();
+        for key in ["budget", "target", "pity", "targets"] {
+            run(&mut repl, &format!("unset {}", key)).unwrap();
+        }
+        let option = option(&repl);
+        for key in ["budget", "target", "pity"] {
+            assert!(option[key].is_null(), "{}", key);
+        }
+        assert_eq!(option["targets"], serde_json::json!([]));
+        assert!(run(&mut repl, "unset cost").is_err());
+    }
+
+    #[test]
+    fn conditional_commands_keep_given_values() {
+        let mut repl = repl();
+        run(&mut repl, "set probability 0.5").unwrap();
+        // Neither target nor budget
+        assert!(matches!(
+            run(&mut repl, "cond"),
+            Err(GcalcError::InvalidConditional(_))
+        ));
+        run(&mut repl, "cond target 0.9").unwrap();
+        assert_eq!(option(&repl)["target"], 0.9);
+        run(&mut repl, "qual budget 30 cost 10").unwrap();
+        let option = option(&repl);
+        assert_eq!(
+            (&option["budget"], &option["cost"]),
+            (&30.0.into(), &10.0.into())
+        );
+        run(&mut repl, "stats").unwrap();
+        run(&mut repl, "range 3 2").unwrap();
+    }
+
+    #[test]
+    fn invalid_commands_are_errors() {
+        let mut repl = repl();
+        for command in [
+            "launch",
+            "set",
+            "set probability",
+            "set luck 1",
+            "set probability 200%",
+            "set pity -1",
+            "unset",
+            "cond target",
+            "range many",
+            "load no-such-file.csv",
+        ] {
+            assert!(run(&mut repl, command).is_err(), "{}", command);
+        }
+        // A failed command doesn't change a configuration
+        assert_eq!(option(&repl)["probability"], 1.0);
+    }
+
+    #[test]
+    fn exit_ends_a_session() {
+        let mut repl = repl();
+        assert!(matches!(repl.execute("quit"), Ok(Flow::Exit)));
+        assert!(matches!(repl.execute("help"), Ok(Flow::Continue)));
+    }
+}
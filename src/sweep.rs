@@ -0,0 +1,318 @@
+use std::str::FromStr;
+
+#[cfg(any(feature = "plotters", feature = "json"))]
+use crate::models::PlotAttribute;
+#[cfg(feature = "plotters")]
+use crate::plot::Renderer;
+#[cfg(feature = "json")]
+use crate::vega::VegaRenderer;
+use crate::{Calculator, GcalcError, GcalcResult};
+
+/// Calculator parameter which changes along a sweep axis
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SweepParameter {
+    Probability,
+    Constant,
+    Cost,
+    Budget,
+    Pity,
+}
+
+impl SweepParameter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Probability => "probability",
+            Self::Constant => "constant",
+            Self::Cost => "cost",
+            Self::Budget => "budget",
+            Self::Pity => "pity",
+        }
+    }
+
+    /// Set a parameter of a calculator as an initial value
+    pub fn apply(&self, calculator: &mut Calculator, value: f32) -> GcalcResult<()> {
+        match self {
+            Self::Probability => calculator.set_probability(value, true)?,
+            Self::Constant => calculator.set_constant(value, true)?,
+            Self::Cost => calculator.set_cost(value, true),
+            Self::Budget => calculator.set_budget(value),
+            Self::Pity => {
+                if value < 1.0 {
+                    return Err(GcalcError::InvalidArgument(format!(
+                        "Pity \"{}\" should be a positive count",
+                        value
+                    )));
+                }
+                calculator.set_pity(value.round() as usize)
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SweepParameter {
+    type Err = GcalcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "probability" | "prob" | "rate" => Ok(Self::Probability),
+            "constant" => Ok(Self::Constant),
+            "cost" => Ok(Self::Cost),
+            "budget" => Ok(Self::Budget),
+            "pity" => Ok(Self::Pity),
+            _ => Err(GcalcError::InvalidConversion(format!(
+                "{} is not a valid sweep parameter",
+                s
+            ))),
+        }
+    }
+}
+
+/// Value of each sweep cell
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SweepMetric {
+    /// Probability of success within budget
+    Success,
+    /// Tries needed to reach target probability
+    Tries,
+}
+
+impl SweepMetric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Success => "success within budget",
+            Self::Tries => "tries to target",
+        }
+    }
+}
+
+impl FromStr for SweepMetric {
+    type Err = GcalcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "success" => Ok(Self::Success),
+            "tries" | "count" => Ok(Self::Tries),
+            _ => Err(GcalcError::InvalidConversion(format!(
+                "{} is not a valid sweep metric",
+                s
+            ))),
+        }
+    }
+}
+
+/// Values of a parameter along an axis
+#[derive(Clone, Debug)]
+pub struct SweepAxis {
+    pub parameter: SweepParameter,
+    pub values: Vec<f32>,
+}
+
+impl SweepAxis {
+    pub fn new(parameter: SweepParameter, values: Vec<f32>) -> Self {
+        Self { parameter, values }
+    }
+
+    /// Parse an axis such as "probability=0.005:0.02:0.005" or "pity=50,70,90"
+    ///
+    /// Range form is start, end and step where end is inclusive.
+    pub fn parse(text: &str) -> GcalcResult<Self> {
+        let invalid = || {
+            GcalcError::InvalidArgument(format!(
+                "\"{}\" should be a form of parameter=start:end:step or parameter=v1,v2",
+                text
+            ))
+        };
+        let (parameter, values) = text.split_once('=').ok_or_else(invalid)?;
+        let parameter = SweepParameter::from_str(parameter.trim())?;
+        let numbers = |text: &str, separator: char| {
+            text.split(separator)
+                .map(|value| value.trim().parse::<f32>().map_err(|_| invalid()))
+                .collect::<GcalcResult<Vec<f32>>>()
+        };
+
+        let values = if values.contains(':') {
+            match numbers(values, ':')?.as_slice() {
+                &[start, end, step] if step > 0.0 && start <= end => {
+                    let steps = ((end - start) / step + 1e-4).floor() as usize;
+                    (0..=steps)
+                        .map(|index| start + step * index as f32)
+                        .collect()
+                }
+                _ => return Err(invalid()),
+            }
+        } else {
+            numbers(values, ',')?
+        };
+        if values.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self::new(parameter, values))
+    }
+}
+
+/// Maximum count of cells, because every cell is a separate calculation
+pub const MAX_SWEEP_CELLS: usize = 10_000;
+
+/// Two parameter sweep over repeated calculator evaluations
+pub struct Sweep {
+    x: SweepAxis,
+    y: SweepAxis,
+    metric: SweepMetric,
+}
+
+impl Sweep {
+    /// Create a sweep of two different parameters
+    ///
+    /// This fails if both axes have a same parameter or if a grid is too large.
+    pub fn new(x: SweepAxis, y: SweepAxis, metric: SweepMetric) -> GcalcResult<Self> {
+        if x.parameter == y.parameter {
+            return Err(GcalcError::InvalidArgument(format!(
+                "X and y axes should have different parameters, but both are \"{}\"",
+                x.parameter.name()
+            )));
+        }
+        let cells = x.values.len().saturating_mul(y.values.len());
+        if cells > MAX_SWEEP_CELLS {
+            return Err(GcalcError::InvalidArgument(format!(
+                "Sweep has {} cells, which exceeds a limit of {}",
+                cells, MAX_SWEEP_CELLS
+            )));
+        }
+        Ok(Self { x, y, metric })
+    }
+
+    /// Evaluate every cell with a calculator from a base
+    ///
+    /// Base creates a calculator for each cell, so that parameters don't leak between cells.
+    pub fn run(&self, base: impl Fn() -> GcalcResult<Calculator>) -> GcalcResult<SweepGrid> {
+        let mut cells = vec![];
+        for &y in &self.y.values {
+            let mut row = vec![];
+            for &x in &self.x.values {
+                let mut calculator = base()?;
+                self.x.parameter.apply(&mut calculator, x)?;
+                self.y.parameter.apply(&mut calculator, y)?;
+                row.push(calculator.evaluate(self.metric)?);
+            }
+            cells.push(row);
+        }
+        Ok(SweepGrid {
+            x: self.x.clone(),
+            y: self.y.clone(),
+            metric: self.metric,
+            cells,
+        })
+    }
+}
+
+/// Results of a sweep, where cells are indexed by y and then x
+pub struct SweepGrid {
+    pub x: SweepAxis,
+    pub y: SweepAxis,
+    pub metric: SweepMetric,
+    pub cells: Vec<Vec<f32>>,
+}
+
+impl SweepGrid {
+    /// Grid as a csv table, where the first column is y values
+    pub fn to_csv(&self) -> String {
+        let mut lines = vec![std::iter::once(format!(
+            "{}\\{}",
+            self.y.parameter.name(),
+            self.x.parameter.name()
+        ))
+        .chain(self.x.values.iter().map(|x| x.to_string()))
+        .collect::<Vec<_>>()
+        .join(",")];
+        for (y, row) in self.y.values.iter().zip(self.cells.iter()) {
+            lines.push(
+                std::iter::once(y.to_string())
+                    .chain(row.iter().map(|cell| cell.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+        }
+        lines.join("\n")
+    }
+
+    /// Draw a heatmap into a file of a path from given attribute
+    #[cfg(feature = "plotters")]
+    pub fn draw(&self, attr: &PlotAttribute) -> GcalcResult<()> {
+        Renderer::draw_heatmap(attr, self)
+    }
+
+    /// Render a heatmap as a svg document
+    #[cfg(feature = "plotters")]
+    pub fn render_svg(&self, attr: &PlotAttribute) -> GcalcResult<String> {
+        Renderer::render_heatmap_svg(attr, self)
+    }
+
+    /// Render a heatmap as a Vega-Lite specification
+    #[cfg(feature = "json")]
+    pub fn render_vega_lite(&self, attr: &PlotAttribute) -> GcalcResult<String> {
+        VegaRenderer::render(&VegaRenderer::heatmap(attr, self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_axis_includes_end() {
+        let axis = SweepAxis::parse("prob=0.005:0.02:0.005").unwrap();
+        assert_eq!(axis.parameter, SweepParameter::Probability);
+        assert_eq!(axis.values.len(), 4);
+        assert!((axis.values[3] - 0.02).abs() < 1e-6);
+    }
+
+    #[test]
+    fn list_axis_keeps_values() {
+        let axis = SweepAxis::parse("pity = 50, 70,90").unwrap();
+        assert_eq!(axis.parameter, SweepParameter::Pity);
+        assert_eq!(axis.values, vec![50.0, 70.0, 90.0]);
+    }
+
+    #[test]
+    fn invalid_axes_are_rejected() {
+        for text in [
+            "pity",
+            "luck=1,2",
+            "cost=1:10:0",
+            "cost=10:1:1",
+            "cost=1:10",
+            "cost=1,a",
+            "cost=",
+        ] {
+            assert!(SweepAxis::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn sweep_requires_different_parameters() {
+        let axis = |text: &str| SweepAxis::parse(text).unwrap();
+        assert!(Sweep::new(axis("cost=1,2"), axis("pity=10,20"), SweepMetric::Tries).is_ok());
+        assert!(Sweep::new(axis("cost=1,2"), axis("cost=3,4"), SweepMetric::Tries).is_err());
+        assert!(Sweep::new(axis("prob=0.1"), axis("rate=0.2"), SweepMetric::Tries).is_err());
+    }
+
+    #[test]
+    fn sweep_cells_are_limited() {
+        let x = SweepAxis::new(SweepParameter::Cost, vec![1.0; 100]);
+        let y = SweepAxis::new(SweepParameter::Budget, vec![1.0; MAX_SWEEP_CELLS / 100]);
+        assert!(Sweep::new(x.clone(), y, SweepMetric::Success).is_ok());
+        let y = SweepAxis::new(SweepParameter::Budget, vec![1.0; MAX_SWEEP_CELLS / 100 + 1]);
+        assert!(Sweep::new(x, y, SweepMetric::Success).is_err());
+    }
+
+    #[test]
+    fn sweep_evaluates_every_cell() {
+        let axis = |text: &str| SweepAxis::parse(text).unwrap();
+        let sweep = Sweep::new(axis("prob=0.5,1"), axis("cost=1,2,3"), SweepMetric::Tries).unwrap();
+        let grid = sweep
+            .run(|| Ok(Calculator::new()?.target_probability(0.7)))
+            .unwrap();
+        assert_eq!(grid.cells.len(), 3);
+        assert_eq!(grid.cells[0], vec![2.0, 1.0]);
+        assert!(grid.to_csv().starts_with("cost\\probability,0.5,1\n1,2,1"));
+    }
+}
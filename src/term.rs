@@ -0,0 +1,407 @@
+use std::io::IsTerminal;
+
+use crate::models::{GcalcResult, PlotAttribute, PlotKind, Record};
+
+// Size of a probability panel and a cost panel in characters
+const PROB_HEIGHT: usize = 10;
+const COST_HEIGHT: usize = 5;
+const DEFAULT_WIDTH: usize = 80;
+
+/// Chart of braille characters for terminals
+///
+/// Probability and cost series are drawn in separate panels which share a count axis, so that
+/// both series are readable without colours.
+pub(crate) struct TermRenderer;
+
+impl TermRenderer {
+    /// Render chart with a width of a terminal
+    ///
+    /// Width follows "COLUMNS" environment variable if any. Colours are used only when stdout
+    /// is a terminal and "NO_COLOR" is not set.
+    pub fn render(attr: &PlotAttribute, data: &[Record]) -> GcalcResult<String> {
+        let width = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .unwrap_or(DEFAULT_WIDTH);
+        let colored = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        Self::render_with(attr, data, width, colored)
+    }
+
+    /// Render chart with a given width, where colours are escape codes
+    pub fn render_with(
+        attr: &PlotAttribute,
+        data: &[Record],
+        width: usize,
+        colored: bool,
+    ) -> GcalcResult<String> {
+        // Points outside of a zoomed count range are not drawn
+        let data: Vec<Record> = match attr.x_range {
+            Some((min, max)) => data
+                .iter()
+                .filter(|r| (min..=max).contains(&(r.count as f64)))
+                .cloned()
+                .collect(),
+            None => data.to_vec(),
+        };
+        // Nothing to draw, while a table already says so
+        if data.is_empty() {
+            return Ok(String::new());
+        }
+
+        let (prob_caption, prob_value): (&str, fn(&Record) -> f64) = match attr.kind {
+            PlotKind::Cumulative => (&attr.prob_caption, |r| r.probability_src as f64),
+            PlotKind::Histogram => ("First success", |r| r.first_success.0 as f64),
+            PlotKind::Survival => ("Survival", |r| 1.0 - r.probability_src as f64),
+        };
+        let prob_range = attr.y_range.unwrap_or_else(|| match attr.kind {
+            PlotKind::Cumulative => (0.0, 1.0),
+            PlotKind::Histogram => (0.0, data.iter().map(prob_value).fold(0f64, f64::max)),
+            // Down to a whole decade below the lowest survival
+            PlotKind::Survival => {
+                let lowest = data
+                    .iter()
+                    .map(prob_value)
+                    .filter(|&value| value > 0.0)
+                    .fold(1f64, f64::min);
+                (10f64.powf(lowest.log10().floor()).min(0.1), 1.0)
+            }
+        });
+        let cost_max = data.iter().map(|r| r.cost as f64).fold(0f64, f64::max);
+
+        let prob_labels = (
+            Self::prob_label(prob_range.1),
+            Self::prob_label(prob_range.0),
+        );
+        let cost_labels = (Self::cost_label(cost_max), Self::cost_label(0.0));
+        let label_width = [
+            &prob_labels.0,
+            &prob_labels.1,
+            &cost_labels.0,
+            &cost_labels.1,
+        ]
+        .iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap_or(0);
+        // Label, a tick and at least a few cells
+        let plot_width = width.saturating_sub(label_width + 2).max(10);
+
+        let (first, last) = attr
+            .x_range
+            .unwrap_or((data[0].count as f64, data[data.len() - 1].count as f64));
+        // Logarithmic axes are drawn as logarithms of values on linear axes
+        let scale_x = |count: f64| if attr.log_x { count.ln() } else { count };
+        let log_y = attr.kind == PlotKind::Survival;
+        let scale_y = |value: f64| if log_y { value.log10() } else { value };
+        let points = |value: &dyn Fn(&Record) -> f64, log: bool| -> Vec<(f64, f64)> {
+            data.iter()
+                .map(|r| (scale_x(r.count as f64), value(r)))
+                .filter(|&(_, value)| !log || value > 0.0)
+                .map(|(x, value)| (x, if log { value.log10() } else { value }))
+                .collect()
+        };
+        let x_span = (scale_x(first), scale_x(last));
+
+        let mut lines = vec![attr.caption.clone()];
+        let prob_color = Self::escape(&attr.prob_color, colored)?;
+        let mut canvas = Canvas::new(plot_width, PROB_HEIGHT);
+        canvas.plot(
+            &points(&prob_value, log_y),
+            x_span,
+            (scale_y(prob_range.0), scale_y(prob_range.1)),
+        );
+        lines.extend(canvas.panel(&prob_labels, label_width, &prob_color));
+
+        // Cost panel is meaningless without cost
+        let cost_color = Self::escape(&attr.cost_color, colored)?;
+        let has_cost = cost_max > 0.0;
+        if has_cost {
+            let mut canvas = Canvas::new(plot_width, COST_HEIGHT);
+            canvas.plot(&points(&|r| r.cost as f64, false), x_span, (0.0, cost_max));
+            lines.extend(canvas.panel(&cost_labels, label_width, &cost_color));
+        }
+
+        let padding = " ".repeat(label_width + 1);
+        lines.push(format!("{}└{}", padding, "─".repeat(plot_width)));
+        let (first_label, last_label) = (first.to_string(), last.to_string());
+        lines.push(format!(
+            "{} {}{:>width$}",
+            padding,
+            first_label,
+            last_label,
+            width = plot_width.saturating_sub(first_label.len())
+        ));
+
+        let reset = if colored { "\x1b[0m" } else { "" };
+        let mut legend = format!("{} {}⣿{} {}", padding, prob_color, reset, prob_caption);
+        if has_cost {
+            legend.push_str(&format!("  {}⣿{} {}", cost_color, reset, attr.cost_caption));
+        }
+        lines.push(legend);
+
+        Ok(lines.join("\n") + "\n")
+    }
+
+    /// Probability label, where a small value of a logarithmic axis keeps its digits
+    fn prob_label(probability: f64) -> String {
+        if probability >= 0.01 || probability == 0.0 {
+            format!("{:.2}", probability)
+        } else {
+            format!("{:e}", probability)
+        }
+    }
+
+    fn cost_label(cost: f64) -> String {
+        if cost >= 10.0 || cost == 0.0 {
+            format!("{:.0}", cost)
+        } else {
+            format!("{:.2}", cost)
+        }
+    }
+
+    /// Ansi escape code of a true colour
+    fn escape(color: &str, colored: bool) -> GcalcResult<String> {
+        let (r, g, b) = PlotAttribute::parse_color(color)?;
+        if colored {
+            Ok(format!("\x1b[38;2;{};{};{}m", r, g, b))
+        } else {
+            Ok(String::new())
+        }
+    }
+}
+
+/// Grid of braille cells where each cell has 2 x 4 dots
+struct Canvas {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![0; width * height],
+        }
+    }
+
+    /// Set a dot where y grows from the bottom
+    fn set(&mut self, x: usize, y: usize) {
+        let (dot_width, dot_height) = (self.width * 2, self.height * 4);
+        if x >= dot_width || y >= dot_height {
+            return;
+        }
+        let row = dot_height - 1 - y;
+        // Braille dots are numbered by columns except for the last row
+        let bit = match (x % 2, row % 4) {
+            (0, 3) => 0x40,
+            (1, 3) => 0x80,
+            (column, dot) => 1 << (column * 3 + dot),
+        };
+        self.cells[(row / 4) * self.width + x / 2] |= bit;
+    }
+
+    /// Lines between points, where x and y are scaled from ranges
+    fn plot(&mut self, points: &[(f64, f64)], (first, last): (f64, f64), (min, max): (f64, f64)) {
+        let x_span = (self.width * 2 - 1) as f64;
+        let y_span = (self.height * 4 - 1) as f64;
+        let scale = |(x, y): (f64, f64)| {
+            let x = if last > first {
+                (x - first) / (last - first) * x_span
+            } else {
+                0.0
+            };
+            let y = if max > min {
+                (y - min) / (max - min) * y_span
+            } else {
+                0.0
+            };
+            (x.round() as i64, y.clamp(0.0, y_span).round() as i64)
+        };
+
+        let mut previous: Option<(i64, i64)> = None;
+        for &point in points {
+            let current = scale(point);
+            match previous {
+                Some(from) => self.line(from, current),
+                None => self.set(current.0 as usize, current.1 as usize),
+            }
+            previous.replace(current);
+        }
+    }
+
+    /// Bresenham's line
+    fn line(&mut self, (mut x, mut y): (i64, i64), (to_x, to_y): (i64, i64)) {
+        let (dx, dy) = ((to_x - x).abs(), -(to_y - y).abs());
+        let (step_x, step_y) = ((to_x - x).signum(), (to_y - y).signum());
+        let mut error = dx + dy;
+        loop {
+            self.set(x as usize, y as usize);
+            if x == to_x && y == to_y {
+                break;
+            }
+            let doubled = error * 2;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Rows with a max label on the top and a min label on the bottom
+    fn panel(&self, (max, min): &(String, String), label_width: usize, color: &str) -> Vec<String> {
+        let reset = if color.is_empty() { "" } else { "\x1b[0m" };
+        (0..self.height)
+            .map(|row| {
+                let label = match row {
+                    0 => max.as_str(),
+                    _ if row == self.height - 1 => min.as_str(),
+                    _ => "",
+                };
+                let tick = if label.is_empty() { '│' } else { '┤' };
+                let cells: String = self.cells[row * self.width..(row + 1) * self.width]
+                    .iter()
+                    .map(|&bits| std::char::from_u32(0x2800 + bits as u32).unwrap_or(' '))
+                    .collect();
+                format!(
+                    "{:>width$} {}{}{}{}",
+                    label,
+                    tick,
+                    color,
+                    cells,
+                    reset,
+                    width = label_width
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(costs: &[f32]) -> Vec<Record> {
+        costs
+            .iter()
+            .enumerate()
+            .map(|(index, &cost)| {
+                let probability = (index + 1) as f32 / costs.len() as f32;
+                Record::new(index + 1, probability, String::new(), cost, 0.0, 0.0)
+            })
+            .collect()
+    }
+
+    fn braille(line: &str) -> String {
+        line.chars()
+            .filter(|c| ('\u{2800}'..='\u{28ff}').contains(c))
+            .collect()
+    }
+
+    #[test]
+    fn canvas_sets_braille_dots() {
+        let mut canvas = Canvas::new(1, 1);
+        // Bottom left and top right of a single cell
+        canvas.set(0, 0);
+        canvas.set(1, 3);
+        assert_eq!(canvas.cells, vec![0x40 | 0x08]);
+
+        // Dots outside of a canvas are ignored
+        canvas.set(2, 0);
+        canvas.set(0, 4);
+        assert_eq!(canvas.cells, vec![0x48]);
+    }
+
+    #[test]
+    fn canvas_draws_line_between_points() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.plot(&[(0.0, 0.0), (1.0, 1.0)], (0.0, 1.0), (0.0, 1.0));
+        // Diagonal from the bottom left to the top right of 4 x 4 dots
+        assert_eq!(canvas.cells, vec![0x40 | 0x20, 0x02 | 0x08]);
+    }
+
+    #[test]
+    fn canvas_panel_labels_top_and_bottom() {
+        let canvas = Canvas::new(3, 3);
+        let rows = canvas.panel(&("1.00".to_owned(), "0.00".to_owned()), 5, "");
+        assert_eq!(rows, vec![" 1.00 ┤⠀⠀⠀", "      │⠀⠀⠀", " 0.00 ┤⠀⠀⠀"]);
+    }
+
+    #[test]
+    fn render_draws_probability_and_cost_panels() {
+        let attr = PlotAttribute::new();
+        let chart =
+            TermRenderer::render_with(&attr, &records(&[10.0, 20.0, 30.0, 40.0]), 40, false)
+                .unwrap();
+        let lines: Vec<&str> = chart.lines().collect();
+
+        assert_eq!(lines[0], "Gcalc result");
+        // Caption, both panels, an axis, count labels and a legend
+        assert_eq!(lines.len(), 1 + PROB_HEIGHT + COST_HEIGHT + 3);
+        assert!(lines[1].trim_start().starts_with("1.00 ┤"));
+        assert!(lines[PROB_HEIGHT].trim_start().starts_with("0.00 ┤"));
+        assert!(lines[PROB_HEIGHT + 1].trim_start().starts_with("40 ┤"));
+        assert!(lines.iter().all(|line| line.chars().count() <= 40));
+        assert!(lines[lines.len() - 2].trim().starts_with('1'));
+        assert!(lines[lines.len() - 2].ends_with('4'));
+        assert_eq!(lines[lines.len() - 1].trim(), "⣿ Prob (r)  ⣿ Cost (b)");
+        assert!(!chart.contains('\x1b'));
+
+        // Every panel has dots of a series
+        for line in &lines[1..=PROB_HEIGHT + COST_HEIGHT] {
+            assert_eq!(braille(line).chars().count(), 40 - 6);
+        }
+        assert!(lines[1..=PROB_HEIGHT]
+            .iter()
+            .any(|line| braille(line).chars().any(|c| c != '\u{2800}')));
+    }
+
+    #[test]
+    fn render_skips_cost_panel_without_cost() {
+        let attr = PlotAttribute::new();
+        let chart = TermRenderer::render_with(&attr, &records(&[0.0, 0.0]), 40, false).unwrap();
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines.len(), 1 + PROB_HEIGHT + 3);
+        assert_eq!(lines[lines.len() - 1].trim(), "⣿ Prob (r)");
+    }
+
+    #[test]
+    fn render_colours_only_when_requested() {
+        let attr = PlotAttribute::new().prob_color("#ff0000");
+        let chart = TermRenderer::render_with(&attr, &records(&[10.0, 20.0]), 40, true).unwrap();
+        assert!(chart.contains("\x1b[38;2;255;0;0m"));
+        assert!(chart.contains("\x1b[0m"));
+
+        let attr = PlotAttribute::new().prob_color("not a colour");
+        assert!(TermRenderer::render_with(&attr, &records(&[10.0]), 40, false).is_err());
+    }
+
+    #[test]
+    fn render_filters_zoomed_range() {
+        let mut attr = PlotAttribute::new();
+        attr.x_range = Some((10.0, 20.0));
+        let chart = TermRenderer::render_with(&attr, &records(&[1.0, 2.0]), 40, false).unwrap();
+        assert!(chart.is_empty());
+
+        attr.x_range = Some((2.0, 3.0));
+        let chart =
+            TermRenderer::render_with(&attr, &records(&[1.0, 2.0, 3.0, 4.0]), 40, false).unwrap();
+        let counts = chart.lines().nth_back(1).unwrap();
+        assert!(counts.trim().starts_with('2'));
+        assert!(counts.ends_with('3'));
+    }
+
+    #[test]
+    fn labels_keep_small_values() {
+        assert_eq!(TermRenderer::prob_label(0.5), "0.50");
+        assert_eq!(TermRenderer::prob_label(0.0), "0.00");
+        assert_eq!(TermRenderer::prob_label(0.001), "1e-3");
+        assert_eq!(TermRenderer::cost_label(1500.0), "1500");
+        assert_eq!(TermRenderer::cost_label(2.5), "2.50");
+    }
+}
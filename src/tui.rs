@@ -0,0 +1,705 @@
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use crate::formatter::RecordFormatter;
+use crate::models::{CsvRef, Record};
+use crate::term::TermRenderer;
+use crate::{Calculator, GcalcError, GcalcResult};
+
+// Width of a parameter panel in characters
+const PANEL_WIDTH: usize = 32;
+// Rows of a table which are kept even if a chart doesn't fit
+const MIN_TABLE_ROWS: usize = 5;
+const DEFAULT_COUNT: usize = 100;
+const DEFAULT_CSV_PATH: &str = "out.csv";
+const DEFAULT_OPTION_PATH: &str = "option.json";
+const HELP: [&str; 4] = [
+    "up/down select  enter edit",
+    "+/- adjust  pgup/pgdn scroll",
+    "c export csv  o save option",
+    "q ctrl+c quit",
+];
+
+/// Parameter of a panel
+#[derive(Clone, Copy, PartialEq)]
+enum Param {
+    Probability,
+    Constant,
+    Cost,
+    Budget,
+    Target,
+    Pity,
+    Count,
+    Reference,
+}
+
+impl Param {
+    const ALL: [Param; 8] = [
+        Param::Probability,
+        Param::Constant,
+        Param::Cost,
+        Param::Budget,
+        Param::Target,
+        Param::Pity,
+        Param::Count,
+        Param::Reference,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Probability => "probability",
+            Self::Constant => "constant",
+            Self::Cost => "cost",
+            Self::Budget => "budget",
+            Self::Target => "target",
+            Self::Pity => "pity",
+            Self::Count => "count",
+            Self::Reference => "reference",
+        }
+    }
+
+    /// Amount of an adjustment, none if a parameter is not a number
+    fn step(&self) -> Option<f64> {
+        match self {
+            Self::Probability | Self::Constant => Some(0.001),
+            Self::Cost | Self::Pity | Self::Count => Some(10.0),
+            Self::Budget => Some(100.0),
+            Self::Target => Some(0.05),
+            Self::Reference => None,
+        }
+    }
+}
+
+/// What an input line edits
+#[derive(Clone, Copy)]
+enum Input {
+    Param(Param),
+    Csv,
+    Option,
+}
+
+/// Full screen session with a parameter panel, a table and a terminal chart
+///
+/// Every change of a parameter is applied to a calculator and records are calculated again.
+/// Conditional calculation is used when budget or target is given, otherwise range of count.
+pub(crate) struct Tui {
+    calculator: Calculator,
+    values: Vec<String>,
+    selected: usize,
+    input: Option<(Input, String)>,
+    count: usize,
+    records: Vec<Record>,
+    scroll: usize,
+    status: String,
+}
+
+impl Tui {
+    pub fn new(calculator: Calculator) -> GcalcResult<Self> {
+        if let CsvRef::Raw(_) = calculator.reference() {
+            return Err(GcalcError::InvalidArgument(
+                "Tui cannot be used with a raw reference".to_owned(),
+            ));
+        }
+        let summary = calculator.input_summary("range");
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        let count = summary
+            .count
+            .filter(|&count| count > 0)
+            .unwrap_or(DEFAULT_COUNT);
+        let values = Param::ALL
+            .iter()
+            .map(|param| match param {
+                Param::Probability => summary.probability.to_string(),
+                Param::Constant => summary.constant.to_string(),
+                Param::Cost => summary.cost.to_string(),
+                Param::Budget => optional(summary.budget.map(|v| v.to_string())),
+                Param::Target => optional(summary.target.map(|v| v.to_string())),
+                Param::Pity => optional(summary.pity.map(|v| v.to_string())),
+                Param::Count => count.to_string(),
+                Param::Reference => optional(
+                    calculator
+                        .reference_file()
+                        .map(|path| path.display().to_string()),
+                ),
+            })
+            .collect();
+
+        Ok(Self {
+            calculator,
+            values,
+            selected: 0,
+            input: None,
+            count,
+            records: vec![],
+            scroll: 0,
+            status: String::new(),
+        })
+    }
+
+    /// Run until quit, where a terminal is restored even if drawing fails
+    pub fn run(&mut self) -> GcalcResult<()> {
+        if !std::io::stdout().is_terminal() {
+            return Err(GcalcError::InvalidArgument(
+                "Tui requires stdout to be a terminal".to_owned(),
+            ));
+        }
+        let _screen = Screen::enter()?;
+        self.calculate();
+        loop {
+            self.draw()?;
+            if let Event::Key(key) = event::read()? {
+                // Release of a key is also an event on some platforms
+                if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a key, false if a session should end
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        // Raw mode doesn't raise a signal for Ctrl+C, and other control keys aren't commands
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return key.code != KeyCode::Char('c');
+        }
+        let code = key.code;
+        if let Some((input, mut line)) = self.input.take() {
+            match code {
+                KeyCode::Enter => self.submit(input, &line),
+                KeyCode::Esc => (),
+                KeyCode::Backspace => {
+                    line.pop();
+                    self.input.replace((input, line));
+                }
+                KeyCode::Char(c) => {
+                    line.push(c);
+                    self.input.replace((input, line));
+                }
+                _ => {
+                    self.input.replace((input, line));
+                }
+            }
+            return true;
+        }
+
+        let param = Param::ALL[self.selected];
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self.selected.checked_sub(1).unwrap_or(Param::ALL.len() - 1)
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1) % Param::ALL.len()
+            }
+            KeyCode::Enter => {
+                self.input
+                    .replace((Input::Param(param), self.values[self.selected].clone()));
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Right => self.adjust(param, 1.0),
+            KeyCode::Char('-') | KeyCode::Left => self.adjust(param, -1.0),
+            KeyCode::PageDown => {
+                self.scroll = (self.scroll + 10).min(self.records.len().saturating_sub(1))
+            }
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::Char('c') => {
+                self.input
+                    .replace((Input::Csv, DEFAULT_CSV_PATH.to_owned()));
+            }
+            KeyCode::Char('o') => {
+                self.input
+                    .replace((Input::Option, DEFAULT_OPTION_PATH.to_owned()));
+            }
+            _ => (),
+        }
+        true
+    }
+
+    fn submit(&mut self, input: Input, line: &str) {
+        let result = match input {
+            Input::Param(param) => self.set(param, line),
+            Input::Csv => self.export_csv(line),
+            Input::Option => self
+                .calculator
+                .to_option()
+                .to_json()
+                .and_then(|json| Ok(std::fs::write(line, json)?))
+                .map(|_| format!("Saved option file \"{}\"", line)),
+        };
+        match result {
+            Ok(status) => self.status = status,
+            Err(err) => self.status = Self::error_status(&err),
+        }
+    }
+
+    /// Increase or decrease a number parameter by its step
+    fn adjust(&mut self, param: Param, direction: f64) {
+        let step = match param.step() {
+            Some(step) => step,
+            None => return,
+        };
+        let text = &self.values[self.selected];
+        let current = match param {
+            Param::Probability | Param::Constant => self
+                .calculator
+                .parse_probability(text)
+                .map(|value| value as f64),
+            _ if text.is_empty() => Ok(0.0),
+            _ => text
+                .parse::<f64>()
+                .map_err(|_| GcalcError::ParseError(format!("\"{}\" is not a number", text))),
+        };
+        let result = current.and_then(|current| {
+            let value = (current + step * direction).max(0.0);
+            let value = match param {
+                Param::Probability | Param::Constant | Param::Target => value.min(1.0),
+                _ => value,
+            };
+            // Rounded to a step, which removes a floating point error of a sum
+            let text = format!("{:.6}", value)
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_owned();
+            // Plain 1 is an ambiguous probability in strict mode
+            let text = match param {
+                Param::Probability | Param::Constant if value >= 1.0 => "100%".to_owned(),
+                _ => text,
+            };
+            self.set(param, &text)
+        });
+        if let Err(err) = result {
+            self.status = Self::error_status(&err);
+        }
+    }
+
+    /// Apply a parameter to a calculator and calculate again
+    ///
+    /// Empty text unsets an optional parameter. Previous text is kept if text is invalid.
+    fn set(&mut self, param: Param, text: &str) -> GcalcResult<String> {
+        let text = text.trim();
+        let cal = &mut self.calculator;
+        let parse_error =
+            || GcalcError::ParseError(format!("Invalid {} \"{}\"", param.name(), text));
+        match param {
+            Param::Probability => {
+                let probability = cal.parse_probability(text)?;
+                cal.set_probability(probability, true)?;
+            }
+            Param::Constant => {
+                let constant = cal.parse_probability(text)?;
+                cal.set_constant(constant, true)?;
+            }
+            Param::Cost => cal.set_cost(text.parse().map_err(|_| parse_error())?, true),
+            Param::Budget if text.is_empty() => cal.clear_budget(),
+            Param::Budget => cal.set_budget(text.parse().map_err(|_| parse_error())?),
+            Param::Target if text.is_empty() => cal.clear_target_probability(),
+            Param::Target => {
+                cal.set_target_probability(text.parse().map_err(|_| parse_error())?)?
+            }
+            Param::Pity if text.is_empty() => cal.clear_pity(),
+            Param::Pity => cal.set_pity(text.parse().map_err(|_| parse_error())?),
+            Param::Count => self.count = text.parse().map_err(|_| parse_error())?,
+            Param::Reference if text.is_empty() => cal.set_csv_file(CsvRef::None),
+            Param::Reference => {
+                if !Path::new(text).is_file() {
+                    return Err(GcalcError::InvalidArgument(format!(
+                        "Reference file \"{}\" doesn't exist",
+                        text
+                    )));
+                }
+                cal.set_csv_file(CsvRef::File(PathBuf::from(text)));
+            }
+        }
+        let index = Param::ALL
+            .iter()
+            .position(|p| *p == param)
+            .unwrap_or_default();
+        self.values[index] = text.to_owned();
+        self.calculate();
+        Ok(self.status.clone())
+    }
+
+    /// Calculate records, where a failure is shown as a status and previous records are kept
+    fn calculate(&mut self) {
+        let conditional = self.is_conditional();
+        let records = if conditional {
+            self.calculator.conditional_records()
+        } else {
+            self.calculator.range_records(Some(self.count))
+        };
+        match records {
+            Ok(records) => {
+                self.status = format!(
+                    "{} record(s) of {} calculation",
+                    records.len(),
+                    if conditional { "conditional" } else { "range" }
+                );
+                self.records = records;
+                self.scroll = self.scroll.min(self.records.len().saturating_sub(1));
+            }
+            Err(err) => self.status = Self::error_status(&err),
+        }
+    }
+
+    fn is_conditional(&self) -> bool {
+        Param::ALL.iter().zip(&self.values).any(|(param, value)| {
+            matches!(param, Param::Budget | Param::Target) && !value.is_empty()
+        })
+    }
+
+    fn export_csv(&self, path: &str) -> GcalcResult<String> {
+        let columns = self.calculator.record_columns()?;
+        let csv = RecordFormatter::to_raw_csv(&self.records, None, &columns)
+            .map_err(GcalcError::FormatFail)?;
+        std::fs::write(path, csv)?;
+        Ok(format!(
+            "Exported {} record(s) to \"{}\"",
+            self.records.len(),
+            path
+        ))
+    }
+
+    /// Error as a single line
+    fn error_status(err: &GcalcError) -> String {
+        err.to_string().replace('\n', " ")
+    }
+
+    fn draw(&self) -> GcalcResult<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        let view_width = width.saturating_sub(PANEL_WIDTH + 1);
+        // The last row is a status line
+        let view_height = height.saturating_sub(1);
+
+        let mut stdout = std::io::stdout();
+        queue!(stdout, Clear(ClearType::All))?;
+
+        // Parameter panel
+        queue!(
+            stdout,
+            MoveTo(0, 0),
+            Print(Self::fit("Parameters", PANEL_WIDTH))
+        )?;
+        for (index, param) in Param::ALL.iter().enumerate() {
+            let line = format!(" {:<12}{}", param.name(), self.values[index]);
+            let attribute = if index == self.selected {
+                Attribute::Reverse
+            } else {
+                Attribute::Reset
+            };
+            queue!(
+                stdout,
+                MoveTo(0, index as u16 + 2),
+                SetAttribute(attribute),
+                Print(Self::fit(&line, PANEL_WIDTH)),
+                SetAttribute(Attribute::Reset)
+            )?;
+        }
+        let mode = if self.is_conditional() {
+            "conditional"
+        } else {
+            "range"
+        };
+        let lines = std::iter::once(format!(" mode        {}", mode))
+            .chain(std::iter::once(String::new()))
+            .chain(HELP.iter().map(|line| format!(" {}", line)));
+        for (row, line) in (Param::ALL.len() + 3..view_height).zip(lines) {
+            queue!(
+                stdout,
+                MoveTo(0, row as u16),
+                Print(Self::fit(&line, PANEL_WIDTH))
+            )?;
+        }
+
+        // Table and chart
+        let x = (PANEL_WIDTH + 1) as u16;
+        let chart = if self.records.is_empty() || view_width == 0 {
+            vec![]
+        } else {
+            let colored = std::env::var_os("NO_COLOR").is_none();
+            let chart = TermRenderer::render_with(
+                self.calculator.chart_attribute(),
+                &self.records,
+                view_width,
+                colored,
+            )?;
+            chart.lines().map(|line| line.to_owned()).collect()
+        };
+        let chart_fits = view_height > chart.len() + MIN_TABLE_ROWS;
+        let table_height = if chart_fits {
+            view_height - chart.len() - 1
+        } else {
+            view_height
+        };
+        for (row, line) in self.table(table_height).iter().enumerate() {
+            queue!(
+                stdout,
+                MoveTo(x, row as u16),
+                Print(Self::fit(line, view_width))
+            )?;
+        }
+        if chart_fits {
+            for (row, line) in chart.iter().enumerate() {
+                queue!(
+                    stdout,
+                    MoveTo(x, (table_height + 1 + row) as u16),
+                    Print(line)
+                )?;
+            }
+        }
+
+        // Status or input line
+        let status = match &self.input {
+            Some((input, line)) => {
+                let prompt = match input {
+                    Input::Param(param) => param.name(),
+                    Input::Csv => "csv path",
+                    Input::Option => "option path",
+                };
+                format!("{}> {}_", prompt, line)
+            }
+            None => self.status.clone(),
+        };
+        queue!(
+            stdout,
+            MoveTo(0, view_height as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(Self::fit(&status, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    /// Rows of a table from a scroll position, including a header
+    fn table(&self, height: usize) -> Vec<String> {
+        let columns = match self.calculator.record_columns() {
+            Ok(columns) => columns,
+            Err(err) => return vec![Self::error_status(&err)],
+        };
+        let records = self
+            .records
+            .iter()
+            .skip(self.scroll)
+            .take(height.saturating_sub(1));
+        let rows: Vec<Vec<String>> = std::iter::once(columns.iter().map(|c| c.header()).collect())
+            .chain(records.map(|record| {
+                columns
+                    .iter()
+                    .map(|column| record.display_cell(column.kind))
+                    .collect()
+            }))
+            .collect();
+        let widths: Vec<usize> = (0..columns.len())
+            .map(|index| {
+                rows.iter()
+                    .map(|row| row[index].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        rows.iter()
+            .map(|row| {
+                row.iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+            })
+            .collect()
+    }
+
+    /// Pad or truncate text into a width
+    fn fit(text: &str, width: usize) -> String {
+        let text: String = text.chars().take(width).collect();
+        format!("{:<width$}", text, width = width)
+    }
+}
+
+/// Alternate screen in raw mode, which is restored when dropped
+struct Screen;
+
+impl Screen {
+    fn enter() -> GcalcResult<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(std::io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        // Nothing can be done if a terminal cannot be restored
+        let _ = execute!(std::io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tui(calculator: Calculator) -> Tui {
+        let mut tui = Tui::new(calculator).unwrap();
+        tui.calculate();
+        tui
+    }
+
+    fn press(tui: &mut Tui, code: KeyCode) -> bool {
+        tui.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn value(tui: &Tui, param: Param) -> &str {
+        let index = Param::ALL.iter().position(|p| *p == param).unwrap();
+        &tui.values[index]
+    }
+
+    fn select(tui: &mut Tui, param: Param) {
+        tui.selected = Param::ALL.iter().position(|p| *p == param).unwrap();
+    }
+
+    #[test]
+    fn raw_reference_is_rejected() {
+        let calculator = Calculator::new()
+            .unwrap()
+            .csv_ref(CsvRef::Raw("count,prob\n1,0.1".to_owned()));
+        assert!(Tui::new(calculator).is_err());
+
+        let calculator = Calculator::new()
+            .unwrap()
+            .csv_ref(CsvRef::File(PathBuf::from("ref.csv")));
+        let tui = Tui::new(calculator).unwrap();
+        assert_eq!(value(&tui, Param::Reference), "ref.csv");
+        assert_eq!(value(&tui, Param::Count), DEFAULT_COUNT.to_string());
+    }
+
+    #[test]
+    fn budget_or_target_makes_calculation_conditional() {
+        let mut tui = tui(Calculator::new().unwrap().probability(0.5).unwrap());
+        assert!(!tui.is_conditional());
+        assert_eq!(tui.records.len(), DEFAULT_COUNT);
+
+        tui.set(Param::Target, "0.9").unwrap();
+        assert!(tui.is_conditional());
+        assert_eq!(tui.records.len(), 4);
+
+        tui.set(Param::Target, "").unwrap();
+        tui.set(Param::Pity, "3").unwrap();
+        assert!(!tui.is_conditional());
+        tui.set(Param::Cost, "10").unwrap();
+        tui.set(Param::Budget, "20").unwrap();
+        assert!(tui.is_conditional());
+        assert_eq!(tui.records.last().unwrap().cost, 30.0);
+    }
+
+    #[test]
+    fn invalid_value_keeps_previous_one() {
+        let mut tui = tui(Calculator::new().unwrap().probability(0.5).unwrap());
+        assert!(tui.set(Param::Pity, "soon").is_err());
+        assert!(tui.set(Param::Reference, "no-such-file.csv").is_err());
+        assert!(tui.set(Param::Probability, "200%").is_err());
+        assert_eq!(value(&tui, Param::Pity), "");
+        assert_eq!(value(&tui, Param::Probability), "0.5");
+    }
+
+    #[test]
+    fn adjustment_steps_within_bounds() {
+        let mut tui = tui(Calculator::new().unwrap().probability(0.5).unwrap());
+        select(&mut tui, Param::Probability);
+        tui.adjust(Param::Probability, 1.0);
+        assert_eq!(value(&tui, Param::Probability), "0.501");
+        tui.adjust(Param::Probability, -1.0);
+        tui.adjust(Param::Probability, -1.0);
+        assert_eq!(value(&tui, Param::Probability), "0.499");
+
+        // Empty optional value starts from 0 and doesn't go below it
+        select(&mut tui, Param::Budget);
+        tui.adjust(Param::Budget, -1.0);
+        assert_eq!(value(&tui, Param::Budget), "0");
+        tui.adjust(Param::Budget, 1.0);
+        assert_eq!(value(&tui, Param::Budget), "100");
+
+        // Reference is not a number
+        select(&mut tui, Param::Reference);
+        tui.adjust(Param::Reference, 1.0);
+        assert_eq!(value(&tui, Param::Reference), "");
+    }
+
+    #[test]
+    fn certain_probability_is_explicit_in_strict_mode() {
+        let calculator = Calculator::new()
+            .unwrap()
+            .strict_prob(true)
+            .probability(0.9995)
+            .unwrap();
+        let mut tui = tui(calculator);
+        select(&mut tui, Param::Probability);
+        tui.adjust(Param::Probability, 1.0);
+        assert_eq!(value(&tui, Param::Probability), "100%");
+        assert_eq!(tui.records[0].probability_src, 1.0);
+        // Adjusted again from an explicit notation
+        tui.adjust(Param::Probability, -1.0);
+        assert_eq!(value(&tui, Param::Probability), "0.999");
+    }
+
+    #[test]
+    fn keys_select_edit_and_quit() {
+        let mut tui = tui(Calculator::new().unwrap().probability(0.5).unwrap());
+        press(&mut tui, KeyCode::Up);
+        assert_eq!(tui.selected, Param::ALL.len() - 1);
+        press(&mut tui, KeyCode::Char('j'));
+        press(&mut tui, KeyCode::Down);
+        assert_eq!(tui.selected, 1);
+
+        // Edit pity with a line
+        select(&mut tui, Param::Pity);
+        press(&mut tui, KeyCode::Enter);
+        for c in "30".chars() {
+            press(&mut tui, KeyCode::Char(c));
+        }
+        press(&mut tui, KeyCode::Backspace);
+        press(&mut tui, KeyCode::Char('5'));
+        // Keys are a part of a line while editing
+        assert!(press(&mut tui, KeyCode::Char('q')));
+        press(&mut tui, KeyCode::Backspace);
+        press(&mut tui, KeyCode::Enter);
+        assert!(tui.input.is_none());
+        assert_eq!(value(&tui, Param::Pity), "35");
+
+        // Escape cancels an edit
+        press(&mut tui, KeyCode::Enter);
+        press(&mut tui, KeyCode::Char('9'));
+        press(&mut tui, KeyCode::Esc);
+        assert_eq!(value(&tui, Param::Pity), "35");
+
+        press(&mut tui, KeyCode::Char('+'));
+        assert_eq!(value(&tui, Param::Pity), "45");
+
+        press(&mut tui, KeyCode::PageDown);
+        assert_eq!(tui.scroll, 10);
+        press(&mut tui, KeyCode::PageUp);
+        press(&mut tui, KeyCode::PageUp);
+        assert_eq!(tui.scroll, 0);
+
+        assert!(tui.handle_key(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL)));
+        assert!(!tui.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)));
+        assert!(!press(&mut tui, KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn invalid_edit_is_a_status() {
+        let mut tui = tui(Calculator::new().unwrap().probability(0.5).unwrap());
+        select(&mut tui, Param::Count);
+        press(&mut tui, KeyCode::Enter);
+        press(&mut tui, KeyCode::Char('x'));
+        press(&mut tui, KeyCode::Enter);
+        assert!(tui.status.contains("Invalid count"), "{}", tui.status);
+        assert_eq!(value(&tui, Param::Count), DEFAULT_COUNT.to_string());
+    }
+}
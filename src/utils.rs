@@ -1,4 +1,4 @@
-use crate::models::ProbType;
+use crate::models::{NumberFormat, ProbType, RoundingMode};
 use crate::{GcalcError, GcalcResult};
 
 /// Calculate geometric series of given inputs
@@ -20,25 +20,71 @@ pub fn geometric_series_qual(probability: f32, qalification: f32) -> usize {
     count_similar.ceil() as usize
 }
 
-/// Get probability as lenient as possible
-pub fn get_prob_alap(number_str: &str, suffix: Option<&str>) -> GcalcResult<f32> {
-    let mut number = number_str.to_owned();
-
-    // Remove general suffix
-    if number.ends_with('%') {
-        number.pop();
-    }
-
-    // Remove custom suffix
-    if let Some(suffix) = suffix {
-        if number.ends_with(suffix) {
-            number = number.trim_end_matches(suffix).to_owned();
+/// Parse probability with an explicit notation
+///
+/// Supported notations are
+/// - Fraction     : "1/200"
+/// - Odds         : "1 in 300"
+/// - Percent      : "0.5%"
+/// - Permille     : "5‰" or "5permille"
+/// - Basis points : "50bp" or "50bps"
+/// - Bare number  : "0.005"
+///
+/// Bare number is interpreted by [get_number_as_fraction], which reads numbers from 1 to 100 as
+/// percentage. Strict mode rejects such ambiguous bare numbers.
+pub fn parse_probability(text: &str, strict: bool) -> GcalcResult<f32> {
+    let source = text.trim();
+    let text = source.to_lowercase();
+    let number = |num: &str| {
+        num.trim().parse::<f32>().map_err(|_| {
+            GcalcError::ParseError(format!(
+                "\"{}\" is not a valid probability notation",
+                source
+            ))
+        })
+    };
+    let ratio = |num: &str, den: &str| {
+        let (num, den) = (number(num)?, number(den)?);
+        if den <= 0.0 {
+            return Err(GcalcError::InvalidProb(format!(
+                "Denominator of \"{}\" should be a positive number",
+                source
+            )));
         }
-    }
+        Ok(num / den)
+    };
 
-    let number = number.parse::<f32>()?;
+    let prob = if let Some((num, den)) = text.split_once('/') {
+        ratio(num, den)?
+    } else if let Some((num, den)) = text.split_once(" in ") {
+        ratio(num, den)?
+    } else if let Some(num) = text.strip_suffix('%') {
+        number(num)? / 100.0
+    } else if let Some(num) = text
+        .strip_suffix('‰')
+        .or_else(|| text.strip_suffix("permille"))
+    {
+        number(num)? / 1000.0
+    } else if let Some(num) = text.strip_suffix("bps").or_else(|| text.strip_suffix("bp")) {
+        number(num)? / 10000.0
+    } else {
+        let num = number(&text)?;
+        if strict && num >= 1.0 {
+            return Err(GcalcError::InvalidProb(format!(
+                "Probability \"{}\" is ambiguous, use explicit notation such as \"{}%\"",
+                source, num
+            )));
+        }
+        return get_number_as_fraction(num);
+    };
 
-    get_number_as_fraction(number)
+    if !(0.0..=1.0).contains(&prob) {
+        return Err(GcalcError::InvalidProb(format!(
+            "Probability \"{}\" should be within 0% ~ 100%",
+            source
+        )));
+    }
+    Ok(prob)
 }
 
 pub fn get_number_as_fraction(number: f32) -> GcalcResult<f32> {
@@ -70,35 +116,251 @@ pub fn get_number_as_fraction(number: f32) -> GcalcResult<f32> {
 /// Available types are
 /// - Percentage
 /// - Floating number
-pub fn get_prob_as_formatted(
-    mut num: f32,
-    prob_type: &ProbType,
-    precision: &Option<usize>,
-) -> String {
-    let mut num_string: String;
-
-    // Add percentage
+pub fn get_prob_as_formatted(num: f32, prob_type: &ProbType, precision: &Option<usize>) -> String {
+    let format = NumberFormat {
+        precision: *precision,
+        ..NumberFormat::default()
+    };
+    format_probability(num, prob_type, &format)
+}
+
+/// Get probability according to given type and number format
+///
+/// Percentage is scaled on decimal digits, so that rounding is not affected by a
+/// multiplication error of floating numbers.
+pub fn format_probability(num: f32, prob_type: &ProbType, format: &NumberFormat) -> String {
     if let ProbType::Percentage = prob_type {
-        num *= 100f32; // multiply by 100
-        num_string = float_to_string(num, precision);
+        let mut num_string = match format.precision {
+            Some(_) => format_decimal(num, 2, format),
+            None => (num * 100f32).to_string(),
+        };
         num_string.push('%');
+        num_string
     } else {
-        num_string = float_to_string(num, precision);
+        format_number(num, format)
     }
+}
 
-    num_string
+/// Convert floating number to string with a number format
+///
+/// Number is printed as it is when precision is not given.
+pub fn format_number(num: f32, format: &NumberFormat) -> String {
+    match format.precision {
+        Some(_) => format_decimal(num, 0, format),
+        None => num.to_string(),
+    }
 }
 
-/// Convert floating number to string
+/// Format a number multiplied by 10^shift with a precision and rounding mode
 ///
-/// This doesn't simply use a single format macro
-/// because format macro varies according to exponents of given number.
-pub fn float_to_string(num: f32, precision: &Option<usize>) -> String {
-    if let Some(precision) = precision {
-        let decimal_precision = 10.0f32.powi(*precision as i32);
-        let converted = f32::trunc(num * decimal_precision) / decimal_precision;
-        format!("{:.1$}", converted, precision)
+/// Rounding is done on the shortest decimal representation of a number, thus 0.29 is not
+/// truncated into 0.28 and 0.5999 is rounded into 0.60 as it is written.
+fn format_decimal(num: f32, shift: i32, format: &NumberFormat) -> String {
+    if !num.is_finite() {
+        return (num * 10f32.powi(shift)).to_string();
+    }
+    let negative = num.is_sign_negative();
+
+    // e.g. "4.2e-4" into digits "42" and exponent -4
+    let scientific = format!("{:e}", num.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let digits: Vec<u8> = mantissa
+        .bytes()
+        .filter(u8::is_ascii_digit)
+        .map(|digit| digit - b'0')
+        .collect();
+    let exponent = exponent.parse::<i32>().unwrap_or(0) + shift;
+    let is_zero = digits.iter().all(|&digit| digit == 0);
+
+    let mut decimals = format.precision.unwrap_or(0);
+    if let Some(significant) = format.significant {
+        if !is_zero {
+            let required = significant as i32 - 1 - exponent;
+            decimals = decimals.max(required.max(0) as usize);
+        }
+    }
+
+    // Digits before the decimal point plus decimals
+    let keep = exponent + 1 + decimals as i32;
+    let mut kept: Vec<u8> = (0..keep.max(0) as usize)
+        .map(|index| digits.get(index).copied().unwrap_or(0))
+        .collect();
+    let (first_rest, rest_nonzero) = if keep < 0 {
+        (0, !is_zero)
     } else {
-        num.to_string()
+        let rest = digits.get(keep as usize..).unwrap_or(&[]);
+        (
+            rest.first().copied().unwrap_or(0),
+            rest.iter().any(|&digit| digit != 0),
+        )
+    };
+
+    let round_up = match format.rounding {
+        RoundingMode::Truncate => false,
+        RoundingMode::HalfUp => first_rest >= 5,
+        RoundingMode::HalfEven => {
+            let beyond_half = digits
+                .get(keep.max(0) as usize + 1..)
+                .is_some_and(|rest| rest.iter().any(|&digit| digit != 0));
+            let odd = kept.last().is_some_and(|digit| digit % 2 == 1);
+            keep >= 0 && (first_rest > 5 || (first_rest == 5 && (beyond_half || odd)))
+        }
+        RoundingMode::Ceil => !negative && rest_nonzero,
+        RoundingMode::Floor => negative && rest_nonzero,
+    };
+
+    if round_up {
+        let mut carry = true;
+        for digit in kept.iter_mut().rev() {
+            if *digit == 9 {
+                *digit = 0;
+            } else {
+                *digit += 1;
+                carry = false;
+                break;
+            }
+        }
+        if carry {
+            kept.insert(0, 1);
+        }
+    }
+
+    // Pad leading zeros so that at least one digit is before the decimal point
+    while kept.len() <= decimals {
+        kept.insert(0, 0);
+    }
+    let point = kept.len() - decimals;
+    let mut text: String = kept[..point]
+        .iter()
+        .map(|digit| char::from(b'0' + digit))
+        .collect();
+    if decimals > 0 {
+        text.push('.');
+        text.extend(kept[point..].iter().map(|digit| char::from(b'0' + digit)));
+    }
+
+    if negative && kept.iter().any(|&digit| digit != 0) {
+        text.insert(0, '-');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn parse_probability_reads_explicit_notations() {
+        for (text, expected) in [
+            ("1/200", 0.005),
+            (" 1 / 4 ", 0.25),
+            ("1 in 300", 1.0 / 300.0),
+            ("0.5%", 0.005),
+            ("5‰", 0.005),
+            ("5 permille", 0.005),
+            ("50bp", 0.005),
+            ("50BPS", 0.005),
+            ("0.005", 0.005),
+        ] {
+            assert_close(parse_probability(text, true).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn parse_probability_reads_bare_numbers_as_percentage_unless_strict() {
+        assert_close(parse_probability("1.5", false).unwrap(), 0.015);
+        assert_close(parse_probability("1", false).unwrap(), 1.0);
+        assert_close(parse_probability("0.99", true).unwrap(), 0.99);
+        for text in ["1", "1.5"] {
+            assert!(matches!(
+                parse_probability(text, true),
+                Err(GcalcError::InvalidProb(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn parse_probability_rejects_invalid_values() {
+        for text in ["1/0", "1/-2", "150%", "3/2", "1000"] {
+            assert!(
+                matches!(
+                    parse_probability(text, false),
+                    Err(GcalcError::InvalidProb(_))
+                ),
+                "{}",
+                text
+            );
+        }
+        for text in ["abc", "1/x", "%", ""] {
+            assert!(
+                matches!(
+                    parse_probability(text, false),
+                    Err(GcalcError::ParseError(_))
+                ),
+                "{}",
+                text
+            );
+        }
+    }
+
+    fn decimal(num: f32, precision: usize, rounding: RoundingMode) -> String {
+        let format = NumberFormat {
+            precision: Some(precision),
+            rounding,
+            significant: None,
+        };
+        format_decimal(num, 0, &format)
+    }
+
+    #[test]
+    fn format_decimal_rounds_written_digits() {
+        for (num, expected) in [(0.29, "0.29"), (0.5999, "0.60"), (9.995, "10.00")] {
+            assert_eq!(decimal(num, 2, RoundingMode::HalfUp), expected);
+        }
+        assert_eq!(decimal(0.29, 2, RoundingMode::Truncate), "0.29");
+    }
+
+    #[test]
+    fn format_decimal_applies_rounding_modes() {
+        use RoundingMode::*;
+        for (num, mode, expected) in [
+            (0.129, Truncate, "0.12"),
+            (-0.129, Truncate, "-0.12"),
+            (0.125, HalfUp, "0.13"),
+            (0.124, HalfUp, "0.12"),
+            (0.125, HalfEven, "0.12"),
+            (0.135, HalfEven, "0.14"),
+            (0.1251, HalfEven, "0.13"),
+            (0.121, Ceil, "0.13"),
+            (-0.121, Ceil, "-0.12"),
+            (0.129, Floor, "0.12"),
+            (-0.121, Floor, "-0.13"),
+            (-0.001, HalfUp, "0.00"),
+        ] {
+            assert_eq!(decimal(num, 2, mode), expected, "{} {:?}", num, mode);
+        }
+    }
+
+    #[test]
+    fn format_decimal_keeps_significant_digits_and_shift() {
+        let format = NumberFormat {
+            precision: Some(2),
+            rounding: RoundingMode::HalfUp,
+            significant: Some(3),
+        };
+        assert_eq!(format_decimal(0.000123456, 0, &format), "0.000123");
+        assert_eq!(format_decimal(12.3456, 0, &format), "12.35");
+        assert_eq!(format_decimal(0.0, 0, &format), "0.00");
+        assert_eq!(format_decimal(0.001234, 2, &format), "0.123");
+        assert_eq!(format_decimal(f32::INFINITY, 2, &format), "inf");
     }
 }
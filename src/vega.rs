@@ -0,0 +1,748 @@
+use serde_json::{json, Value};
+
+use crate::models::{Annotation, GcalcResult, PlotAttribute, PlotKind, Record};
+use crate::sweep::{SweepGrid, SweepMetric};
+use crate::GcalcError;
+
+const SCHEMA: &str = "https://vega.github.io/schema/vega-lite/v5.json";
+
+/// Vega-Lite specification of charts
+///
+/// Specification has the same series and annotations as an image with data embedded, so that
+/// notebooks and dashboards can render an interactive chart. Width and height follow an image
+/// size and scales can be zoomed and panned.
+pub(crate) struct VegaRenderer;
+
+impl VegaRenderer {
+    /// Write specification into a file of a path from given attribute
+    pub fn write(attr: &PlotAttribute, spec: &Value) -> GcalcResult<()> {
+        std::fs::write(&attr.path, Self::render(spec)?)?;
+        Ok(())
+    }
+
+    /// Specification as a json document
+    pub fn render(spec: &Value) -> GcalcResult<String> {
+        serde_json::to_string_pretty(spec).map_err(|err| GcalcError::FormatFail(Box::new(err)))
+    }
+
+    /// Specification of a chart of records, which follows a plot kind
+    pub fn records(
+        attr: &PlotAttribute,
+        data: &[Record],
+        annotation: &Annotation,
+    ) -> GcalcResult<Value> {
+        match attr.kind {
+            PlotKind::Cumulative => Self::cumulative(attr, data, annotation),
+            PlotKind::Histogram => Self::histogram(attr, data, annotation),
+            PlotKind::Survival => Self::survival(attr, data, annotation),
+        }
+    }
+
+    /// Specification of probability lines of named series with a legend
+    #[cfg(feature = "plotters")]
+    pub fn overlay(attr: &PlotAttribute, series: &[(String, Vec<Record>)]) -> GcalcResult<Value> {
+        let values = series
+            .iter()
+            .flat_map(|(name, data)| {
+                data.iter().map(move |record| {
+                    json!({
+                        "series": name,
+                        "count": record.count,
+                        "probability": number(record.probability_src),
+                    })
+                })
+            })
+            .collect();
+
+        let mut spec = Self::base(attr, values)?;
+        spec["mark"] = json!({ "type": "line", "clip": true });
+        spec["params"] = json!([zoom()]);
+        spec["encoding"] = json!({
+            "x": x_encoding(attr),
+            "y": y_encoding(attr, "probability", &attr.prob_caption, false),
+            "color": {
+                "field": "series",
+                "type": "nominal",
+                "legend": { "orient": "bottom-right" },
+            },
+            "tooltip": [
+                { "field": "series", "type": "nominal" },
+                { "field": "count", "type": "quantitative" },
+                { "field": "probability", "type": "quantitative" },
+            ],
+        });
+        Ok(spec)
+    }
+
+    /// Specification of a heatmap where colours range from cost colour to probability colour
+    pub fn heatmap(attr: &PlotAttribute, grid: &SweepGrid) -> GcalcResult<Value> {
+        let values = grid
+            .y
+            .values
+            .iter()
+            .zip(grid.cells.iter())
+            .flat_map(|(&y, row)| {
+                grid.x
+                    .values
+                    .iter()
+                    .zip(row.iter())
+                    .map(move |(&x, &value)| {
+                        // Cells without a value are left empty
+                        let value = if value.is_finite() {
+                            json!(number(value))
+                        } else {
+                            Value::Null
+                        };
+                        json!({ "x": number(x), "y": number(y), "value": value })
+                    })
+            })
+            .collect();
+        let format = match grid.metric {
+            SweepMetric::Success => ".3f",
+            SweepMetric::Tries => "d",
+        };
+
+        let mut layers = vec![json!({
+            "mark": "rect",
+            "encoding": {
+                "color": {
+                    "field": "value",
+                    "type": "quantitative",
+                    "title": grid.metric.name(),
+                    "scale": { "range": [hex(&attr.cost_color)?, hex(&attr.prob_color)?] },
+                },
+                "tooltip": [
+                    { "field": "x", "type": "ordinal", "title": grid.x.parameter.name() },
+                    { "field": "y", "type": "ordinal", "title": grid.y.parameter.name() },
+                    {
+                        "field": "value",
+                        "type": "quantitative",
+                        "title": grid.metric.name(),
+                        "format": format,
+                    },
+                ],
+            },
+        })];
+        // Labels would overlap each other in a large grid
+        if grid.x.values.len() * grid.y.values.len() <= 100 {
+            layers.push(json!({
+                "mark": { "type": "text", "color": "black" },
+                "encoding": {
+                    "text": { "field": "value", "type": "quantitative", "format": format },
+                },
+            }));
+        }
+
+        let mut spec = Self::base(attr, values)?;
+        spec["encoding"] = json!({
+            "x": { "field": "x", "type": "ordinal", "title": grid.x.parameter.name() },
+            "y": {
+                "field": "y",
+                "type": "ordinal",
+                "title": grid.y.parameter.name(),
+                "sort": "descending",
+            },
+        });
+        spec["layer"] = json!(layers);
+        Ok(spec)
+    }
+
+    /// Cumulative probability line with cost line on a secondary axis
+    fn cumulative(
+        attr: &PlotAttribute,
+        data: &[Record],
+        annotation: &Annotation,
+    ) -> GcalcResult<Value> {
+        let values = data
+            .iter()
+            .map(|record| {
+                json!({
+                    "count": record.count,
+                    "probability": number(record.probability_src),
+                    "cost": number(record.cost),
+                })
+            })
+            .collect();
+        let y = y_encoding(attr, "probability", &attr.prob_caption, false);
+
+        let mut probability = vec![json!({
+            "mark": {
+                "type": "line",
+                "point": true,
+                "color": hex(&attr.prob_color)?,
+                "clip": true,
+            },
+            "params": [zoom()],
+            "encoding": {
+                "x": x_encoding(attr),
+                "y": y,
+                "tooltip": tooltip(&["count", "probability", "cost"]),
+            },
+        })];
+        probability.extend(Self::annotations(
+            attr,
+            data,
+            annotation,
+            &y,
+            |probability| Some(number(probability)),
+        )?);
+
+        let mut spec = Self::base(attr, values)?;
+        spec["layer"] = json!([
+            { "layer": probability },
+            {
+                "mark": {
+                    "type": "line",
+                    "point": true,
+                    "color": hex(&attr.cost_color)?,
+                    "opacity": 0.3,
+                    "clip": true,
+                },
+                "encoding": {
+                    "x": x_encoding(attr),
+                    "y": {
+                        "field": "cost",
+                        "type": "quantitative",
+                        "title": attr.cost_caption,
+                        "axis": { "orient": "right" },
+                    },
+                    "tooltip": tooltip(&["count", "probability", "cost"]),
+                },
+            },
+        ]);
+        spec["resolve"] = json!({ "scale": { "y": "independent" } });
+        Ok(spec)
+    }
+
+    /// Probability of no success yet on a logarithmic axis
+    ///
+    /// Counts with a guaranteed success have no survival and are not included.
+    fn survival(
+        attr: &PlotAttribute,
+        data: &[Record],
+        annotation: &Annotation,
+    ) -> GcalcResult<Value> {
+        let survival = |probability: f32| {
+            let survival = number(1.0 - probability);
+            if survival > 0.0 {
+                Some(survival)
+            } else {
+                None
+            }
+        };
+        let values = data
+            .iter()
+            .filter_map(|record| {
+                let value = survival(record.probability_src)?;
+                Some(json!({ "count": record.count, "survival": value }))
+            })
+            .collect();
+        let y = y_encoding(
+            attr,
+            "survival",
+            &format!("1 - {}", attr.prob_caption),
+            true,
+        );
+
+        let mut layers = vec![json!({
+            "mark": {
+                "type": "line",
+                "point": true,
+                "color": hex(&attr.prob_color)?,
+                "clip": true,
+            },
+            "params": [zoom()],
+            "encoding": {
+                "x": x_encoding(attr),
+                "y": y,
+                "tooltip": tooltip(&["count", "survival"]),
+            },
+        })];
+        layers.extend(Self::annotations(attr, data, annotation, &y, survival)?);
+
+        let mut spec = Self::base(attr, values)?;
+        spec["layer"] = json!(layers);
+        Ok(spec)
+    }
+
+    /// Per-try first success probability as bars with markers
+    fn histogram(
+        attr: &PlotAttribute,
+        data: &[Record],
+        annotation: &Annotation,
+    ) -> GcalcResult<Value> {
+        let marker_color = hex(&attr.cost_color)?;
+        let values = data
+            .iter()
+            .map(|record| {
+                json!({
+                    "count": record.count,
+                    "first_success": number(record.first_success.0),
+                })
+            })
+            .collect();
+
+        let mut layers = vec![json!({
+            "mark": { "type": "bar", "color": hex(&attr.prob_color)?, "clip": true },
+            "params": [zoom()],
+            "encoding": {
+                "x": x_encoding(attr),
+                "y": y_encoding(attr, "first_success", &attr.prob_caption, false),
+                "tooltip": tooltip(&["count", "first_success"]),
+            },
+        })];
+        for (index, marker) in annotation
+            .histogram_markers(attr, data)
+            .into_iter()
+            .enumerate()
+        {
+            layers.push(Self::vertical(
+                marker.count,
+                &marker.label,
+                &marker_color,
+                // Labels are staggered so that close markers don't overlap
+                json!({ "y": 0, "dy": 12 + 14 * index as i32 }),
+            ));
+        }
+
+        let mut spec = Self::base(attr, values)?;
+        spec["layer"] = json!(layers);
+        Ok(spec)
+    }
+
+    /// Layers of a target rule, budget and pity rules and points where milestones are reached
+    ///
+    /// Probability converts a cumulative probability into a value of y encoding, while None is
+    /// not drawn.
+    fn annotations(
+        attr: &PlotAttribute,
+        data: &[Record],
+        annotation: &Annotation,
+        y: &Value,
+        probability: impl Fn(f32) -> Option<f64>,
+    ) -> GcalcResult<Vec<Value>> {
+        if !attr.annotations {
+            return Ok(vec![]);
+        }
+        let prob_color = hex(&attr.prob_color)?;
+        let cost_color = hex(&attr.cost_color)?;
+        let field = y["field"].as_str().unwrap_or_default();
+        let mut layers = vec![];
+
+        if let Some((target, value)) = annotation
+            .target
+            .and_then(|target| Some((target, probability(target)?)))
+        {
+            let mut datum = json!({ "label": format!("target {}", target) });
+            datum[field] = json!(value);
+            layers.push(json!({
+                "data": { "values": [datum] },
+                "encoding": { "y": y },
+                "layer": [
+                    { "mark": { "type": "rule", "color": prob_color, "opacity": 0.6 } },
+                    {
+                        "mark": {
+                            "type": "text",
+                            "color": prob_color,
+                            "align": "left",
+                            "baseline": "bottom",
+                            "x": 4,
+                            "dy": -4,
+                        },
+                        "encoding": { "text": { "field": "label" } },
+                    },
+                ],
+            }));
+        }
+
+        let verticals = [
+            (annotation.budget_label(data), &cost_color),
+            (annotation.pity_label(data), &"#000000".to_owned()),
+        ];
+        for (offset, (vertical, color)) in verticals.iter().enumerate() {
+            if let Some((count, label)) = vertical {
+                layers.push(Self::vertical(
+                    *count,
+                    label,
+                    color,
+                    // Staggered so that close lines don't overlap
+                    json!({ "y": "height", "dy": -10 - 14 * offset as i32 }),
+                ));
+            }
+        }
+
+        let milestones = annotation
+            .crossings(data)
+            .into_iter()
+            .filter_map(|(target, index)| {
+                let record = &data[index];
+                let mut datum = json!({
+                    "count": record.count,
+                    "label": format!("{} @ {}", target, record.count),
+                });
+                datum[field] = json!(probability(record.probability_src)?);
+                Some(datum)
+            })
+            .collect::<Vec<_>>();
+        if !milestones.is_empty() {
+            layers.push(json!({
+                "data": { "values": milestones },
+                "encoding": { "x": x_encoding(attr), "y": y },
+                "layer": [
+                    {
+                        "mark": {
+                            "type": "point",
+                            "color": prob_color,
+                            "size": 120,
+                            "strokeWidth": 2,
+                            "clip": true,
+                        },
+                    },
+                    {
+                        "mark": {
+                            "type": "text",
+                            "color": prob_color,
+                            "align": "left",
+                            "baseline": "top",
+                            "dy": 8,
+                            "clip": true,
+                        },
+                        "encoding": { "text": { "field": "label" } },
+                    },
+                ],
+            }));
+        }
+
+        Ok(layers)
+    }
+
+    /// Vertical rule at a count with a label where position places the label
+    fn vertical(count: usize, label: &str, color: &str, position: Value) -> Value {
+        let mut text = json!({ "type": "text", "color": color, "align": "left", "dx": 4 });
+        for (key, value) in position.as_object().into_iter().flatten() {
+            text[key] = value.clone();
+        }
+        json!({
+            "data": { "values": [{ "count": count, "label": label }] },
+            "encoding": { "x": { "field": "count", "type": "quantitative" } },
+            "layer": [
+                { "mark": { "type": "rule", "color": color, "opacity": 0.6, "strokeWidth": 2 } },
+                { "mark": text, "encoding": { "text": { "field": "label" } } },
+            ],
+        })
+    }
+
+    /// Top level properties with data of a chart
+    fn base(attr: &PlotAttribute, values: Vec<Value>) -> GcalcResult<Value> {
+        Ok(json!({
+            "$schema": SCHEMA,
+            "title": attr.caption,
+            "width": attr.img_size.0,
+            "height": attr.img_size.1,
+            "background": hex(&attr.background)?,
+            "config": { "font": attr.font_type },
+            "data": { "values": values },
+        }))
+    }
+}
+
+/// Count axis with a scale of an attribute
+fn x_encoding(attr: &PlotAttribute) -> Value {
+    json!({
+        "field": "count",
+        "type": "quantitative",
+        "title": "count",
+        "scale": scale(attr.log_x, attr.x_range),
+    })
+}
+
+fn y_encoding(attr: &PlotAttribute, field: &str, title: &str, log: bool) -> Value {
+    json!({
+        "field": field,
+        "type": "quantitative",
+        "title": title,
+        "scale": scale(log, attr.y_range),
+    })
+}
+
+/// Linear or logarithmic scale, which is zoomed into a range if any
+fn scale(log: bool, range: Option<(f64, f64)>) -> Value {
+    let mut scale = json!({ "type": if log { "log" } else { "linear" }, "zero": false });
+    if let Some((min, max)) = range {
+        scale["domain"] = json!([min, max]);
+    }
+    scale
+}
+
+/// Interval selection bound to scales, which zooms with a wheel and pans with a drag
+fn zoom() -> Value {
+    json!({ "name": "zoom", "select": "interval", "bind": "scales" })
+}
+
+fn tooltip(fields: &[&str]) -> Value {
+    fields
+        .iter()
+        .map(|field| json!({ "field": field, "type": "quantitative" }))
+        .collect()
+}
+
+/// Colour of an attribute as a hex code
+fn hex(color: &str) -> GcalcResult<String> {
+    let (r, g, b) = PlotAttribute::parse_color(color)?;
+    Ok(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
+/// Shortest decimal of a value, because a widened f32 has trailing digits
+fn number(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sweep::{SweepAxis, SweepParameter};
+
+    /// Records of a constant rate, where cost of each try is 10
+    fn records(rate: f32, count: usize) -> Vec<Record> {
+        (1..=count)
+            .map(|count| {
+                let survival = (1.0 - rate).powi(count as i32);
+                let first = survival / (1.0 - rate) * rate;
+                Record::new(
+                    count,
+                    1.0 - survival,
+                    String::new(),
+                    10.0 * count as f32,
+                    0.0,
+                    0.0,
+                )
+                .marginal(
+                    (rate, String::new()),
+                    (first, String::new()),
+                    (survival, String::new()),
+                )
+            })
+            .collect()
+    }
+
+    fn marks(layers: &Value) -> Vec<&str> {
+        layers
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|layer| {
+                layer["mark"]
+                    .as_str()
+                    .or_else(|| layer["mark"]["type"].as_str())
+                    .unwrap_or("layer")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn base_follows_chart_attribute() {
+        let attr = PlotAttribute::new()
+            .caption("Chart")
+            .size(640, 480)
+            .background("black");
+        let spec = VegaRenderer::records(&attr, &records(0.5, 3), &Annotation::default()).unwrap();
+        assert_eq!(spec["$schema"], SCHEMA);
+        assert_eq!(spec["title"], "Chart");
+        assert_eq!(
+            (spec["width"].clone(), spec["height"].clone()),
+            (json!(640), json!(480))
+        );
+        assert_eq!(spec["background"], "#000000");
+        assert_eq!(spec["config"]["font"], "Helvetica");
+
+        let attr = PlotAttribute::new().background("dark");
+        assert!(VegaRenderer::records(&attr, &records(0.5, 3), &Annotation::default()).is_err());
+    }
+
+    #[test]
+    fn cumulative_has_probability_and_cost_layers() {
+        let attr = PlotAttribute::new();
+        let spec = VegaRenderer::records(&attr, &records(0.5, 3), &Annotation::default()).unwrap();
+        assert_eq!(
+            spec["data"]["values"][1],
+            json!({ "count": 2, "probability": 0.75, "cost": 20.0 })
+        );
+        assert_eq!(spec["resolve"]["scale"]["y"], "independent");
+
+        let layers = spec["layer"].as_array().unwrap();
+        assert_eq!(layers.len(), 2);
+        let probability = &layers[0]["layer"][0];
+        assert_eq!(probability["mark"]["color"], "#ff0000");
+        assert_eq!(probability["params"][0]["bind"], "scales");
+        assert_eq!(probability["encoding"]["y"]["field"], "probability");
+        assert_eq!(layers[1]["encoding"]["y"]["field"], "cost");
+        assert_eq!(layers[1]["encoding"]["y"]["axis"]["orient"], "right");
+    }
+
+    #[test]
+    fn cumulative_annotations_are_layers() {
+        let annotation = Annotation {
+            target: Some(0.5),
+            milestones: vec![0.75, 0.99],
+            budget: Some(25.0),
+            pity: Some(3),
+        };
+        let data = records(0.5, 4);
+        let spec = VegaRenderer::records(&PlotAttribute::new(), &data, &annotation).unwrap();
+        let probability = &spec["layer"][0]["layer"];
+        // Line, target, budget, pity and milestones
+        assert_eq!(
+            marks(probability),
+            ["line", "layer", "layer", "layer", "layer"]
+        );
+        assert_eq!(probability[1]["data"]["values"][0]["label"], "target 0.5");
+        assert_eq!(probability[1]["data"]["values"][0]["probability"], 0.5);
+        assert_eq!(
+            probability[2]["data"]["values"][0]["label"],
+            "budget 25 @ 2"
+        );
+        assert_eq!(probability[3]["data"]["values"][0]["label"], "pity 3");
+        // Unreached milestone has no point
+        let milestones = probability[4]["data"]["values"].as_array().unwrap();
+        let labels: Vec<&str> = milestones
+            .iter()
+            .map(|m| m["label"].as_str().unwrap())
+            .collect();
+        assert_eq!(labels, ["0.75 @ 2", "0.5 @ 1"]);
+
+        let attr = PlotAttribute::new().annotations(false);
+        let spec = VegaRenderer::records(&attr, &data, &annotation).unwrap();
+        assert_eq!(marks(&spec["layer"][0]["layer"]), ["line"]);
+    }
+
+    #[test]
+    fn survival_is_logarithmic_without_certain_counts() {
+        let mut data = records(0.5, 3);
+        data[2].probability_src = 1.0;
+        let attr = PlotAttribute::new().kind(PlotKind::Survival);
+        let annotation = Annotation {
+            target: Some(1.0),
+            ..Annotation::default()
+        };
+        let spec = VegaRenderer::records(&attr, &data, &annotation).unwrap();
+        assert_eq!(
+            spec["data"]["values"],
+            json!([{ "count": 1, "survival": 0.5 }, { "count": 2, "survival": 0.25 }])
+        );
+        let line = &spec["layer"][0];
+        assert_eq!(line["encoding"]["y"]["scale"]["type"], "log");
+        assert_eq!(line["encoding"]["y"]["title"], "1 - Prob (r)");
+        // Target of certainty has no survival to draw
+        assert_eq!(marks(&spec["layer"]), ["line"]);
+    }
+
+    #[test]
+    fn histogram_has_bars_and_markers() {
+        let attr = PlotAttribute::new().kind(PlotKind::Histogram).markers(true);
+        let spec = VegaRenderer::records(&attr, &records(0.5, 4), &Annotation::default()).unwrap();
+        assert_eq!(
+            spec["data"]["values"][1],
+            json!({ "count": 2, "first_success": 0.25 })
+        );
+        let layers = &spec["layer"];
+        assert_eq!(marks(layers), ["bar", "layer", "layer"]);
+        assert_eq!(layers[1]["data"]["values"][0]["label"], "median 1");
+        assert_eq!(layers[2]["data"]["values"][0]["label"], "p90 4");
+        // Close markers are staggered
+        assert_eq!(layers[1]["layer"][1]["mark"]["dy"], 12);
+        assert_eq!(layers[2]["layer"][1]["mark"]["dy"], 26);
+    }
+
+    #[test]
+    fn scales_follow_log_axis_and_ranges() {
+        let attr = PlotAttribute::new()
+            .log_x(true)
+            .x_range(1.0, 100.0)
+            .y_range(0.2, 0.8);
+        assert_eq!(
+            x_encoding(&attr)["scale"],
+            json!({ "type": "log", "zero": false, "domain": [1.0, 100.0] })
+        );
+        assert_eq!(
+            y_encoding(&attr, "probability", "Prob", false)["scale"],
+            json!({ "type": "linear", "zero": false, "domain": [0.2, 0.8] })
+        );
+        assert_eq!(
+            x_encoding(&PlotAttribute::new())["scale"],
+            json!({ "type": "linear", "zero": false })
+        );
+    }
+
+    #[test]
+    fn heatmap_cells_are_values_of_axes() {
+        let grid = SweepGrid {
+            x: SweepAxis::new(SweepParameter::Probability, vec![0.1, 0.2]),
+            y: SweepAxis::new(SweepParameter::Pity, vec![10.0]),
+            metric: SweepMetric::Tries,
+            cells: vec![vec![7.0, f32::NAN]],
+        };
+        let spec = VegaRenderer::heatmap(&PlotAttribute::new(), &grid).unwrap();
+        assert_eq!(
+            spec["data"]["values"],
+            json!([
+                { "x": 0.1, "y": 10.0, "value": 7.0 },
+                { "x": 0.2, "y": 10.0, "value": null },
+            ])
+        );
+        assert_eq!(spec["encoding"]["x"]["title"], "probability");
+        assert_eq!(spec["encoding"]["y"]["sort"], "descending");
+        let layers = &spec["layer"];
+        assert_eq!(marks(layers), ["rect", "text"]);
+        assert_eq!(
+            layers[0]["encoding"]["color"]["scale"]["range"],
+            json!(["#0000ff", "#ff0000"])
+        );
+        assert_eq!(layers[1]["encoding"]["text"]["format"], "d");
+
+        // Labels are omitted in a large grid
+        let grid = SweepGrid {
+            x: SweepAxis::new(SweepParameter::Cost, (0..11).map(|v| v as f32).collect()),
+            y: SweepAxis::new(SweepParameter::Budget, (0..10).map(|v| v as f32).collect()),
+            metric: SweepMetric::Success,
+            cells: vec![vec![0.5; 11]; 10],
+        };
+        let spec = VegaRenderer::heatmap(&PlotAttribute::new(), &grid).unwrap();
+        assert_eq!(marks(&spec["layer"]), ["rect"]);
+    }
+
+    #[test]
+    #[cfg(feature = "plotters")]
+    fn overlay_colours_series_with_a_legend() {
+        let series = vec![
+            ("base".to_owned(), records(0.1, 2)),
+            ("boosted".to_owned(), records(0.5, 1)),
+        ];
+        let spec = VegaRenderer::overlay(&PlotAttribute::new(), &series).unwrap();
+        let names: Vec<&str> = spec["data"]["values"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value["series"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["base", "base", "boosted"]);
+        assert_eq!(spec["encoding"]["color"]["field"], "series");
+        assert_eq!(spec["mark"]["type"], "line");
+    }
+
+    #[test]
+    fn numbers_are_shortest_decimals() {
+        assert_eq!(number(0.1), 0.1);
+        assert_eq!(json!(number(0.75)).to_string(), "0.75");
+        assert_eq!(hex("white").unwrap(), "#ffffff");
+        assert_eq!(hex("#AbCdEf").unwrap(), "#abcdef");
+    }
+
+    #[test]
+    fn render_writes_pretty_json() {
+        let spec = json!({ "title": "Chart" });
+        assert_eq!(
+            VegaRenderer::render(&spec).unwrap(),
+            "{\n  \"title\": \"Chart\"\n}"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// Interval between polls of modification times
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// Files should stay unchanged for this long, because an editor can save a file in several writes
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watcher of files which polls modification times
+///
+/// Polling works the same for every platform and for files saved by replacing, which spreadsheet
+/// programs often do.
+pub(crate) struct Watcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    poll_interval: Duration,
+    debounce: Duration,
+}
+
+impl Watcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self::with_intervals(paths, POLL_INTERVAL, DEBOUNCE)
+    }
+
+    /// Watcher which polls every poll interval and waits for a debounce interval after a change
+    pub fn with_intervals(
+        paths: Vec<PathBuf>,
+        poll_interval: Duration,
+        debounce: Duration,
+    ) -> Self {
+        let mut watcher = Self {
+            files: vec![],
+            poll_interval,
+            debounce,
+        };
+        watcher.set_paths(paths);
+        watcher
+    }
+
+    /// Watch given paths instead, where a path already watched keeps its modification time
+    ///
+    /// Thus a change of a kept file which is not polled yet is still a change.
+    pub fn set_paths(&mut self, paths: Vec<PathBuf>) {
+        let previous = std::mem::take(&mut self.files);
+        self.files = paths
+            .into_iter()
+            .map(|path| {
+                let modified = match previous.iter().find(|(watched, _)| *watched == path) {
+                    Some((_, modified)) => *modified,
+                    None => Self::modified(&path),
+                };
+                (path, modified)
+            })
+            .collect();
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.iter().map(|(path, _)| path)
+    }
+
+    /// Block until files change and then stay unchanged for a debounce interval
+    ///
+    /// This returns changed files. Removed file is also a change.
+    pub fn wait(&mut self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        loop {
+            std::thread::sleep(if changed.is_empty() {
+                self.poll_interval
+            } else {
+                self.debounce
+            });
+            let current = self.poll();
+            if current.is_empty() && !changed.is_empty() {
+                return changed;
+            }
+            for path in current {
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+    }
+
+    /// Update modification times and return files of which times have changed
+    fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        for (path, modified) in self.files.iter_mut() {
+            let current = Self::modified(path);
+            if current != *modified {
+                *modified = current;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Changes are far apart from a debounce interval, thus a slow thread doesn't split them
+    const TEST_POLL: Duration = Duration::from_millis(5);
+    const TEST_DEBOUNCE: Duration = Duration::from_millis(500);
+    const TEST_WRITE: Duration = Duration::from_millis(20);
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("gcalc-watch-{}-{}", std::process::id(), name));
+        std::fs::write(&path, "0").unwrap();
+        path
+    }
+
+    fn watcher(paths: Vec<PathBuf>) -> Watcher {
+        Watcher::with_intervals(paths, TEST_POLL, TEST_DEBOUNCE)
+    }
+
+    // Removal and creation are changes regardless of a resolution of modification times
+    #[test]
+    fn rapid_changes_are_debounced_into_a_change() {
+        let (first, second) = (temp_file("first"), temp_file("second"));
+        let mut watcher = watcher(vec![first.clone(), second.clone()]);
+
+        let changes = {
+            let (first, second) = (first.clone(), second.clone());
+            std::thread::spawn(move || {
+                std::fs::remove_file(&first).unwrap();
+                std::thread::sleep(TEST_WRITE);
+                std::fs::remove_file(&second).unwrap();
+                std::thread::sleep(TEST_WRITE);
+                std::fs::write(&first, "1").unwrap();
+            })
+        };
+        let changed = watcher.wait();
+        changes.join().unwrap();
+
+        assert_eq!(changed, [first.clone(), second]);
+        // Every change was seen before a wait returned
+        assert!(watcher.poll().is_empty());
+        std::fs::remove_file(first).unwrap();
+    }
+
+    #[test]
+    fn removed_file_is_a_change() {
+        let path = temp_file("removed");
+        let mut watcher = watcher(vec![path.clone()]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(watcher.wait(), [path]);
+    }
+
+    #[test]
+    fn new_paths_keep_modification_times_of_watched_files() {
+        let (kept, dropped, added) = (temp_file("kept"), temp_file("dropped"), temp_file("added"));
+        let mut watcher = watcher(vec![kept.clone(), dropped.clone()]);
+        std::fs::remove_file(&kept).unwrap();
+        std::fs::remove_file(&dropped).unwrap();
+
+        watcher.set_paths(vec![kept.clone(), added.clone()]);
+        let paths: Vec<&PathBuf> = watcher.paths().collect();
+        assert_eq!(paths, [&kept, &added]);
+        // Change before paths are set is still a change, while a new file is not changed
+        assert_eq!(watcher.poll(), [kept]);
+
+        std::fs::remove_file(&added).unwrap();
+        assert_eq!(watcher.poll(), [added]);
+        assert!(watcher.poll().is_empty());
+    }
+}